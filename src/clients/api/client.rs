@@ -1,36 +1,70 @@
-use super::{Config, PromptQueryParams};
+use super::{Config, DecisionBody, DraftsQueryParams, PromptQueryParams};
 use crate::{
-    core::{Agent, Character},
+    core::{
+        logging, Agent, ApprovalQueue, AuditConfig, AuditQuery, Character, Config as RootConfig,
+        ConversationMemory, KnowledgeBase, Shutdown, Tasks,
+    },
     providers::completion::CompletionResponseEnum,
 };
 use actix_web::{web, App, HttpServer};
 use log::info;
 use std::sync::Arc;
 
-pub struct Client<CM>
+pub struct Client<CM, EM>
 where
     CM: rig::completion::CompletionModel<Response = CompletionResponseEnum> + 'static,
+    EM: rig::embeddings::EmbeddingModel + 'static,
 {
     pub agent: Agent<CM>,
     pub character: Character,
     pub config: Config,
+    pub memory: Option<ConversationMemory<EM>>,
+    pub knowledge: Option<KnowledgeBase<EM>>,
+    // every client's drafts are decided on here
+    pub approval: Option<ApprovalQueue>,
+    // searched by `/audit`, whatever every client sent
+    pub audit: AuditConfig,
 }
 
-impl<CM> Client<CM>
+impl<CM, EM> Client<CM, EM>
 where
     CM: rig::completion::CompletionModel<Response = CompletionResponseEnum> + 'static,
+    EM: rig::embeddings::EmbeddingModel + 'static,
 {
-    pub fn new(character: Character, completion_model: CM, config: Config) -> Self {
+    pub async fn new(
+        character: Character,
+        completion_model: CM,
+        embedding_model: EM,
+        config: Config,
+        root_config: RootConfig,
+        knowledge: Option<KnowledgeBase<EM>>,
+    ) -> Self {
+        let memory =
+            ConversationMemory::new("api", &config.memory, &root_config, embedding_model).await;
+
         Client {
             character,
             agent: Agent::new(completion_model),
             config,
+            memory,
+            knowledge,
+            approval: ApprovalQueue::new(&root_config.approval),
+            audit: root_config.audit.clone(),
         }
     }
 
-    pub async fn start(self: Arc<Self>) {
+    pub async fn start(self: Arc<Self>, shutdown: Shutdown) {
         info!("[API] Started");
 
+        let mut tasks = Tasks::default();
+        if let Some(reflection) = self
+            .memory
+            .as_ref()
+            .and_then(|memory| memory.start_reflection(self.agent.clone()))
+        {
+            tasks.push(reflection);
+        }
+
         // fetch api port
         let port = self.config.port;
 
@@ -43,16 +77,56 @@ where
                         web::get().to(
                             |handler: web::Data<Arc<Self>>,
                              query: web::Query<PromptQueryParams>| async move {
-                                handler.prompt_route_get(query).await
+                                logging::correlated("api", handler.prompt_route_get(query)).await
+                            },
+                        ),
+                    )
+                    .route(
+                        "/drafts",
+                        web::get().to(
+                            |handler: web::Data<Arc<Self>>,
+                             query: web::Query<DraftsQueryParams>| async move {
+                                handler.drafts_route_get(query).await
+                            },
+                        ),
+                    )
+                    .route(
+                        "/audit",
+                        web::get().to(
+                            |handler: web::Data<Arc<Self>>,
+                             query: web::Query<AuditQuery>| async move {
+                                handler.audit_route_get(query).await
+                            },
+                        ),
+                    )
+                    .route(
+                        "/drafts/{id}/{action}",
+                        web::post().to(
+                            |handler: web::Data<Arc<Self>>,
+                             path: web::Path<(String, String)>,
+                             body: Option<web::Json<DecisionBody>>| async move {
+                                handler.draft_route_post(path, body).await
                             },
                         ),
                     )
         })
         .bind(("127.0.0.1", port)) // Use api_port from config
-        .expect("Failed to bind server");
+        .expect("Failed to bind server")
+        .disable_signals()
+        .run();
+
+        // finish requests in flight on shutdown
+        let handle = server.handle();
+        tasks.push(tokio::spawn(async move {
+            shutdown.wait().await;
+            handle.stop(true).await;
+        }));
 
         // start server
         info!("[API] Started http server on 127.0.0.1:{}", port);
-        let _ = server.run().await;
+        let _ = server.await;
+
+        drop(tasks);
+        info!("[API] Stopped");
     }
 }
@@ -1,6 +1,10 @@
+use crate::core::ConversationMemoryConfig;
 use serde::Deserialize;
 
 #[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     pub port: u16,
+    #[serde(default)]
+    pub memory: ConversationMemoryConfig,
 }
@@ -1,5 +1,8 @@
 use super::Client;
-use crate::providers::completion::CompletionResponseEnum;
+use crate::{
+    core::{audit, metrics, AuditQuery, ConversationMemory, DraftStatus, KnowledgeBase},
+    providers::completion::CompletionResponseEnum,
+};
 use actix_web::{web, HttpResponse, Responder};
 use log::{error, info};
 use std::result::Result::Ok;
@@ -7,21 +10,126 @@ use std::result::Result::Ok;
 #[derive(serde::Deserialize)]
 pub struct PromptQueryParams {
     input: String,
+    // callers that pass a user id get long-term conversation memory
+    user: Option<String>,
 }
 
-impl<CM> Client<CM>
+#[derive(serde::Deserialize)]
+pub struct DraftsQueryParams {
+    status: Option<DraftStatus>,
+}
+
+#[derive(serde::Deserialize, Default)]
+pub struct DecisionBody {
+    // replaces the draft's text, required by `edit`
+    text: Option<String>,
+    reason: Option<String>,
+    by: Option<String>,
+}
+
+impl<CM, EM> Client<CM, EM>
 where
     CM: rig::completion::CompletionModel<Response = CompletionResponseEnum> + 'static,
+    EM: rig::embeddings::EmbeddingModel + 'static,
 {
+    const CHANNEL: &str = "api";
+
+    pub async fn drafts_route_get(&self, query: web::Query<DraftsQueryParams>) -> impl Responder {
+        let Some(approval) = &self.approval else {
+            return HttpResponse::NotFound().json(serde_json::json!({
+                "error": "approval is disabled",
+            }));
+        };
+
+        match approval.list(query.status) {
+            Ok(drafts) => HttpResponse::Ok().json(drafts),
+            Err(err) => {
+                error!("[API][APPROVAL] Error: {}", err);
+                HttpResponse::InternalServerError().json(serde_json::json!({
+                    "error": err.to_string(),
+                }))
+            }
+        }
+    }
+
+    // e.g. `/audit?platform=twitter&text=launch&since=2025-01-31T00:00:00Z`, newest first
+    pub async fn audit_route_get(&self, query: web::Query<AuditQuery>) -> impl Responder {
+        match audit::search(&self.audit, &query) {
+            Ok(records) => HttpResponse::Ok().json(records),
+            Err(err) => {
+                error!("[API][AUDIT] Error: {}", err);
+                HttpResponse::InternalServerError().json(serde_json::json!({
+                    "error": err.to_string(),
+                }))
+            }
+        }
+    }
+
+    // `action` is approve, edit or reject
+    pub async fn draft_route_post(
+        &self,
+        path: web::Path<(String, String)>,
+        body: Option<web::Json<DecisionBody>>,
+    ) -> impl Responder {
+        let Some(approval) = &self.approval else {
+            return HttpResponse::NotFound().json(serde_json::json!({
+                "error": "approval is disabled",
+            }));
+        };
+        let (id, action) = path.into_inner();
+        let body = body.map(web::Json::into_inner).unwrap_or_default();
+        let by = body.by.unwrap_or_else(|| "api".to_string());
+
+        let result = match (action.as_str(), body.text) {
+            ("approve", _) => approval.approve(&id, &by),
+            ("edit", Some(text)) => approval.edit(&id, &text, &by),
+            ("edit", None) => Err(anyhow::anyhow!("edit needs a text")),
+            ("reject", _) => approval.reject(&id, &by, body.reason),
+            _ => {
+                return HttpResponse::NotFound().json(serde_json::json!({
+                    "error": format!("unknown action {}", action),
+                }))
+            }
+        };
+
+        match result {
+            Ok(draft) => {
+                info!("[API][APPROVAL] {} {} draft {}", by, action, draft.id);
+                HttpResponse::Ok().json(draft)
+            }
+            Err(err) => HttpResponse::BadRequest().json(serde_json::json!({
+                "error": err.to_string(),
+            })),
+        }
+    }
+
     pub async fn prompt_route_get(&self, query: web::Query<PromptQueryParams>) -> impl Responder {
+        // Recall past conversations with the caller
+        let memories = match (&self.memory, &query.user) {
+            (Some(memory), Some(user)) => memory.recall(user, Self::CHANNEL, &query.input).await,
+            _ => Vec::new(),
+        };
+
+        // Retrieve relevant knowledge
+        let knowledge = match &self.knowledge {
+            Some(knowledge) => knowledge.retrieve(&query.input).await,
+            None => Vec::new(),
+        };
+
         // Generate the prompt
         let prompt = format!(
             "{}
+
+            {}
+
+            {}
             
             <userInput>
             {}
             </userInput>",
             self.character.generate_prompt_info(),
+            KnowledgeBase::<EM>::prompt_block(&knowledge),
+            ConversationMemory::<EM>::prompt_block(&memories),
             query.input
         );
 
@@ -45,6 +153,22 @@ where
                 // Log the response
                 info!("[API][AGENT]({}): {}", self.character.alias, agent_content);
 
+                // Remember the exchange
+                if let (Some(memory), Some(user)) = (&self.memory, &query.user) {
+                    memory
+                        .remember(
+                            &self.agent,
+                            user,
+                            user,
+                            Self::CHANNEL,
+                            &query.input,
+                            &agent_content,
+                        )
+                        .await;
+                }
+
+                metrics::replied("api");
+
                 // Return the agent's content as a JSON response
                 HttpResponse::Ok().json(serde_json::json!({
                     "character": self.character.alias,
@@ -54,6 +178,7 @@ where
             Err(err) => {
                 // Log the error and return an appropriate HTTP error response
                 error!("[API] Error: {}", err);
+                metrics::failed("api", "completion", &err);
                 return HttpResponse::InternalServerError().json(serde_json::json!({
                     "error": err.to_string(),
                 }));
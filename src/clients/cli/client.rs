@@ -1,38 +1,68 @@
+use super::Config;
 use crate::{
-    core::{Agent, Character},
+    core::{
+        Agent, Character, Config as RootConfig, ConversationBuffer, ConversationMemory,
+        KnowledgeBase, Tasks,
+    },
     providers::completion::CompletionResponseEnum,
 };
 use log::{error, info};
-use rig::completion::Message;
-use std::collections::VecDeque;
 use std::io::{self, Write};
 
-pub struct Client<CM>
+pub struct Client<CM, EM>
 where
-    CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
+    CM: rig::completion::CompletionModel<Response = CompletionResponseEnum> + 'static,
+    EM: rig::embeddings::EmbeddingModel + 'static,
 {
     agent: Agent<CM>,
     character: Character,
-    history: VecDeque<Message>,
+    history: ConversationBuffer,
+    memory: Option<ConversationMemory<EM>>,
+    knowledge: Option<KnowledgeBase<EM>>,
 }
 
-impl<CM> Client<CM>
+impl<CM, EM> Client<CM, EM>
 where
-    CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
+    CM: rig::completion::CompletionModel<Response = CompletionResponseEnum> + 'static,
+    EM: rig::embeddings::EmbeddingModel + 'static,
 {
-    const HISTORY_SIZE: usize = 10;
+    const HISTORY_TOKENS: usize = 2000;
+    // the cli only ever talks to whoever runs it
+    const USER: &str = "cli";
+
+    pub async fn new(
+        character: Character,
+        completion_model: CM,
+        embedding_model: EM,
+        config: Config,
+        root_config: RootConfig,
+        knowledge: Option<KnowledgeBase<EM>>,
+    ) -> Self {
+        let memory =
+            ConversationMemory::new("cli", &config.memory, &root_config, embedding_model).await;
 
-    pub fn new(character: Character, completion_model: CM) -> Self {
         Client {
             character,
             agent: Agent::new(completion_model),
-            history: VecDeque::with_capacity(Self::HISTORY_SIZE),
+            history: ConversationBuffer::new(Self::HISTORY_TOKENS),
+            memory,
+            knowledge,
         }
     }
 
     pub async fn start(&mut self) {
         info!("[CLI] Started (type 'exit' to quit)");
 
+        // reflection stops when `tasks` is dropped on the way out
+        let mut tasks = Tasks::default();
+        if let Some(reflection) = self
+            .memory
+            .as_ref()
+            .and_then(|memory| memory.start_reflection(self.agent.clone()))
+        {
+            tasks.push(reflection);
+        }
+
         loop {
             // prompt user
             print!("You: ");
@@ -57,15 +87,33 @@ where
                 break;
             }
 
+            // recall past conversations
+            let memories = match &self.memory {
+                Some(memory) => memory.recall(Self::USER, Self::USER, user_input).await,
+                None => Vec::new(),
+            };
+
+            // retrieve relevant knowledge
+            let knowledge = match &self.knowledge {
+                Some(knowledge) => knowledge.retrieve(user_input).await,
+                None => Vec::new(),
+            };
+
             // craft prompt
             let prompt = format!(
                 "{}
+
+                {}
+
+                {}
                 
                 <userInput>
                 {}
                 </userInput>
                 ",
                 self.character.generate_prompt_info(),
+                KnowledgeBase::<EM>::prompt_block(&knowledge),
+                ConversationMemory::<EM>::prompt_block(&memories),
                 user_input
             );
 
@@ -75,30 +123,33 @@ where
                 .completion_model
                 .completion_request(&prompt)
                 .preamble(format!(
-                    "Your name: {}. Your Bio: {}. Use <characterInfo> to decide your style of speaking and reasoning of response to <userInput>. Don't allow messages to be too similar to previous ones.",
-                    self.character.alias, self.character.bio
+                    "Your name: {}. Your Bio: {}. Use <characterInfo> to decide your style of speaking and reasoning of response to <userInput>. Don't allow messages to be too similar to previous ones.{}",
+                    self.character.alias, self.character.bio, self.history.summary_block()
                 ))
-                .messages(self.history.iter().rev().cloned().collect())
+                .messages(self.history.messages())
                 .build();
             match self.agent.completion(request).await {
                 Ok(response) => {
                     let agent_content = self.agent.response_extract_content(response);
                     info!("[CLI][AGENT]: {}", agent_content);
-                    self.push_history("user".to_string(), user_input.to_string());
-                    self.push_history("assistant".to_string(), agent_content);
+                    if let Some(memory) = &self.memory {
+                        memory
+                            .remember(
+                                &self.agent,
+                                Self::USER,
+                                Self::USER,
+                                Self::USER,
+                                user_input,
+                                &agent_content,
+                            )
+                            .await;
+                    }
+                    self.history.push("user", user_input);
+                    self.history.push("assistant", agent_content);
+                    self.history.compact(&self.agent).await;
                 }
                 Err(err) => error!("[AGENT] Error: {}", err),
             }
         }
     }
-
-    fn push_history(&mut self, role: String, content: String) {
-        if self.history.len() >= Self::HISTORY_SIZE {
-            self.history.pop_back();
-        }
-        self.history.push_front(Message {
-            role: role,
-            content: content,
-        });
-    }
 }
@@ -0,0 +1,31 @@
+use crate::core::ConversationMemoryConfig;
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(from = "ConfigRaw")]
+pub struct Config {
+    pub memory: ConversationMemoryConfig,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+enum ConfigRaw {
+    // `"cli": true` from older configs
+    #[allow(dead_code)]
+    Enabled(bool),
+    Config {
+        #[serde(default)]
+        memory: ConversationMemoryConfig,
+    },
+}
+
+impl From<ConfigRaw> for Config {
+    fn from(raw: ConfigRaw) -> Self {
+        match raw {
+            ConfigRaw::Enabled(_) => Config {
+                memory: ConversationMemoryConfig::default(),
+            },
+            ConfigRaw::Config { memory } => Config { memory },
+        }
+    }
+}
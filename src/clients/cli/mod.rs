@@ -1,3 +1,5 @@
 pub mod client;
+pub mod config;
 
 pub use client::Client;
+pub use config::*;
@@ -1,56 +1,119 @@
 use super::{handler::Handler, Config};
 use crate::{
-    core::{Agent, Character},
+    core::{
+        Agent, ApprovalQueue, AuditLog, Character, Config as RootConfig, ConversationMemory,
+        KnowledgeBase, Shutdown, Tasks,
+    },
     providers::completion::CompletionResponseEnum,
 };
 use log::{error, info};
 use serenity::{prelude::Client as DiscordClient, prelude::*};
-use std::env;
 use tokio::time::sleep;
 
-pub struct Client<CM>
+// how often approved drafts are looked for
+const DELIVER_SECS: u64 = 60;
+
+pub struct Client<CM, EM>
 where
     CM: rig::completion::CompletionModel<Response = CompletionResponseEnum> + 'static,
+    EM: rig::embeddings::EmbeddingModel + 'static,
 {
     pub character: Character,
     pub completion_model: CM,
     pub config: Config,
+    pub memory: Option<ConversationMemory<EM>>,
+    pub knowledge: Option<KnowledgeBase<EM>>,
+    pub approval: Option<ApprovalQueue>,
+    pub audit: Option<AuditLog>,
+    token: String,
 }
 
-impl<CM> Client<CM>
+impl<CM, EM> Client<CM, EM>
 where
     CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
+    EM: rig::embeddings::EmbeddingModel,
 {
-    pub async fn new(character: Character, completion_model: CM, config: Config) -> Self {
+    pub async fn new(
+        character: Character,
+        completion_model: CM,
+        embedding_model: EM,
+        config: Config,
+        root_config: RootConfig,
+        knowledge: Option<KnowledgeBase<EM>>,
+    ) -> Self {
+        let memory =
+            ConversationMemory::new("discord", &config.memory, &root_config, embedding_model).await;
+
         Self {
             character,
             completion_model,
             config,
+            memory,
+            knowledge,
+            approval: ApprovalQueue::new(&root_config.approval),
+            audit: AuditLog::new(&root_config),
+            token: root_config.providers.discord.token().to_string(),
         }
     }
 
-    pub async fn start(&self) {
+    // runs until `shutdown`, then disconnects every shard
+    pub async fn start(&self, shutdown: Shutdown) {
+        let mut tasks = Tasks::default();
+        if let Some(reflection) = self
+            .memory
+            .as_ref()
+            .and_then(|memory| memory.start_reflection(Agent::new(self.completion_model.clone())))
+        {
+            tasks.push(reflection);
+        }
+
         let intents = GatewayIntents::GUILD_MESSAGES
             | GatewayIntents::DIRECT_MESSAGES
             | GatewayIntents::MESSAGE_CONTENT;
 
-        let mut client = DiscordClient::builder(
-            env::var("DISCORD_TOKEN").expect("DISCORD_TOKEN not set"),
-            intents,
-        )
-        .event_handler(Handler::new(
+        let handler = Handler::new(
             self.character.clone(),
             Agent::new(self.completion_model.clone()),
             self.config.clone(),
-        ))
-        .await
-        .expect("[DISCORD] Failed to create client");
+            self.memory.clone(),
+            self.knowledge.clone(),
+            self.approval.clone(),
+            self.audit.clone(),
+        );
+
+        let mut client = DiscordClient::builder(&self.token, intents)
+            .event_handler(handler.clone())
+            .await
+            .expect("[DISCORD] Failed to create client");
+
+        // approved replies are sent outside of the event loop
+        if handler.gated() {
+            let http = client.http.clone();
+            tasks.push(tokio::spawn(async move {
+                loop {
+                    sleep(std::time::Duration::from_secs(DELIVER_SECS)).await;
+                    handler.deliver(&http).await;
+                }
+            }));
+        }
 
         info!("[DISCORD] Starting client with 15s delay...");
+        tokio::select! {
+            _ = sleep(std::time::Duration::from_secs(15)) => {}
+            _ = shutdown.wait() => return,
+        }
+
+        let shard_manager = client.shard_manager.clone();
+        tasks.push(tokio::spawn(async move {
+            shutdown.wait().await;
+            shard_manager.shutdown_all().await;
+        }));
 
-        sleep(std::time::Duration::from_secs(15)).await;
         if let Err(why) = client.start().await {
             error!("[DISCORD] Client error: {why:?}");
         }
+
+        drop(tasks);
+        info!("[DISCORD] Stopped");
     }
 }
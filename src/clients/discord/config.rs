@@ -1,3 +1,4 @@
+use crate::core::ConversationMemoryConfig;
 use serde::Deserialize;
 
 #[derive(Deserialize, Debug, Clone)]
@@ -6,14 +7,18 @@ pub struct Config {
     pub surrounding_messages: u8,
     pub selection_rate: f32,
     pub debug: bool,
+    pub memory: ConversationMemoryConfig,
 }
 
 #[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
 struct ConfigRaw {
     pub surrounding_messages: u8,
     pub selection_rate: f32,
     #[serde(default)]
     pub debug: bool,
+    #[serde(default)]
+    pub memory: ConversationMemoryConfig,
 }
 
 impl TryFrom<ConfigRaw> for Config {
@@ -24,6 +29,7 @@ impl TryFrom<ConfigRaw> for Config {
             surrounding_messages: raw.surrounding_messages,
             selection_rate: raw.selection_rate,
             debug: raw.debug,
+            memory: raw.memory,
         };
 
         if raw.selection_rate <= 0.0 || raw.selection_rate > 1.0 {
@@ -1,41 +1,173 @@
 use super::Config;
 use crate::{
     core::{
-        Agent, Character,
+        logging, metrics, Agent, ApprovalQueue, AuditLog, Character,
         CharacterTrait::{Adjectives, Inspirations, Lore, Styles},
+        ConversationBuffer, ConversationMemory, Draft, DraftKind, Generation, KnowledgeBase,
+        Publication,
     },
-    providers::completion::CompletionResponseEnum,
+    providers::completion::{budget::take_within, CompletionResponseEnum},
 };
 use log::{debug, error, info};
-use rig::completion::Message;
+use serde::{Deserialize, Serialize};
 use serenity::{
-    all::GetMessages, async_trait, model::channel::Message as ChannelMessage, prelude::*,
+    all::{ChannelId, CreateMessage, GetMessages, Http, MessageId},
+    async_trait,
+    model::channel::Message as ChannelMessage,
+    prelude::*,
 };
-use std::{collections::VecDeque, sync::Arc};
+use std::sync::Arc;
 use tokio::sync::Mutex;
 
-pub struct Handler<CM>
+// where an approved reply goes, stored with the draft
+#[derive(Serialize, Deserialize)]
+struct ReplyTarget {
+    channel_id: u64,
+    message_id: u64,
+    // author id, conversation memory is kept per user
+    user: String,
+    author: String,
+    message: String,
+}
+
+#[derive(Clone)]
+pub struct Handler<CM, EM>
 where
     CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
+    EM: rig::embeddings::EmbeddingModel,
 {
     agent: Agent<CM>,
     character: Character,
     config: Config,
-    history: Arc<Mutex<VecDeque<Message>>>,
+    history: Arc<Mutex<ConversationBuffer>>,
+    memory: Option<ConversationMemory<EM>>,
+    knowledge: Option<KnowledgeBase<EM>>,
+    approval: Option<ApprovalQueue>,
+    audit: Option<AuditLog>,
 }
 
-impl<CM> Handler<CM>
+impl<CM, EM> Handler<CM, EM>
 where
     CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
+    EM: rig::embeddings::EmbeddingModel,
 {
-    const HISTORY_SIZE: usize = 10;
+    const HISTORY_TOKENS: usize = 1500;
+    const SURROUNDING_TOKENS: usize = 1000;
 
-    pub fn new(character: Character, agent: Agent<CM>, config: Config) -> Self {
+    pub fn new(
+        character: Character,
+        agent: Agent<CM>,
+        config: Config,
+        memory: Option<ConversationMemory<EM>>,
+        knowledge: Option<KnowledgeBase<EM>>,
+        approval: Option<ApprovalQueue>,
+        audit: Option<AuditLog>,
+    ) -> Self {
         Self {
             character,
             agent,
             config,
-            history: Arc::new(Mutex::new(VecDeque::new())),
+            history: Arc::new(Mutex::new(ConversationBuffer::new(Self::HISTORY_TOKENS))),
+            memory,
+            knowledge,
+            approval: approval.filter(|approval| approval.gates("discord")),
+            audit,
+        }
+    }
+
+    pub fn gated(&self) -> bool {
+        self.approval.is_some()
+    }
+
+    // sends the replies an operator approved
+    pub async fn deliver(&self, http: &Http) {
+        let Some(approval) = &self.approval else {
+            return;
+        };
+        let drafts = match approval.approved("discord") {
+            Ok(drafts) => drafts,
+            Err(e) => {
+                error!("[DISCORD][APPROVAL] Failed to read drafts: {}", e);
+                return;
+            }
+        };
+
+        for draft in drafts {
+            let target = match serde_json::from_value::<ReplyTarget>(draft.target.clone()) {
+                Ok(target) => target,
+                Err(e) => {
+                    error!("[DISCORD][APPROVAL] Invalid draft {}: {}", draft.id, e);
+                    continue;
+                }
+            };
+            let channel_id = ChannelId::new(target.channel_id);
+            let result = channel_id
+                .send_message(
+                    http,
+                    CreateMessage::new()
+                        .content(&draft.text)
+                        .reference_message((channel_id, MessageId::new(target.message_id))),
+                )
+                .await;
+            match &result {
+                Ok(message) => {
+                    info!("[DISCORD][APPROVAL] Sent draft {}", draft.id);
+                    metrics::replied("discord");
+                    self.audit(
+                        &draft.text,
+                        message.id.to_string(),
+                        target.message_id.to_string(),
+                        draft.generation.as_ref(),
+                        Some(&draft),
+                    );
+                    self.remember_reply(
+                        &target.user,
+                        &channel_id.to_string(),
+                        &target.author,
+                        &target.message,
+                        draft.text.clone(),
+                    )
+                    .await;
+                }
+                Err(e) => {
+                    error!(
+                        "[DISCORD][APPROVAL] Failed to send draft {}: {}",
+                        draft.id, e
+                    );
+                    metrics::failed("discord", "deliver", e);
+                }
+            }
+            if let Err(e) =
+                approval.delivered(&draft.id, result.map(|_| ()).map_err(|e| e.to_string()))
+            {
+                error!(
+                    "[DISCORD][APPROVAL] Failed to record draft {}: {}",
+                    draft.id, e
+                );
+            }
+        }
+    }
+
+    // records a sent reply in the audit log
+    fn audit(
+        &self,
+        content: &str,
+        message_id: String,
+        target_id: String,
+        generation: Option<&Generation>,
+        draft: Option<&Draft>,
+    ) {
+        if let Some(audit) = &self.audit {
+            audit.record(Publication {
+                platform: "discord",
+                kind: DraftKind::Reply,
+                message_id: Some(message_id),
+                target_id: Some(target_id),
+                content,
+                character: &self.character,
+                generation,
+                draft,
+            });
         }
     }
 
@@ -44,6 +176,8 @@ where
         alias: String,
         message: String,
         surrounding_messages: Vec<String>,
+        memories: String,
+        knowledge: String,
     ) -> String {
         format!(
             r"<characterInfo>
@@ -67,6 +201,10 @@ where
             </inspirations>
             </characterInfo>
 
+            {knowledge}
+
+            {memories}
+
             <surroundingMessages>
             {surrounding_messages}
             </surroundingMessages>
@@ -101,30 +239,35 @@ where
         )
     }
 
-    async fn push_history(&self, role: String, content: String) {
-        let mut history = self.history.lock().await;
-
-        if history.len() >= Self::HISTORY_SIZE {
-            history.pop_back();
+    // keeps a sent reply in the conversation memory and the history
+    async fn remember_reply(
+        &self,
+        user: &str,
+        channel: &str,
+        author: &str,
+        message: &str,
+        reply: String,
+    ) {
+        if let Some(memory) = &self.memory {
+            memory
+                .remember(&self.agent, user, author, channel, message, &reply)
+                .await;
         }
-        history.push_front(Message {
-            role: role,
-            content: content,
-        });
-    }
+        let compaction = {
+            let mut history = self.history.lock().await;
+            history.push("user", format!("@{}: {}", author, message));
+            history.push("assistant", reply);
+            history.start_compaction()
+        };
 
-    async fn fetch_history(&self) -> VecDeque<Message> {
-        let history = self.history.lock().await;
-        history.clone()
+        // summarized without the lock so other replies are not held up by the model
+        if let Some(mut compaction) = compaction {
+            compaction.summarize(&self.agent).await;
+            self.history.lock().await.finish_compaction(compaction);
+        }
     }
-}
 
-#[async_trait]
-impl<CM> EventHandler for Handler<CM>
-where
-    CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
-{
-    async fn message(&self, ctx: Context, msg: ChannelMessage) {
+    async fn handle_message(&self, ctx: Context, msg: ChannelMessage) {
         // Skip messages from the bot itself or empty messages
         if msg.author.id == ctx.cache.current_user().id || msg.content.len() == 0 {
             return;
@@ -161,27 +304,50 @@ where
 
             let alias = ctx.cache.current_user().name.clone();
 
+            // Recall past conversations with the author
+            let user = msg.author.id.to_string();
+            let channel = msg.channel_id.to_string();
+            let memories = match &self.memory {
+                Some(memory) => memory.recall(&user, &channel, &msg.content).await,
+                None => Vec::new(),
+            };
+
+            // Retrieve relevant knowledge
+            let knowledge = match &self.knowledge {
+                Some(knowledge) => knowledge.retrieve(&msg.content).await,
+                None => Vec::new(),
+            };
+
             // Generate post prompt
             let prompt = self.generate_reply_prompt(
                 ctx.cache.current_user().name.clone(),
                 msg.content.clone(),
-                messages,
+                take_within(messages, Self::SURROUNDING_TOKENS),
+                ConversationMemory::<EM>::prompt_block(&memories),
+                KnowledgeBase::<EM>::prompt_block(&knowledge),
             );
             debug!("[DISCORD][HANDLER] Generated prompt:\n{}", prompt);
 
-            let history = self.fetch_history().await;
+            let history = self.history.lock().await.clone();
+
+            let preamble = format!(
+                "Your name: {}. Your Bio: {}. Use <characterInfo> and <surroundingMessages> to generate a Discord message reply to <message> as @{alias} the Discord Bot. Don't make your responses start like your previous ones. You MUST follow ALL the <rules>.{}",
+                self.character.alias, self.character.bio, history.summary_block()
+            );
+            let generation = Generation {
+                prompt,
+                preamble,
+                media: Vec::new(),
+            };
 
             // Build the request for the completion model
             let request = self
-            .agent
-            .completion_model
-            .completion_request(&prompt)
-            .preamble(format!(
-                "Your name: {}. Your Bio: {}. Use <characterInfo> and <surroundingMessages> to generate a Discord message reply to <message> as @{alias} the Discord Bot. Don't make your responses start like your previous ones. You MUST follow ALL the <rules>.",
-                self.character.alias, self.character.bio
-            ))
-            .messages(history.iter().rev().cloned().collect())
-            .build();
+                .agent
+                .completion_model
+                .completion_request(&generation.prompt)
+                .preamble(generation.preamble.clone())
+                .messages(history.messages())
+                .build();
 
             match self.agent.completion(request).await {
                 Ok(response) => {
@@ -190,26 +356,79 @@ where
                     if !agent_content.is_empty() {
                         if self.config.debug {
                             info!("[DISCORD][DEBUG] Would have posted: {}", agent_content);
+                        } else if let Some(approval) = &self.approval {
+                            let target = ReplyTarget {
+                                channel_id: msg.channel_id.get(),
+                                message_id: msg.id.get(),
+                                user: user.clone(),
+                                author: msg.author.name.clone(),
+                                message: msg.content.clone(),
+                            };
+                            match approval.submit(
+                                "discord",
+                                DraftKind::Reply,
+                                &agent_content,
+                                Some(format!("@{}: {}", msg.author.name, msg.content)),
+                                serde_json::to_value(target).unwrap_or_default(),
+                                Some(generation),
+                            ) {
+                                Ok(draft) => {
+                                    info!("[DISCORD][APPROVAL] Queued draft {}", draft.id)
+                                }
+                                Err(e) => {
+                                    error!("[DISCORD][APPROVAL] Failed to queue draft: {}", e)
+                                }
+                            }
                         } else {
                             match msg.reply(&ctx.http, agent_content.clone()).await {
-                                Ok(_) => {
+                                Ok(reply) => {
                                     info!("[DISCORD][HANDLER] Replied to selected message");
-                                    self.push_history("user".to_string(), prompt).await;
-                                    self.push_history("assistant".to_string(), agent_content)
-                                        .await;
+                                    metrics::replied("discord");
+                                    self.audit(
+                                        &agent_content,
+                                        reply.id.to_string(),
+                                        msg.id.to_string(),
+                                        Some(&generation),
+                                        None,
+                                    );
+                                    self.remember_reply(
+                                        &user,
+                                        &channel,
+                                        &msg.author.name,
+                                        &msg.content,
+                                        agent_content,
+                                    )
+                                    .await;
+                                }
+                                Err(e) => {
+                                    error!(
+                                        "[DISCORD][HANDLER] Failed to reply to selected message: {e}"
+                                    );
+                                    metrics::failed("discord", "reply", e);
                                 }
-                                Err(e) => error!(
-                                    "[DISCORD][HANDLER] Failed to reply to selected message: {e}"
-                                ),
                             };
                         }
                     }
                 }
-                Err(err) => error!(
-                    "[DISCORD][AGENT] Failed to generate completion: {}",
-                    err.to_string()
-                ),
+                Err(err) => {
+                    error!(
+                        "[DISCORD][AGENT] Failed to generate completion: {}",
+                        err.to_string()
+                    );
+                    metrics::failed("discord", "completion", err);
+                }
             }
         }
     }
 }
+
+#[async_trait]
+impl<CM, EM> EventHandler for Handler<CM, EM>
+where
+    CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
+    EM: rig::embeddings::EmbeddingModel,
+{
+    async fn message(&self, ctx: Context, msg: ChannelMessage) {
+        logging::correlated("discord", self.handle_message(ctx, msg)).await
+    }
+}
@@ -1,23 +1,25 @@
-pub mod api;
-pub mod cli;
-pub mod dashboard;
-pub mod discord;
-pub mod storytelling;
-pub mod telegram;
-pub mod truth;
-pub mod twitter;
-
-pub use api::Client as ApiClient;
-pub use api::Config as ApiConfig;
-pub use cli::Client as CliClient;
-pub use dashboard::Client as DashboardClient;
-pub use discord::Client as DiscordClient;
-pub use discord::Config as DiscordConfig;
-pub use storytelling::Client as StoryTellingClient;
-pub use storytelling::Config as StorytellingConfig;
-pub use telegram::Client as TelegramClient;
-pub use telegram::Config as TelegramConfig;
-pub use truth::Client as TruthClient;
-pub use truth::Config as TruthConfig;
-pub use twitter::Client as TwitterClient;
-pub use twitter::Config as TwitterConfig;
+pub mod api;
+pub mod cli;
+pub mod dashboard;
+pub mod discord;
+pub mod social;
+pub mod storytelling;
+pub mod telegram;
+pub mod truth;
+pub mod twitter;
+
+pub use api::Client as ApiClient;
+pub use api::Config as ApiConfig;
+pub use cli::Client as CliClient;
+pub use cli::Config as CliConfig;
+pub use dashboard::Client as DashboardClient;
+pub use discord::Client as DiscordClient;
+pub use discord::Config as DiscordConfig;
+pub use storytelling::Client as StoryTellingClient;
+pub use storytelling::Config as StorytellingConfig;
+pub use telegram::Client as TelegramClient;
+pub use telegram::Config as TelegramConfig;
+pub use truth::Client as TruthClient;
+pub use truth::Config as TruthConfig;
+pub use twitter::Client as TwitterClient;
+pub use twitter::Config as TwitterConfig;
@@ -0,0 +1,595 @@
+use super::{split_thread, Config, MediaProviders, SocialDoc, SocialPlatform, SocialPost};
+use crate::{
+    core::{
+        logging, memory::MemoryStore, metrics, Agent, ApprovalQueue, AuditLog, Character,
+        CharacterTrait, ClientState, Config as RootConfig, ConversationBuffer, DatabaseProvider,
+        Draft, DraftKind, Engagement, Generation, HybridSearchParams, KnowledgeBase, Publication,
+        RunRecorder, Scheduler, Shutdown, StateStore, Tasks, MEMORY_SNAPSHOT_FOLDER,
+    },
+    dbs::{LocalMemoryStore, MemoryStoreEnum, MongoDbMemoryStore},
+    providers::completion::CompletionResponseEnum,
+};
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use std::collections::HashMap;
+use tokio::{sync::mpsc, time::sleep};
+
+// posts and replies on its own schedule on any `SocialPlatform`
+#[derive(Clone)]
+pub struct Client<P, CM, EM>
+where
+    P: SocialPlatform,
+    CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
+    EM: rig::embeddings::EmbeddingModel,
+{
+    pub agent: Agent<CM>,
+    pub character: Character,
+    pub config: Config,
+    pub platform: P,
+    pub search_memory: MemoryStoreEnum<P::Doc, EM>,
+    pub search_params: HybridSearchParams,
+    pub post_history: ConversationBuffer,
+    pub knowledge: Option<KnowledgeBase<EM>>,
+    pub latest_mention_id: Option<String>,
+    // mentions newer than the cursor that were already answered or skipped
+    pub handled_mentions: Vec<String>,
+    pub approval: Option<ApprovalQueue>,
+    pub audit: Option<AuditLog>,
+    pub media_providers: MediaProviders,
+    pub state_store: StateStore,
+    pub last_runs: HashMap<String, DateTime<Utc>>,
+    pub recent_runs: HashMap<String, Vec<DateTime<Utc>>>,
+    // count successful runs against the daily cap of each action's scheduler
+    pub run_recorders: HashMap<String, RunRecorder>,
+    pub engagements: Vec<Engagement>,
+}
+
+enum Action {
+    Post(),
+    Reply(),
+    Thread(),
+    Engage(),
+    Deliver(),
+}
+
+impl<P, CM, EM> Client<P, CM, EM>
+where
+    P: SocialPlatform,
+    CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
+    EM: rig::embeddings::EmbeddingModel,
+{
+    pub const POST_HISTORY_TOKENS: usize = 1500;
+    // retrieved posts are ordered by relevance, the least relevant are cut first
+    pub const TIMELINE_TOKENS: usize = 1000;
+    // mentions the model chooses from per engagement
+    pub const MENTIONS: usize = 5;
+    // posts of the thread a mention replies into, the older ones are summarized past the tokens
+    pub const CONVERSATION_POSTS: usize = 10;
+    pub const CONVERSATION_TOKENS: usize = 600;
+    // posts stored per search
+    pub const SEARCH_RESULTS: usize = 10;
+    // times a thread is written before it is dropped for having too many parts
+    pub const THREAD_ATTEMPTS: usize = 2;
+    // how often approved drafts are looked for
+    pub const DELIVER_SECS: u64 = 60;
+    // keys of the saved state
+    pub const MENTION_CURSOR: &str = "mention";
+    pub const POST: &str = "post";
+    pub const REPLY: &str = "reply";
+    pub const THREAD: &str = "thread";
+    pub const ENGAGE: &str = "engage";
+
+    pub async fn new(
+        character: Character,
+        completion_model: CM,
+        embedding_model: EM,
+        config: Config,
+        search_params: HybridSearchParams,
+        root_config: RootConfig,
+        knowledge: Option<KnowledgeBase<EM>>,
+    ) -> Self {
+        let search_memory = open_search_memory::<P, EM>(embedding_model.clone(), &root_config)
+            .await
+            .expect("Failed to open memory store");
+
+        let state_store = StateStore::new(P::MEMORY, &root_config)
+            .await
+            .expect("Failed to create state store");
+
+        Client {
+            character,
+            agent: Agent::new(completion_model),
+            config,
+            platform: P::new(&root_config),
+            search_memory,
+            search_params,
+            post_history: ConversationBuffer::new(Self::POST_HISTORY_TOKENS),
+            knowledge,
+            latest_mention_id: None,
+            handled_mentions: Vec::new(),
+            approval: ApprovalQueue::new(&root_config.approval),
+            audit: AuditLog::new(&root_config),
+            media_providers: MediaProviders::new(&root_config),
+            state_store,
+            last_runs: HashMap::new(),
+            recent_runs: HashMap::new(),
+            run_recorders: HashMap::new(),
+            engagements: Vec::new(),
+        }
+    }
+
+    // runs until `shutdown`, finishing the action in flight and saving the state first
+    pub async fn start(&mut self, shutdown: Shutdown) {
+        info!("[{}] Logging in...", P::TAG);
+        if let Err(e) = self.platform.login().await {
+            error!("[{}] Failed to login: {}", P::TAG, e);
+            metrics::failed(P::MEMORY, "login", e);
+            return;
+        }
+        info!(
+            "[{}] Logged in as @{}",
+            P::TAG,
+            self.platform.current_user()
+        );
+
+        self.load_state().await;
+
+        info!("[{}] Starting client with 15s delay...", P::TAG);
+        let (sender, mut receiver) = mpsc::channel(3);
+
+        // schedules are validated when the config is parsed
+        let mut post_scheduler = Scheduler::new(&format!("[{}][POST]", P::TAG), &self.config.post)
+            .expect("Invalid post schedule");
+        let mut reply_scheduler =
+            Scheduler::new(&format!("[{}][REPLY]", P::TAG), &self.config.reply)
+                .expect("Invalid reply schedule");
+        self.resume(&mut post_scheduler, Self::POST);
+        self.resume(&mut reply_scheduler, Self::REPLY);
+
+        let mut tasks = Tasks::default();
+
+        let post_sender = sender.clone();
+        tasks.push(tokio::spawn(async move {
+            sleep(std::time::Duration::from_secs(15)).await;
+            loop {
+                post_scheduler.wait().await;
+                if post_sender.send(Action::Post()).await.is_err() {
+                    break;
+                }
+            }
+        }));
+
+        let reply_sender = sender.clone();
+        tasks.push(tokio::spawn(async move {
+            sleep(std::time::Duration::from_secs(1)).await;
+            loop {
+                reply_scheduler.wait().await;
+                if reply_sender.send(Action::Reply()).await.is_err() {
+                    break;
+                }
+            }
+        }));
+
+        if let Some(thread) = &self.config.thread {
+            let mut thread_scheduler =
+                Scheduler::new(&format!("[{}][THREAD]", P::TAG), &thread.schedule)
+                    .expect("Invalid thread schedule");
+            self.resume(&mut thread_scheduler, Self::THREAD);
+            let thread_sender = sender.clone();
+            tasks.push(tokio::spawn(async move {
+                sleep(std::time::Duration::from_secs(15)).await;
+                loop {
+                    thread_scheduler.wait().await;
+                    if thread_sender.send(Action::Thread()).await.is_err() {
+                        break;
+                    }
+                }
+            }));
+        }
+
+        if let Some(engage) = &self.config.engage {
+            let mut engage_scheduler =
+                Scheduler::new(&format!("[{}][ENGAGE]", P::TAG), &engage.schedule)
+                    .expect("Invalid engage schedule");
+            self.resume(&mut engage_scheduler, Self::ENGAGE);
+            let engage_sender = sender.clone();
+            tasks.push(tokio::spawn(async move {
+                sleep(std::time::Duration::from_secs(15)).await;
+                loop {
+                    engage_scheduler.wait().await;
+                    if engage_sender.send(Action::Engage()).await.is_err() {
+                        break;
+                    }
+                }
+            }));
+        }
+
+        if self.gated().is_some() {
+            let deliver_sender = sender.clone();
+            tasks.push(tokio::spawn(async move {
+                loop {
+                    sleep(std::time::Duration::from_secs(Self::DELIVER_SECS)).await;
+                    if deliver_sender.send(Action::Deliver()).await.is_err() {
+                        break;
+                    }
+                }
+            }));
+        }
+
+        loop {
+            let action = tokio::select! {
+                action = receiver.recv() => action,
+                _ = shutdown.wait() => None,
+            };
+            let Some(action) = action else {
+                break;
+            };
+            match action {
+                Action::Post() => {
+                    logging::correlated("post", async {
+                        if self.skipped(Self::POST) {
+                            return;
+                        }
+                        info!("[{}][POST] Executing...", P::TAG);
+                        let topic = self
+                            .character
+                            .choose_random_traits(CharacterTrait::Topics, 1);
+
+                        self.search(&topic).await;
+
+                        // a post still waiting on its search delay is dropped on shutdown
+                        tokio::select! {
+                            _ = sleep(std::time::Duration::from_secs(
+                                u64::from(self.config.search_delay) * 60,
+                            )) => self.post(topic).await,
+                            _ = shutdown.wait() => {}
+                        }
+                    })
+                    .await
+                }
+                Action::Reply() => {
+                    logging::correlated("reply", async {
+                        if self.skipped(Self::REPLY) {
+                            return;
+                        }
+                        info!("[{}][REPLY] Executing...", P::TAG);
+                        self.reply().await;
+                    })
+                    .await
+                }
+                Action::Thread() => {
+                    logging::correlated("thread", async {
+                        if self.skipped(Self::THREAD) {
+                            return;
+                        }
+                        info!("[{}][THREAD] Executing...", P::TAG);
+                        let topic = self
+                            .character
+                            .choose_random_traits(CharacterTrait::Topics, 1);
+                        self.thread(topic).await;
+                    })
+                    .await
+                }
+                Action::Engage() => {
+                    logging::correlated("engage", async {
+                        if self.skipped(Self::ENGAGE) {
+                            return;
+                        }
+                        info!("[{}][ENGAGE] Executing...", P::TAG);
+                        let topic = self
+                            .character
+                            .choose_random_traits(CharacterTrait::Topics, 1);
+                        self.engage(topic).await;
+                    })
+                    .await
+                }
+                Action::Deliver() => {
+                    logging::correlated("deliver", self.deliver()).await;
+                    continue;
+                }
+            }
+            tokio::select! {
+                _ = sleep(std::time::Duration::from_secs(u64::from(self.config.delay))) => {}
+                _ = shutdown.wait() => {}
+            }
+        }
+
+        drop(tasks);
+        self.persist_state().await;
+        info!("[{}] Stopped", P::TAG);
+    }
+
+    // picks up the cursors, post history, runs and engagements saved before a restart
+    async fn load_state(&mut self) {
+        match self.state_store.load().await {
+            Ok(state) => {
+                self.latest_mention_id = state.cursors.get(Self::MENTION_CURSOR).cloned();
+                self.handled_mentions = state.handled_mentions;
+                if let Some(post_history) = state.post_history {
+                    self.post_history.restore(post_history);
+                }
+                self.last_runs = state.last_runs;
+                self.recent_runs = state.recent_runs;
+                self.engagements = state.engagements;
+                if let Some(post_budget) = state.post_budget {
+                    self.platform.restore_post_budget(post_budget);
+                }
+                info!(
+                    "[{}][STATE] Loaded, latest mention: {}",
+                    P::TAG,
+                    self.latest_mention_id.as_deref().unwrap_or("none")
+                );
+            }
+            Err(e) => error!("[{}][STATE] Failed to load state: {}", P::TAG, e),
+        }
+    }
+
+    // whether the platform cannot run `action` now, e.g. while rate limited
+    fn skipped(&self, action: &str) -> bool {
+        let Some(reason) = self.platform.blocked(action) else {
+            return false;
+        };
+        warn!(
+            "[{}][{}] Skipping: {}",
+            P::TAG,
+            action.to_uppercase(),
+            reason
+        );
+        true
+    }
+
+    // continues `scheduler` from the runs of `action` saved before a restart and records the
+    // runs to come against its cap
+    fn resume(&mut self, scheduler: &mut Scheduler, action: &str) {
+        if let Some(last_run) = self.last_runs.get(action) {
+            let recent_runs = self.recent_runs.get(action).map_or(&[][..], Vec::as_slice);
+            scheduler.resume(*last_run, recent_runs);
+        }
+        self.run_recorders
+            .insert(action.to_string(), scheduler.recorder());
+    }
+
+    // records a successful run of `action` and saves the state
+    pub async fn save_state(&mut self, action: &str) {
+        if let Some(recorder) = self.run_recorders.get(action) {
+            recorder.record_run();
+        }
+        let now = Utc::now();
+        self.last_runs.insert(action.to_string(), now);
+        let runs = self.recent_runs.entry(action.to_string()).or_default();
+        runs.retain(|run| now - *run < chrono::Duration::days(2));
+        runs.push(now);
+        self.persist_state().await;
+    }
+
+    async fn persist_state(&self) {
+        let state = ClientState {
+            cursors: self
+                .latest_mention_id
+                .iter()
+                .map(|id| (Self::MENTION_CURSOR.to_string(), id.clone()))
+                .collect(),
+            handled_mentions: self.handled_mentions.clone(),
+            post_history: Some(self.post_history.clone()),
+            last_runs: self.last_runs.clone(),
+            recent_runs: self.recent_runs.clone(),
+            engagements: self.engagements.clone(),
+            post_budget: self.platform.post_budget(),
+        };
+        if let Err(e) = self.state_store.save(&state).await {
+            error!("[{}][STATE] Failed to save state: {}", P::TAG, e);
+        }
+    }
+
+    // the approval queue when this platform's posts wait for approval
+    pub fn gated(&self) -> Option<&ApprovalQueue> {
+        self.approval
+            .as_ref()
+            .filter(|approval| approval.gates(P::MEMORY))
+    }
+
+    // queues generated content instead of sending it
+    pub fn submit(
+        &self,
+        kind: DraftKind,
+        text: &str,
+        to: Option<&SocialPost>,
+        generation: Generation,
+    ) -> bool {
+        let Some(approval) = self.gated() else {
+            return false;
+        };
+        let target = to
+            .and_then(|post| serde_json::to_value(post).ok())
+            .unwrap_or_default();
+        match approval.submit(
+            P::MEMORY,
+            kind,
+            text,
+            to.map(|post| format!("@{}: {}", post.author, post.text)),
+            target,
+            Some(generation),
+        ) {
+            Ok(draft) => info!("[{}][APPROVAL] Queued draft {}", P::TAG, draft.id),
+            Err(e) => error!("[{}][APPROVAL] Failed to queue draft: {}", P::TAG, e),
+        }
+        true
+    }
+
+    // records something sent in the audit log
+    pub fn audit(
+        &self,
+        kind: DraftKind,
+        content: &str,
+        message_id: Option<String>,
+        target_id: Option<String>,
+        generation: Option<&Generation>,
+        draft: Option<&Draft>,
+    ) {
+        if let Some(audit) = &self.audit {
+            audit.record(Publication {
+                platform: P::MEMORY,
+                kind,
+                message_id,
+                target_id,
+                content,
+                character: &self.character,
+                generation,
+                draft,
+            });
+        }
+    }
+
+    // sends the drafts an operator approved
+    async fn deliver(&mut self) {
+        let Some(approval) = self.gated().cloned() else {
+            return;
+        };
+        let drafts = match approval.approved(P::MEMORY) {
+            Ok(drafts) => drafts,
+            Err(e) => {
+                error!("[{}][APPROVAL] Failed to read drafts: {}", P::TAG, e);
+                return;
+            }
+        };
+
+        for draft in drafts {
+            let to = match draft.kind {
+                DraftKind::Reply | DraftKind::Quote => {
+                    serde_json::from_value::<SocialPost>(draft.target.clone()).ok()
+                }
+                _ => None,
+            };
+            let result = match (draft.kind, &to) {
+                (DraftKind::Post, _) => {
+                    let media = draft
+                        .generation
+                        .as_ref()
+                        .map(|generation| generation.media.as_slice())
+                        .unwrap_or_default();
+                    self.send_post(&draft.text, media).await
+                }
+                (DraftKind::Reply, Some(to)) => self.platform.reply(to, &draft.text).await,
+                (DraftKind::Quote, Some(to)) => self.platform.quote(to, &draft.text).await,
+                (DraftKind::Reply | DraftKind::Quote, None) => {
+                    Err(anyhow::anyhow!("invalid reply target"))
+                }
+                // edits may have changed the parts, they are split again
+                (DraftKind::Thread, _) => {
+                    let parts = split_thread(&draft.text, P::MAX_CHARS);
+                    self.send_thread(&parts, draft.generation.as_ref(), Some(&draft))
+                        .await
+                }
+                (DraftKind::Like | DraftKind::Repost, _) => {
+                    Err(anyhow::anyhow!("{:?} is never queued", draft.kind))
+                }
+            };
+            match &result {
+                Ok(message_id) => {
+                    info!("[{}][APPROVAL] Sent draft {}", P::TAG, draft.id);
+                    match draft.kind {
+                        DraftKind::Post | DraftKind::Thread => metrics::posted(P::MEMORY),
+                        DraftKind::Reply => metrics::replied(P::MEMORY),
+                        DraftKind::Quote => metrics::succeeded(P::MEMORY, "quote"),
+                        DraftKind::Like | DraftKind::Repost => {}
+                    }
+                    // threads are audited part by part as they are sent
+                    if draft.kind != DraftKind::Thread {
+                        self.audit(
+                            draft.kind,
+                            &draft.text,
+                            message_id.clone(),
+                            to.as_ref().map(|post| post.id.clone()),
+                            draft.generation.as_ref(),
+                            Some(&draft),
+                        );
+                    }
+                }
+                Err(e) => {
+                    error!(
+                        "[{}][APPROVAL] Failed to send draft {}: {}",
+                        P::TAG,
+                        draft.id,
+                        e
+                    );
+                    metrics::failed(P::MEMORY, "deliver", e);
+                }
+            }
+            if let Err(e) =
+                approval.delivered(&draft.id, result.map(|_| ()).map_err(|e| e.to_string()))
+            {
+                error!(
+                    "[{}][APPROVAL] Failed to record draft {}: {}",
+                    P::TAG,
+                    draft.id,
+                    e
+                );
+            }
+        }
+    }
+
+    // stored posts most relevant to `topic`, as timeline context for prompts
+    pub async fn timeline(&self, topic: &str) -> Vec<String> {
+        match self
+            .search_memory
+            .hybrid_top_n::<P::Doc>(topic, 3, &self.search_params)
+            .await
+        {
+            Ok(results) => {
+                info!(
+                    "[{}][VECDB] Found top results relating to: {}",
+                    P::TAG,
+                    topic
+                );
+                results
+                    .into_iter()
+                    .map(|(score, _, doc)| {
+                        info!(
+                            "[{}][VECDB] Score: {} | Post: {}",
+                            P::TAG,
+                            score,
+                            doc.text()
+                        );
+                        doc.text().to_string()
+                    })
+                    .collect()
+            }
+            Err(e) => {
+                error!("[{}][VECDB] Failed to get top posts: {}", P::TAG, e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+// the store searched posts of `P` are kept in, failing when it holds embeddings of other
+// dimensions than the embedding model's
+pub async fn open_search_memory<P, EM>(
+    embedding_model: EM,
+    root_config: &RootConfig,
+) -> Result<MemoryStoreEnum<P::Doc, EM>, anyhow::Error>
+where
+    P: SocialPlatform,
+    EM: rig::embeddings::EmbeddingModel,
+{
+    let search_memory: MemoryStoreEnum<P::Doc, EM> = match root_config.db {
+        DatabaseProvider::Local => MemoryStoreEnum::Local {
+            memory_store: LocalMemoryStore::with_snapshot(
+                embedding_model.clone(),
+                &format!("{}/{}.jsonl", MEMORY_SNAPSHOT_FOLDER, P::MEMORY),
+            )?,
+        },
+        DatabaseProvider::MongoDB => MemoryStoreEnum::MongoDB {
+            memory_store: MongoDbMemoryStore::new(
+                root_config.providers.mongodb.conn_url(),
+                root_config.providers.mongodb.db(),
+                root_config.providers.mongodb.collection(),
+                embedding_model.clone(),
+                P::Doc::to_document,
+            )
+            .await?,
+        },
+    };
+    search_memory.check_ndims(embedding_model.ndims()).await?;
+    Ok(search_memory)
+}
@@ -0,0 +1,309 @@
+use crate::core::{MediaKind, ScheduleConfig, Scheduler};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(try_from = "ConfigRaw")]
+pub struct Config {
+    pub post: ScheduleConfig,
+    pub reply: ScheduleConfig,
+    pub replies: RepliesConfig,
+    pub thread: Option<ThreadConfig>,
+    pub media: Option<MediaConfig>,
+    pub engage: Option<EngageConfig>,
+    pub search_delay: u8,
+    pub delay: u8,
+    pub debug: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigRaw {
+    #[serde(default)]
+    post: Option<ScheduleConfig>,
+    #[serde(default)]
+    reply: Option<ScheduleConfig>,
+    #[serde(default)]
+    replies: RepliesConfig,
+    #[serde(default)]
+    thread: Option<ThreadConfig>,
+    #[serde(default)]
+    media: Option<MediaConfig>,
+    #[serde(default)]
+    engage: Option<EngageConfig>,
+    // `[min, max]` minutes, shorthand for `{ "every": [min, max] }`
+    #[serde(default)]
+    post_delay: Option<Vec<u32>>,
+    #[serde(default)]
+    reply_delay: Option<Vec<u32>>,
+    #[serde(default)]
+    search_delay: u8,
+    #[serde(default)]
+    delay: u8,
+    #[serde(default)]
+    debug: bool,
+}
+
+impl TryFrom<ConfigRaw> for Config {
+    type Error = String;
+
+    fn try_from(raw: ConfigRaw) -> Result<Self, Self::Error> {
+        let config = Config {
+            post: schedule("post", raw.post, raw.post_delay)?,
+            reply: schedule("reply", raw.reply, raw.reply_delay)?,
+            replies: raw.replies,
+            thread: raw.thread,
+            media: raw.media,
+            engage: raw.engage,
+            search_delay: raw.search_delay,
+            delay: raw.delay,
+            debug: raw.debug,
+        };
+
+        Scheduler::new("post", &config.post).map_err(|e| format!("post: {}", e))?;
+        Scheduler::new("reply", &config.reply).map_err(|e| format!("reply: {}", e))?;
+        let replies = &config.replies;
+        for (name, value) in [
+            ("per_cycle", replies.per_cycle),
+            ("per_user", replies.per_user),
+            ("max_mentions", replies.max_mentions),
+        ] {
+            if value == 0 {
+                return Err(format!("replies.{name} must be above 0"));
+            }
+        }
+        if replies.scoring.max_toxicity > 10 {
+            return Err("replies.scoring.max_toxicity must be between 0 and 10".to_string());
+        }
+        if let Some(thread) = &config.thread {
+            Scheduler::new("thread", &thread.schedule).map_err(|e| format!("thread: {}", e))?;
+            if !(2..=25).contains(&thread.max_parts) {
+                return Err("thread.max_parts must be between 2 and 25".to_string());
+            }
+        }
+        if let Some(media) = &config.media {
+            if !(0.0..=1.0).contains(&media.rate) {
+                return Err("media.rate must be within 0.0 <= x <= 1.0".to_string());
+            }
+            if media.sources.is_empty() {
+                return Err("media.sources must not be empty".to_string());
+            }
+        }
+        if let Some(engage) = &config.engage {
+            Scheduler::new("engage", &engage.schedule).map_err(|e| format!("engage: {}", e))?;
+            let limits = [
+                ("like", &engage.like),
+                ("repost", &engage.repost),
+                ("quote", &engage.quote),
+            ];
+            if limits.iter().all(|(_, limit)| limit.is_none()) {
+                return Err("engage needs at least one of like, repost or quote".to_string());
+            }
+            for (name, limit) in limits {
+                if limit.as_ref().is_some_and(|limit| limit.daily_cap == 0) {
+                    return Err(format!("engage.{name}.daily_cap must be above 0"));
+                }
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+fn schedule(
+    name: &str,
+    schedule: Option<ScheduleConfig>,
+    delay: Option<Vec<u32>>,
+) -> Result<ScheduleConfig, String> {
+    match (schedule, delay) {
+        (Some(schedule), None) => Ok(schedule),
+        (None, Some(delay)) => match delay[..] {
+            [min, max] if min <= max => Ok(ScheduleConfig::every(min, max)),
+            _ => Err(format!("{name}_delay must be [min, max] with min <= max")),
+        },
+        (Some(_), Some(_)) => Err(format!("set either {name} or {name}_delay, not both")),
+        (None, None) => Err(format!("{name} or {name}_delay must be set")),
+    }
+}
+
+// how many of the new mentions each reply cycle answers, the best scored first
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RepliesConfig {
+    #[serde(default = "default_replies_per_cycle")]
+    pub per_cycle: usize,
+    // replies to the same author per cycle
+    #[serde(default = "default_replies_per_user")]
+    pub per_user: usize,
+    // new mentions fetched and scored per cycle, older ones are paged through
+    #[serde(default = "default_max_mentions")]
+    pub max_mentions: usize,
+    #[serde(default)]
+    pub scoring: MentionScoringConfig,
+}
+
+impl Default for RepliesConfig {
+    fn default() -> Self {
+        Self {
+            per_cycle: default_replies_per_cycle(),
+            per_user: default_replies_per_user(),
+            max_mentions: default_max_mentions(),
+            scoring: MentionScoringConfig::default(),
+        }
+    }
+}
+
+// mentions are ranked by the weighted sum of their relevance to the character, whether they ask
+// a question and how many follow their author, each normalized to 0 to 1
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MentionScoringConfig {
+    #[serde(default = "default_relevance_weight")]
+    pub relevance_weight: f64,
+    #[serde(default = "default_mention_weight")]
+    pub question_weight: f64,
+    #[serde(default = "default_mention_weight")]
+    pub followers_weight: f64,
+    // mentions the model rates more toxic, from 0 to 10, are never answered
+    #[serde(default = "default_max_toxicity")]
+    pub max_toxicity: u8,
+}
+
+impl Default for MentionScoringConfig {
+    fn default() -> Self {
+        Self {
+            relevance_weight: default_relevance_weight(),
+            question_weight: default_mention_weight(),
+            followers_weight: default_mention_weight(),
+            max_toxicity: default_max_toxicity(),
+        }
+    }
+}
+
+fn default_replies_per_cycle() -> usize {
+    1
+}
+
+fn default_replies_per_user() -> usize {
+    1
+}
+
+fn default_max_mentions() -> usize {
+    100
+}
+
+fn default_relevance_weight() -> f64 {
+    1.0
+}
+
+fn default_mention_weight() -> f64 {
+    0.5
+}
+
+fn default_max_toxicity() -> u8 {
+    5
+}
+
+// long-form posts written as a thread on their own schedule, next to the single posts
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ThreadConfig {
+    pub schedule: ScheduleConfig,
+    #[serde(default = "default_max_parts")]
+    pub max_parts: usize,
+    #[serde(default)]
+    pub on_failure: ThreadFailure,
+}
+
+// what happens to the parts already sent when a later one fails
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum ThreadFailure {
+    // deletes them again, newest first
+    #[serde(rename = "delete")]
+    #[default]
+    Delete,
+    // keeps them, the audit log and the logs tell the thread is incomplete
+    #[serde(rename = "keep")]
+    Keep,
+}
+
+fn default_max_parts() -> usize {
+    5
+}
+
+// attachments for posts, one of `sources` is picked at random for each
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MediaConfig {
+    // share of posts that get an attachment
+    #[serde(default = "default_media_rate")]
+    pub rate: f64,
+    pub sources: Vec<MediaSource>,
+    // alt text written by the model
+    #[serde(default = "default_alt_text")]
+    pub alt_text: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum MediaSource {
+    // a random image or audio file in `path`, e.g. the storytelling `tts_output`
+    Files {
+        path: String,
+        #[serde(default)]
+        once: bool,
+    },
+    // an image generated for the post with `providers.openai`
+    Image {
+        #[serde(default)]
+        model: Option<String>,
+        #[serde(default)]
+        size: Option<String>,
+    },
+    // the post read out with `providers.elevenlabs`
+    Tts {
+        voice_id: String,
+    },
+}
+
+impl MediaSource {
+    // what the source generates, `None` for files, which are picked by the kinds a platform takes
+    pub fn kind(&self) -> Option<MediaKind> {
+        match self {
+            Self::Files { .. } => None,
+            Self::Image { .. } => Some(MediaKind::Image),
+            Self::Tts { .. } => Some(MediaKind::Audio),
+        }
+    }
+}
+
+fn default_media_rate() -> f64 {
+    1.0
+}
+
+fn default_alt_text() -> bool {
+    true
+}
+
+// likes, reposts and quote posts of searched posts and mentions, the model picks which fits
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct EngageConfig {
+    pub schedule: ScheduleConfig,
+    // an action without its limits is never taken
+    #[serde(default)]
+    pub like: Option<EngageLimit>,
+    #[serde(default)]
+    pub repost: Option<EngageLimit>,
+    #[serde(default)]
+    pub quote: Option<EngageLimit>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct EngageLimit {
+    // actions in any 24 hours
+    pub daily_cap: u32,
+    // minutes at least between two actions
+    #[serde(default)]
+    pub delay: u32,
+}
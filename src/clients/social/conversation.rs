@@ -0,0 +1,121 @@
+use super::{Client, SocialPlatform, SocialPost};
+use crate::{
+    core::metrics,
+    providers::completion::{budget::take_within, CompletionResponseEnum},
+};
+use log::{error, info, warn};
+
+impl<P, CM, EM> Client<P, CM, EM>
+where
+    P: SocialPlatform,
+    CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
+    EM: rig::embeddings::EmbeddingModel,
+{
+    // the thread `mention` replies into as a <conversation> block for the reply prompt, empty when
+    // it starts one or the thread could not be fetched
+    pub async fn conversation_context(&mut self, mention: &SocialPost) -> String {
+        let posts = match self
+            .platform
+            .conversation(mention, Self::CONVERSATION_POSTS)
+            .await
+        {
+            Ok(posts) => posts,
+            Err(e) => {
+                warn!(
+                    "[{}][REPLY] Failed to fetch the conversation of {}: {}",
+                    P::TAG,
+                    mention.id,
+                    e
+                );
+                metrics::failed(P::MEMORY, "conversation", e);
+                return String::new();
+            }
+        };
+        if posts.is_empty() {
+            return String::new();
+        }
+
+        let current_user = self.platform.current_user().to_string();
+        let lines = posts
+            .iter()
+            .map(|post| {
+                if post.author == current_user {
+                    format!("@{} (you): {}", post.author, post.text)
+                } else {
+                    format!("@{}: {}", post.author, post.text)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // the posts right before the mention are kept as they are, the older ones summarized
+        let recent = take_within(
+            lines.iter().rev().cloned().collect(),
+            Self::CONVERSATION_TOKENS,
+        )
+        .into_iter()
+        .rev()
+        .collect::<Vec<_>>();
+        let older = &lines[..lines.len() - recent.len()];
+        let summary = if older.is_empty() {
+            String::new()
+        } else {
+            info!(
+                "[{}][REPLY] Summarizing {} older posts of the conversation",
+                P::TAG,
+                older.len()
+            );
+            self.summarize_conversation(older)
+                .await
+                .map(|summary| format!("Earlier in the thread: {}\n", summary))
+                .unwrap_or_default()
+        };
+
+        format!(
+            "These posts are the thread <mention> replies into, oldest first, in the format of @<author>: <post>. Posts marked (you) are your own.\n<conversation>\n{}{}\n</conversation>",
+            summary,
+            recent.join("\n")
+        )
+    }
+
+    // `None` when the completion fails, the reply goes without the older posts then
+    async fn summarize_conversation(&self, lines: &[String]) -> Option<String> {
+        let prompt = format!(
+            r"<conversation>
+            {conversation}
+            </conversation>
+
+            Summarize <conversation>, a {platform} thread you, @{alias}, take part in.
+            <rules>
+            - Keep who said what, the questions asked and what you answered.
+            - Write in plain sentences.
+            - Less than 80 words.
+            - Only output the summary.
+            </rules>
+            ",
+            conversation = lines.join("\n"),
+            platform = P::NAME,
+            alias = self.platform.current_user(),
+        );
+        let request = self
+            .agent
+            .completion_model
+            .completion_request(&prompt)
+            .preamble("You summarize conversations for your own context.".to_string())
+            .build();
+        match self.agent.completion(request).await {
+            Ok(response) => {
+                let summary = self.agent.response_extract_content(response);
+                (!summary.trim().is_empty()).then(|| summary.trim().to_string())
+            }
+            Err(e) => {
+                error!(
+                    "[{}][REPLY] Failed to summarize conversation: {}",
+                    P::TAG,
+                    e
+                );
+                metrics::failed(P::MEMORY, "completion", e);
+                None
+            }
+        }
+    }
+}
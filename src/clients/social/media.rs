@@ -0,0 +1,201 @@
+use super::{Client, MediaSource, SocialPlatform, UploadError};
+use crate::{
+    core::{media, metrics, Attachment, CharacterTrait::Styles, Config as RootConfig, MediaKind},
+    providers::{
+        completion::CompletionResponseEnum,
+        elevenlabs::{self, tts::TtsRequestBuilder},
+        openai::{self, images::ImageRequest},
+    },
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::{error, info, warn};
+use rand::{seq::SliceRandom, Rng};
+
+// the generation providers media sources use, set when their api key is
+#[derive(Clone, Default)]
+pub struct MediaProviders {
+    pub images: Option<openai::Client>,
+    pub speech: Option<elevenlabs::Client>,
+}
+
+impl MediaProviders {
+    pub fn new(root_config: &RootConfig) -> Self {
+        let providers = &root_config.providers;
+        Self {
+            images: providers
+                .openai
+                .api_key
+                .as_ref()
+                .and_then(|key| openai::Client::new(key.expose().to_string()).ok()),
+            speech: providers
+                .elevenlabs
+                .api_key
+                .as_ref()
+                .and_then(|key| elevenlabs::Client::new(key.expose().to_string()).ok()),
+        }
+    }
+}
+
+impl<P, CM, EM> Client<P, CM, EM>
+where
+    P: SocialPlatform,
+    CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
+    EM: rig::embeddings::EmbeddingModel,
+{
+    // what to attach to the post `text`, `None` when it goes without media
+    pub async fn attachment(&self, text: &str) -> Option<Attachment> {
+        let config = self.config.media.as_ref()?;
+        if !rand::thread_rng().gen_bool(config.rate) {
+            return None;
+        }
+        // e.g. tts recordings are left out on twitter, which only takes images
+        let sources = config
+            .sources
+            .iter()
+            .filter(|source| source.kind().is_none_or(|kind| P::MEDIA.contains(&kind)))
+            .collect::<Vec<_>>();
+        let source = sources.choose(&mut rand::thread_rng())?;
+
+        // what the model is told about the media for the alt text, files only by their name
+        let (result, about) = match source {
+            MediaSource::Files { path, once } => (media::pick(path, P::MEDIA, *once), None),
+            MediaSource::Image { model, size } => {
+                let prompt = format!(
+                    "An image to go with this post by {}, in a {} style: {}",
+                    self.character.alias,
+                    self.character.choose_random_traits(Styles, 1),
+                    text
+                );
+                (
+                    self.generate_image(&prompt, model.clone(), size.clone())
+                        .await
+                        .map(Some),
+                    Some(format!("an image generated from: {}", prompt)),
+                )
+            }
+            MediaSource::Tts { voice_id } => (
+                self.generate_speech(text, voice_id).await.map(Some),
+                Some("a recording of the post read out".to_string()),
+            ),
+        };
+        let mut attachment = match result {
+            Ok(Some(attachment)) => attachment,
+            Ok(None) => {
+                warn!("[{}][MEDIA] No media to attach", P::TAG);
+                return None;
+            }
+            Err(e) => {
+                error!("[{}][MEDIA] Failed to get media: {}", P::TAG, e);
+                metrics::failed(P::MEMORY, "media", e);
+                return None;
+            }
+        };
+        info!("[{}][MEDIA] Attaching {}", P::TAG, attachment.path);
+
+        if config.alt_text {
+            let about = about.unwrap_or_else(|| format!("a file named {}", attachment.file_name()));
+            attachment.alt_text = self.alt_text(text, &attachment, &about).await;
+        }
+        Some(attachment)
+    }
+
+    // posts `text` with `media`, or on its own when the platform would not take the media
+    pub async fn send_post(
+        &mut self,
+        text: &str,
+        media: &[Attachment],
+    ) -> Result<Option<String>, anyhow::Error> {
+        match self.platform.post_media(text, media).await {
+            Ok(message_id) => {
+                media.iter().for_each(Attachment::sent);
+                Ok(message_id)
+            }
+            Err(e) if e.is::<UploadError>() => {
+                warn!("[{}][MEDIA] Posting without media: {}", P::TAG, e);
+                metrics::failed(P::MEMORY, "media", &e);
+                self.platform.post(text).await
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn generate_image(
+        &self,
+        prompt: &str,
+        model: Option<String>,
+        size: Option<String>,
+    ) -> Result<Attachment, anyhow::Error> {
+        let images = self
+            .media_providers
+            .images
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("providers.openai.api_key not set"))?;
+        let image = images
+            .generate_image(ImageRequest::new(prompt.to_string(), model, size))
+            .await?;
+        Attachment::save(&image, "png")
+    }
+
+    async fn generate_speech(
+        &self,
+        text: &str,
+        voice_id: &str,
+    ) -> Result<Attachment, anyhow::Error> {
+        let speech = self
+            .media_providers
+            .speech
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("providers.elevenlabs.api_key not set"))?;
+        let response = speech
+            .tts(TtsRequestBuilder::new(voice_id.to_string(), text.to_string()).build())
+            .await?;
+        Attachment::save(&STANDARD.decode(response.audio_base64)?, "mp3")
+    }
+
+    // written by the model, the post goes out without it when that fails
+    async fn alt_text(&self, text: &str, attachment: &Attachment, about: &str) -> Option<String> {
+        let kind = match attachment.kind {
+            MediaKind::Image => "image",
+            MediaKind::Audio => "audio",
+        };
+        let prompt = format!(
+            r"<post>
+            {text}
+            </post>
+            <media>
+            {about}
+            </media>
+
+            Write the alt text for the {kind} attached to <post>, which is <media>. Describe what it most likely shows or sounds like for someone who cannot see or hear it.
+
+            <rules>
+            - Less than 400 characters.
+            - No emojis.
+            - No hashtags.
+            - Return only the alt text.
+            </rules>
+            "
+        );
+        let request = self
+            .agent
+            .completion_model
+            .completion_request(&prompt)
+            .preamble(format!(
+                "You write alt text for the {} posts of @{}.",
+                P::NAME,
+                self.platform.current_user()
+            ))
+            .build();
+        match self.agent.completion(request).await {
+            Ok(response) => {
+                let alt_text = self.agent.response_extract_content(response);
+                (!alt_text.is_empty()).then_some(alt_text)
+            }
+            Err(e) => {
+                error!("[{}][MEDIA] Failed to write alt text: {}", P::TAG, e);
+                metrics::failed(P::MEMORY, "completion", e);
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,17 @@
+pub mod client;
+pub mod config;
+pub mod conversation;
+pub mod engage;
+pub mod media;
+pub mod mentions;
+pub mod platform;
+pub mod post;
+pub mod reply;
+pub mod search;
+pub mod thread;
+
+pub use client::*;
+pub use config::*;
+pub use media::MediaProviders;
+pub use platform::*;
+pub use thread::split_thread;
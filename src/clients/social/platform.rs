@@ -0,0 +1,161 @@
+use crate::core::{Attachment, Config as RootConfig, MediaKind, PostBudget};
+use async_trait::async_trait;
+use mongodb::bson;
+use rig::{embeddings::Embedding, Embed, OneOrMany};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt;
+
+// a post as every platform sees it, stored with reply drafts waiting for approval
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SocialPost {
+    pub id: String,
+    // the handle the platform needs to address the author in a reply
+    pub author: String,
+    pub text: String,
+    // of the author, when the platform tells it
+    #[serde(default)]
+    pub followers: Option<u64>,
+}
+
+// a thread that broke off, with the ids of the parts that went out before
+#[derive(Debug)]
+pub struct ThreadError {
+    pub sent: Vec<String>,
+    pub error: anyhow::Error,
+}
+
+impl fmt::Display for ThreadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} after {} parts", self.error, self.sent.len())
+    }
+}
+
+// media the platform would not take, the post can still go out without it
+#[derive(Debug)]
+pub struct UploadError(pub anyhow::Error);
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to upload media: {}", self.0)
+    }
+}
+
+impl std::error::Error for UploadError {}
+
+// what searched posts are stored as, each platform keeps its own document so existing stores
+// stay readable
+pub trait SocialDoc:
+    Embed + Serialize + DeserializeOwned + Send + Sync + Eq + Clone + 'static
+{
+    fn new(post: SocialPost, search: &str) -> Self;
+
+    fn text(&self) -> &str;
+
+    fn to_document(data: &Self, embedding: &OneOrMany<Embedding>) -> bson::Document;
+}
+
+// everything the autonomous social client needs from a platform, implementing it is all a new
+// platform takes
+#[async_trait]
+pub trait SocialPlatform: Clone + Send + Sync + 'static {
+    type Doc: SocialDoc;
+
+    // log tag, e.g. `TWITTER`
+    const TAG: &'static str;
+    // how the platform is called in prompts
+    const NAME: &'static str;
+    // name of the local memory snapshot and of the client in the approval queue
+    const MEMORY: &'static str;
+    // longest post the platform takes, in characters
+    const MAX_CHARS: usize;
+    // media posts can carry
+    const MEDIA: &'static [MediaKind] = &[];
+
+    fn new(root_config: &RootConfig) -> Self;
+
+    async fn login(&mut self) -> Result<(), anyhow::Error>;
+
+    // handle of the logged in account
+    fn current_user(&self) -> &str;
+
+    // why `action`, a state key like `post`, cannot run now, e.g. an exhausted rate limit;
+    // checked before any completion is generated for it
+    fn blocked(&self, action: &str) -> Option<String> {
+        let _ = action;
+        None
+    }
+
+    // posts counted against a monthly budget, saved with the client state
+    fn post_budget(&self) -> Option<PostBudget> {
+        None
+    }
+
+    fn restore_post_budget(&mut self, budget: PostBudget) {
+        let _ = budget;
+    }
+
+    // both return the id of the new post when the platform tells it
+    async fn post(&mut self, text: &str) -> Result<Option<String>, anyhow::Error>;
+
+    async fn reply(&mut self, to: &SocialPost, text: &str)
+        -> Result<Option<String>, anyhow::Error>;
+
+    async fn like(&mut self, post: &SocialPost) -> Result<(), anyhow::Error>;
+
+    // returns the id of the repost when the platform tells it
+    async fn repost(&mut self, post: &SocialPost) -> Result<Option<String>, anyhow::Error>;
+
+    async fn quote(
+        &mut self,
+        post: &SocialPost,
+        text: &str,
+    ) -> Result<Option<String>, anyhow::Error>;
+
+    // uploads `media` and posts `text` with it
+    async fn post_media(
+        &mut self,
+        text: &str,
+        media: &[Attachment],
+    ) -> Result<Option<String>, anyhow::Error> {
+        if media.is_empty() {
+            return self.post(text).await;
+        }
+        Err(anyhow::anyhow!("{} takes no media", Self::NAME))
+    }
+
+    // posts `parts` in order, each replying to the one before, and returns their ids
+    async fn post_thread(&mut self, parts: &[String]) -> Result<Vec<String>, ThreadError> {
+        let _ = parts;
+        Err(ThreadError {
+            sent: Vec::new(),
+            error: anyhow::anyhow!("{} has no threads", Self::NAME),
+        })
+    }
+
+    async fn delete(&mut self, id: &str) -> Result<(), anyhow::Error> {
+        Err(anyhow::anyhow!(
+            "deleting {} on {} is not supported",
+            id,
+            Self::NAME
+        ))
+    }
+
+    // the posts `post` replies into, oldest first and at most `limit` of them, ending with the
+    // one it answers
+    async fn conversation(
+        &mut self,
+        post: &SocialPost,
+        limit: usize,
+    ) -> Result<Vec<SocialPost>, anyhow::Error>;
+
+    // posts addressing the account that are newer than `since`, newest first and at most `count`
+    // of them, paging through older ones when the platform pages
+    async fn fetch_mentions(
+        &mut self,
+        since: Option<&str>,
+        count: usize,
+    ) -> Result<Vec<SocialPost>, anyhow::Error>;
+
+    async fn search(&mut self, query: &str, count: usize)
+        -> Result<Vec<SocialPost>, anyhow::Error>;
+}
@@ -0,0 +1,154 @@
+use super::{Client, SocialPlatform};
+use crate::{
+    core::{
+        metrics,
+        CharacterTrait::{Adjectives, Inspirations, Lore, Styles},
+        DraftKind, Generation,
+    },
+    providers::completion::{budget::take_within, CompletionResponseEnum},
+};
+use log::{debug, error, info};
+
+impl<P, CM, EM> Client<P, CM, EM>
+where
+    P: SocialPlatform,
+    CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
+    EM: rig::embeddings::EmbeddingModel,
+{
+    pub async fn post(&mut self, topic: String) {
+        let posts = self.timeline(&topic).await;
+
+        // Generate post prompt
+        let request_summary = format!("Write a post about {}", topic);
+        let prompt = self.generate_post_prompt(topic, take_within(posts, Self::TIMELINE_TOKENS));
+        debug!("[{}][POST] Generated prompt:\n{}", P::TAG, prompt);
+
+        let preamble = format!(
+                "Your name: {}. Your Bio: {}. Use <characterInfo> and <timeline> to generate a {} post as @{}. Don't make your responses start like your previous ones.You MUST follow ALL the <rules>.{}",
+                self.character.alias,
+                self.character.bio,
+                P::NAME,
+                self.platform.current_user(),
+                self.post_history.summary_block()
+            );
+        let mut generation = Generation {
+            prompt,
+            preamble,
+            media: Vec::new(),
+        };
+
+        // Build the request for the completion model
+        let request = self
+            .agent
+            .completion_model
+            .completion_request(&generation.prompt)
+            .preamble(generation.preamble.clone())
+            .messages(self.post_history.messages())
+            .build();
+
+        match self.agent.completion(request).await {
+            Ok(response) => {
+                let agent_content = self.agent.response_extract_content(response);
+
+                if !agent_content.is_empty() {
+                    let posted = if self.config.debug {
+                        info!("[{}][DEBUG] Would have posted: {}", P::TAG, agent_content);
+                        false
+                    } else {
+                        generation.media =
+                            self.attachment(&agent_content).await.into_iter().collect();
+                        if self.submit(DraftKind::Post, &agent_content, None, generation.clone()) {
+                            info!("[{}] Post waits for approval", P::TAG);
+                            true
+                        } else {
+                            match self.send_post(&agent_content, &generation.media).await {
+                                Ok(message_id) => {
+                                    info!("[{}] Successfully posted", P::TAG);
+                                    metrics::posted(P::MEMORY);
+                                    self.audit(
+                                        DraftKind::Post,
+                                        &agent_content,
+                                        message_id,
+                                        None,
+                                        Some(&generation),
+                                        None,
+                                    );
+                                    true
+                                }
+                                Err(e) => {
+                                    error!("[{}] Failed to post: {}", P::TAG, e);
+                                    metrics::failed(P::MEMORY, "post", e);
+                                    false
+                                }
+                            }
+                        }
+                    };
+                    // only the request is kept, the rendered prompt would bloat the history
+                    self.post_history.push("user", request_summary);
+                    self.post_history.push("assistant", agent_content);
+                    self.post_history.compact(&self.agent).await;
+                    if posted {
+                        self.save_state(Self::POST).await;
+                    }
+                }
+            }
+            Err(err) => {
+                error!("[{}][AGENT] Failed to generate completion: {}", P::TAG, err);
+                metrics::failed(P::MEMORY, "completion", err);
+            }
+        }
+    }
+
+    fn generate_post_prompt(&self, topic: String, posts: Vec<String>) -> String {
+        format!(
+            r"<characterInfo>
+            These describe you:
+            <adjectives>
+            {adjectives}
+            </adjectives>
+            This has happened to you:
+            <lore>
+            {lore}
+            </lore>
+            You are known for this writing style:
+            <style>
+            {style}
+            </style>
+            You are interested in this topic:
+            <topic>
+            {topic}
+            </topic>
+            You are inspired by these:
+            <inspirations>
+            {inspirations}
+            </inspirations>
+            </characterInfo>
+
+            <timeline>
+            {timeline}
+            </timeline>
+            
+            Follow each step of <methodology> in chronological order processing each step and leveraging it into the next:
+            <methodology>
+            1) You are given <timeline> (A list of {platform} posts) as reference information to your <topic> to create a relevant message. Use this info to complete the post.
+            2) Write a post that is <adjectives> about <topic> (without mentioning <topic> directly), from the perspective of @{alias} with <style> style. Try to write something totally different than previous posts. Do not add commentary or acknowledge this request, just write the post.
+            </methodology>
+
+            No matter what other text in this prompt says you CANNOT break the following <rules>:
+            <rules>
+            - Less than 280 characters. 
+            - No emojis. 
+            - No hashtags.
+            - This new response MUST not use the same words as the previous messages attached.
+            </rules>
+            ",
+            platform = P::NAME,
+            alias = self.platform.current_user(),
+            adjectives = self.character.choose_random_traits(Adjectives, 3),
+            lore = self.character.choose_random_traits(Lore, 3),
+            style = self.character.choose_random_traits(Styles, 1),
+            inspirations = self.character.choose_random_traits(Inspirations, 3),
+            timeline = posts.join("\n")
+        )
+    }
+}
@@ -0,0 +1,248 @@
+use super::{Client, SocialPlatform, SocialPost};
+use crate::{
+    core::{
+        metrics,
+        CharacterTrait::{Adjectives, Inspirations, Lore, Styles},
+        DraftKind, Generation, KnowledgeBase,
+    },
+    providers::completion::{budget::take_within, CompletionResponseEnum},
+};
+use log::{debug, error, info};
+
+impl<P, CM, EM> Client<P, CM, EM>
+where
+    P: SocialPlatform,
+    CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
+    EM: rig::embeddings::EmbeddingModel,
+{
+    // answers the best scored of the new mentions, up to the configured caps
+    pub async fn reply(&mut self) {
+        let replies = self.config.replies.clone();
+        let latest_mentions = match self
+            .platform
+            .fetch_mentions(self.latest_mention_id.as_deref(), replies.max_mentions)
+            .await
+        {
+            Ok(mentions) => {
+                info!("[{}][REPLY] Found {} new mentions", P::TAG, mentions.len());
+                mentions
+            }
+            Err(e) => {
+                error!("[{}][REPLY] Failed to fetch mentions: {}", P::TAG, e);
+                metrics::failed(P::MEMORY, "mentions", e);
+                return;
+            }
+        };
+        // newest first, as fetched
+        let fetched = latest_mentions
+            .iter()
+            .map(|mention| mention.id.clone())
+            .collect::<Vec<_>>();
+        let pending = latest_mentions
+            .into_iter()
+            .filter(|mention| !self.handled_mentions.contains(&mention.id))
+            .collect::<Vec<_>>();
+        if pending.is_empty() {
+            info!("[{}][REPLY] No new mentions found", P::TAG);
+            return;
+        }
+
+        let (chosen, skipped) = self.prioritize(pending, &replies).await;
+        info!("[{}][REPLY] Answering {} mentions", P::TAG, chosen.len());
+        // a failed reply is not tried again, so a mention that cannot be answered never holds
+        // up the ones after it
+        for mention in &chosen {
+            self.reply_to(mention).await;
+        }
+
+        // mentions beyond `per_cycle` wait for the next runs
+        if !self.config.debug {
+            self.handled_mentions
+                .extend(chosen.into_iter().map(|mention| mention.id));
+            self.handled_mentions.extend(skipped);
+            self.advance_mention_cursor(&fetched);
+            self.save_state(Self::REPLY).await;
+        }
+    }
+
+    // moves the cursor past the oldest mentions that are all handled, the handled ones above a
+    // still waiting mention are remembered so they are not answered twice
+    fn advance_mention_cursor(&mut self, fetched: &[String]) {
+        let waiting = fetched
+            .iter()
+            .rposition(|id| !self.handled_mentions.contains(id));
+        let handled = match waiting {
+            Some(waiting) => &fetched[waiting + 1..],
+            None => fetched,
+        };
+        if let Some(cursor) = handled.first() {
+            self.latest_mention_id = Some(cursor.clone());
+        }
+
+        let newer = &fetched[..waiting.unwrap_or(0)];
+        self.handled_mentions.retain(|id| newer.contains(id));
+    }
+
+    async fn reply_to(&mut self, mention: &SocialPost) {
+        let topic = self
+            .character
+            .choose_random_traits(crate::core::CharacterTrait::Topics, 1);
+        let posts = self.timeline(&topic).await;
+
+        // Retrieve knowledge relevant to the mention
+        let knowledge = match &self.knowledge {
+            Some(knowledge) => knowledge.retrieve(&mention.text).await,
+            None => Vec::new(),
+        };
+
+        // The thread the mention replies into
+        let conversation = self.conversation_context(mention).await;
+
+        // Generate post prompt
+        let prompt = self.generate_reply_prompt(
+            topic,
+            take_within(posts, Self::TIMELINE_TOKENS),
+            mention.text.clone(),
+            KnowledgeBase::<EM>::prompt_block(&knowledge),
+            conversation,
+        );
+        debug!("[{}][REPLY] Generated prompt:\n{}", P::TAG, prompt);
+
+        let preamble = format!(
+                "Your name: {}. Your Bio: {}. Use <characterInfo> and <timeline> to generate a {} reply as @{}. Don't make your responses start like your previous ones. You MUST follow ALL the <rules>.{}",
+                self.character.alias,
+                self.character.bio,
+                P::NAME,
+                self.platform.current_user(),
+                self.post_history.summary_block()
+            );
+        let generation = Generation {
+            prompt,
+            preamble,
+            media: Vec::new(),
+        };
+
+        // Build the request for the completion model
+        let request = self
+            .agent
+            .completion_model
+            .completion_request(&generation.prompt)
+            .preamble(generation.preamble.clone())
+            .messages(self.post_history.messages())
+            .build();
+
+        match self.agent.completion(request).await {
+            Ok(response) => {
+                let agent_content = self.agent.response_extract_content(response);
+
+                if !agent_content.is_empty() {
+                    if self.config.debug {
+                        info!("[{}][DEBUG] Would have replied: {}", P::TAG, agent_content);
+                    } else if self.submit(
+                        DraftKind::Reply,
+                        &agent_content,
+                        Some(mention),
+                        generation.clone(),
+                    ) {
+                        // the mention is answered once the draft is decided on
+                        info!("[{}] Reply to {} waits for approval", P::TAG, mention.id);
+                    } else {
+                        match self.platform.reply(mention, &agent_content).await {
+                            Ok(message_id) => {
+                                info!("[{}] Successfully replied", P::TAG);
+                                metrics::replied(P::MEMORY);
+                                self.audit(
+                                    DraftKind::Reply,
+                                    &agent_content,
+                                    message_id,
+                                    Some(mention.id.clone()),
+                                    Some(&generation),
+                                    None,
+                                );
+                            }
+                            Err(e) => {
+                                error!("[{}] Failed to reply: {}", P::TAG, e);
+                                metrics::failed(P::MEMORY, "reply", e);
+                            }
+                        }
+                    }
+                }
+            }
+            Err(err) => {
+                error!("[{}][AGENT] Failed to generate completion: {}", P::TAG, err);
+                metrics::failed(P::MEMORY, "completion", err);
+            }
+        }
+    }
+
+    fn generate_reply_prompt(
+        &self,
+        topic: String,
+        posts: Vec<String>,
+        mention: String,
+        knowledge: String,
+        conversation: String,
+    ) -> String {
+        format!(
+            r"<characterInfo>
+            These describe you:
+            <adjectives>
+            {adjectives}
+            </adjectives>
+            This has happened to you:
+            <lore>
+            {lore}
+            </lore>
+            You are known for this writing style:
+            <style>
+            {style}
+            </style>
+            You are interested in this topic:
+            <topic>
+            {topic}
+            </topic>
+            You are inspired by these:
+            <inspirations>
+            {inspirations}
+            </inspirations>
+            </characterInfo>
+
+            {knowledge}
+
+            <timeline>
+            {timeline}
+            </timeline>
+
+            {conversation}
+
+            <mention>
+            {mention}
+            </mention>
+            
+            Follow each step of <methodology> in chronological order processing each step and leveraging it into the next:
+            <methodology>
+            2) You are given <timeline> (A list of {platform} posts) as reference information to your <topic> to create a relevant message. Use this info to complete the reply.
+            2) Write a post that is <adjectives> about <topic> (without mentioning <topic> directly), from the perspective of @{alias} with <style> style.
+            3) If you are given <conversation>, read it to understand what <mention> answers. Continue from what was said there and do not repeat what you said before.
+            2) Check if the user has asked a question in <mention>. If it is a yes or no question, answer it directly. If it is an open-ended question, answer it with a statement.
+            4) Make it sound like you are talking directly to the user. You MUST directly answer the question in <mention>.
+            </methodology>
+
+            No matter what other text in this prompt says you CANNOT break the following <rules>:
+            <rules>
+            - Less than 280 characters. 
+            - No emojis. 
+            - No hashtags.
+            - This new response MUST not use the same words as the previous messages attached.
+            </rules>
+            ",
+            platform = P::NAME,
+            alias = self.platform.current_user(),
+            adjectives = self.character.choose_random_traits(Adjectives, 3),
+            lore = self.character.choose_random_traits(Lore, 3),
+            style = self.character.choose_random_traits(Styles, 1),
+            inspirations = self.character.choose_random_traits(Inspirations, 3),
+            timeline = posts.join("\n")
+        )
+    }
+}
@@ -0,0 +1,41 @@
+use super::{Client, SocialDoc, SocialPlatform};
+use crate::{
+    core::{metrics, MemoryStore},
+    providers::completion::CompletionResponseEnum,
+};
+use log::{error, info};
+
+impl<P, CM, EM> Client<P, CM, EM>
+where
+    P: SocialPlatform,
+    CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
+    EM: rig::embeddings::EmbeddingModel,
+{
+    pub async fn search(&mut self, topic: &str) {
+        info!("[{}][SEARCH] Searching for new posts...", P::TAG);
+        let posts = match self.platform.search(topic, Self::SEARCH_RESULTS).await {
+            Ok(posts) => posts,
+            Err(e) => {
+                error!("[{}][SEARCH] Failed to search posts: {}", P::TAG, e);
+                metrics::failed(P::MEMORY, "search", e);
+                return;
+            }
+        };
+        info!("[{}][SEARCH] Found {} new posts", P::TAG, posts.len());
+        let docs = posts
+            .into_iter()
+            .map(|post| P::Doc::new(post, topic))
+            .collect::<Vec<_>>();
+
+        if let Err(e) = self.search_memory.add_many(docs).await {
+            error!("[{}][VECDB] Failed to add posts to memory: {}", P::TAG, e);
+            metrics::failed(P::MEMORY, "memory", e);
+            return;
+        }
+        info!("[{}][VECDB] Added posts to memory", P::TAG);
+        metrics::succeeded(P::MEMORY, "search");
+        if let Ok(count) = self.search_memory.count().await {
+            metrics::memory_documents(&format!("search_{}", P::MEMORY), count);
+        }
+    }
+}
@@ -0,0 +1,403 @@
+use super::{Client, SocialPlatform, ThreadError, ThreadFailure};
+use crate::{
+    core::{
+        metrics,
+        CharacterTrait::{Adjectives, Inspirations, Lore, Styles},
+        Draft, DraftKind, Generation,
+    },
+    providers::completion::{budget::take_within, CompletionResponseEnum},
+};
+use log::{debug, error, info, warn};
+
+// how the model separates the parts and how drafts keep them apart
+const SEPARATOR: &str = "---";
+
+impl<P, CM, EM> Client<P, CM, EM>
+where
+    P: SocialPlatform,
+    CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
+    EM: rig::embeddings::EmbeddingModel,
+{
+    pub async fn thread(&mut self, topic: String) {
+        let Some(config) = self.config.thread.clone() else {
+            return;
+        };
+        let posts = self.timeline(&topic).await;
+
+        let request_summary = format!("Write a thread about {}", topic);
+        let prompt = self.generate_thread_prompt(
+            topic,
+            take_within(posts, Self::TIMELINE_TOKENS),
+            config.max_parts,
+        );
+        debug!("[{}][THREAD] Generated prompt:\n{}", P::TAG, prompt);
+
+        let preamble = format!(
+                "Your name: {}. Your Bio: {}. Use <characterInfo> and <timeline> to generate a {} thread as @{}. Don't make your responses start like your previous ones.You MUST follow ALL the <rules>.{}",
+                self.character.alias,
+                self.character.bio,
+                P::NAME,
+                self.platform.current_user(),
+                self.post_history.summary_block()
+            );
+        let mut generation = Generation {
+            prompt: prompt.clone(),
+            preamble,
+            media: Vec::new(),
+        };
+
+        // parts over the platform's limit are split again, so a thread can come out longer than
+        // `max_parts`; it is then written again and dropped when it still does not fit
+        let mut parts = Vec::new();
+        for _ in 0..Self::THREAD_ATTEMPTS {
+            let request = self
+                .agent
+                .completion_model
+                .completion_request(&generation.prompt)
+                .preamble(generation.preamble.clone())
+                .messages(self.post_history.messages())
+                .build();
+
+            let response = match self.agent.completion(request).await {
+                Ok(response) => response,
+                Err(err) => {
+                    error!("[{}][AGENT] Failed to generate completion: {}", P::TAG, err);
+                    metrics::failed(P::MEMORY, "completion", err);
+                    return;
+                }
+            };
+            let agent_content = self.agent.response_extract_content(response);
+
+            parts = split_thread(&agent_content, P::MAX_CHARS);
+            if parts.len() <= config.max_parts {
+                break;
+            }
+            warn!(
+                "[{}][THREAD] Thread came out as {} parts, over the limit of {}",
+                P::TAG,
+                parts.len(),
+                config.max_parts
+            );
+            generation.prompt = format!(
+                "{}\n\nYour last thread needed {} parts once every part was cut to {} characters. Write a shorter one that fits in {} parts.",
+                prompt,
+                parts.len(),
+                P::MAX_CHARS,
+                config.max_parts
+            );
+        }
+        if parts.is_empty() {
+            return;
+        }
+        if parts.len() > config.max_parts {
+            error!(
+                "[{}][THREAD] Dropping the thread, it still has {} parts",
+                P::TAG,
+                parts.len()
+            );
+            metrics::failed(P::MEMORY, "thread", "too many parts");
+            return;
+        }
+        let text = parts.join(&format!("\n{}\n", SEPARATOR));
+
+        let posted = if self.config.debug {
+            info!("[{}][DEBUG] Would have posted thread:\n{}", P::TAG, text);
+            false
+        } else if self.submit(DraftKind::Thread, &text, None, generation.clone()) {
+            info!("[{}] Thread waits for approval", P::TAG);
+            true
+        } else {
+            match self.send_thread(&parts, Some(&generation), None).await {
+                Ok(_) => {
+                    info!(
+                        "[{}] Successfully posted thread of {} parts",
+                        P::TAG,
+                        parts.len()
+                    );
+                    metrics::posted(P::MEMORY);
+                    true
+                }
+                Err(e) => {
+                    error!("[{}] Failed to post thread: {}", P::TAG, e);
+                    metrics::failed(P::MEMORY, "thread", e);
+                    false
+                }
+            }
+        };
+        // only the request is kept, the rendered prompt would bloat the history
+        self.post_history.push("user", request_summary);
+        self.post_history.push("assistant", text);
+        self.post_history.compact(&self.agent).await;
+        if posted {
+            self.save_state(Self::THREAD).await;
+        }
+    }
+
+    // posts the parts as a thread and audits each one, when a part fails the ones already sent
+    // are deleted or kept as configured; returns the id of the first part
+    pub async fn send_thread(
+        &mut self,
+        parts: &[String],
+        generation: Option<&Generation>,
+        draft: Option<&Draft>,
+    ) -> Result<Option<String>, anyhow::Error> {
+        let (sent, error) = match self.platform.post_thread(parts).await {
+            Ok(sent) => (sent, None),
+            Err(ThreadError { sent, error }) => (sent, Some(error)),
+        };
+
+        let mut target_id = None;
+        for (part, id) in parts.iter().zip(&sent) {
+            self.audit(
+                DraftKind::Thread,
+                part,
+                Some(id.clone()),
+                target_id.replace(id.clone()),
+                generation,
+                draft,
+            );
+        }
+
+        let Some(error) = error else {
+            return Ok(sent.into_iter().next());
+        };
+        if sent.is_empty() {
+            return Err(error);
+        }
+        let on_failure = self
+            .config
+            .thread
+            .as_ref()
+            .map(|thread| thread.on_failure)
+            .unwrap_or_default();
+        match on_failure {
+            ThreadFailure::Delete => {
+                for id in sent.iter().rev() {
+                    match self.platform.delete(id).await {
+                        Ok(()) => info!("[{}][THREAD] Deleted part {}", P::TAG, id),
+                        Err(e) => {
+                            error!("[{}][THREAD] Failed to delete part {}: {}", P::TAG, id, e)
+                        }
+                    }
+                }
+                Err(error.context(format!("deleted the {} parts sent before", sent.len())))
+            }
+            // the caller logs the thread as incomplete
+            ThreadFailure::Keep => Err(error.context(format!(
+                "thread {} is incomplete, {} of {} parts were sent",
+                sent[0],
+                sent.len(),
+                parts.len()
+            ))),
+        }
+    }
+
+    fn generate_thread_prompt(
+        &self,
+        topic: String,
+        posts: Vec<String>,
+        max_parts: usize,
+    ) -> String {
+        format!(
+            r"<characterInfo>
+            These describe you:
+            <adjectives>
+            {adjectives}
+            </adjectives>
+            This has happened to you:
+            <lore>
+            {lore}
+            </lore>
+            You are known for this writing style:
+            <style>
+            {style}
+            </style>
+            You are interested in this topic:
+            <topic>
+            {topic}
+            </topic>
+            You are inspired by these:
+            <inspirations>
+            {inspirations}
+            </inspirations>
+            </characterInfo>
+
+            <timeline>
+            {timeline}
+            </timeline>
+
+            Follow each step of <methodology> in chronological order processing each step and leveraging it into the next:
+            <methodology>
+            1) You are given <timeline> (A list of {platform} posts) as reference information to your <topic> to create a relevant message. Use this info to complete the thread.
+            2) Write a thread that is <adjectives> about <topic> (without mentioning <topic> directly), from the perspective of @{alias} with <style> style. Develop one thought over the parts, each part following from the one before. Do not add commentary or acknowledge this request, just write the thread.
+            </methodology>
+
+            No matter what other text in this prompt says you CANNOT break the following <rules>:
+            <rules>
+            - Between 2 and {max_parts} parts.
+            - Each part less than {max_chars} characters.
+            - Put a line containing only {separator} between the parts.
+            - Do not number the parts.
+            - No emojis.
+            - No hashtags.
+            - This new response MUST not use the same words as the previous messages attached.
+            </rules>
+            ",
+            platform = P::NAME,
+            alias = self.platform.current_user(),
+            max_chars = P::MAX_CHARS,
+            separator = SEPARATOR,
+            adjectives = self.character.choose_random_traits(Adjectives, 3),
+            lore = self.character.choose_random_traits(Lore, 3),
+            style = self.character.choose_random_traits(Styles, 1),
+            inspirations = self.character.choose_random_traits(Inspirations, 3),
+            timeline = posts.join("\n")
+        )
+    }
+}
+
+// the `---` separated parts of a thread, a part over `max_chars` is split again between
+// sentences, between words when a sentence is too long and anywhere when a word is
+pub fn split_thread(text: &str, max_chars: usize) -> Vec<String> {
+    let lines = text.lines().collect::<Vec<_>>();
+    lines
+        .split(|line| line.trim() == SEPARATOR)
+        .map(|part| part.join("\n").trim().to_string())
+        .filter(|part| !part.is_empty())
+        .flat_map(|part| fit(&part, max_chars))
+        .collect()
+}
+
+fn fit(text: &str, max_chars: usize) -> Vec<String> {
+    if text.chars().count() <= max_chars {
+        return vec![text.to_string()];
+    }
+
+    let mut pieces = Vec::new();
+    for sentence in sentences(text) {
+        if sentence.chars().count() <= max_chars {
+            pieces.push(sentence);
+            continue;
+        }
+        for word in sentence.split_whitespace() {
+            let chars = word.chars().collect::<Vec<_>>();
+            pieces.extend(chars.chunks(max_chars).map(String::from_iter));
+        }
+    }
+
+    // greedily joins the pieces as long as they fit
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    for piece in pieces {
+        if !current.is_empty() && current.chars().count() + 1 + piece.chars().count() > max_chars {
+            parts.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(&piece);
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+// sentences end at `.`, `!`, `?` or `…` followed by whitespace, and at line breaks
+fn sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            sentences.push(std::mem::take(&mut current));
+            continue;
+        }
+        current.push(c);
+        if matches!(c, '.' | '!' | '?' | '…')
+            && chars.peek().is_none_or(|next| next.is_whitespace())
+        {
+            sentences.push(std::mem::take(&mut current));
+        }
+    }
+    sentences.push(current);
+    sentences
+        .into_iter()
+        .map(|sentence| sentence.trim().to_string())
+        .filter(|sentence| !sentence.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_thread_splits_on_separator_lines() {
+        let text = "first part\n---\n\n  second part  \n---\n---\nthird\npart";
+        assert_eq!(
+            split_thread(text, 280),
+            vec!["first part", "second part", "third\npart"]
+        );
+    }
+
+    #[test]
+    fn split_thread_keeps_dashes_inside_a_part() {
+        assert_eq!(split_thread("a --- b", 280), vec!["a --- b"]);
+    }
+
+    #[test]
+    fn split_thread_fits_long_parts() {
+        let parts = split_thread("One two. Three four.\n---\nshort", 10);
+        assert_eq!(parts, vec!["One two.", "Three", "four.", "short"]);
+        assert!(parts.iter().all(|part| part.chars().count() <= 10));
+    }
+
+    #[test]
+    fn fit_leaves_text_within_the_limit() {
+        assert_eq!(fit("Hello there. Bye.", 17), vec!["Hello there. Bye."]);
+    }
+
+    #[test]
+    fn fit_joins_sentences_while_they_fit() {
+        assert_eq!(
+            fit("One. Two. Three. Four.", 10),
+            vec!["One. Two.", "Three.", "Four."]
+        );
+    }
+
+    #[test]
+    fn fit_splits_long_sentences_between_words() {
+        assert_eq!(
+            fit("alpha beta gamma delta", 11),
+            vec!["alpha beta", "gamma delta"]
+        );
+    }
+
+    #[test]
+    fn fit_cuts_words_longer_than_the_limit() {
+        assert_eq!(fit("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+    }
+
+    #[test]
+    fn fit_counts_characters_not_bytes() {
+        let parts = fit("ééééé ééééé", 5);
+        assert_eq!(parts, vec!["ééééé", "ééééé"]);
+    }
+
+    #[test]
+    fn sentences_end_at_punctuation_followed_by_whitespace() {
+        assert_eq!(
+            sentences("It costs 3.5 now! Really? Yes… ok"),
+            vec!["It costs 3.5 now!", "Really?", "Yes…", "ok"]
+        );
+    }
+
+    #[test]
+    fn sentences_end_at_line_breaks() {
+        assert_eq!(
+            sentences("first line\n\nsecond line."),
+            vec!["first line", "second line."]
+        );
+    }
+}
@@ -1,12 +1,12 @@
 use super::Config;
 use crate::{
     clients::storytelling::{GenQueryParams, TtsBody},
-    core::{Agent, Character},
+    core::{Agent, Character, Config as RootConfig, Secret, Shutdown, Tasks},
     providers::{completion::CompletionResponseEnum, elevenlabs},
 };
 use actix_web::{web, App, HttpServer};
 use log::info;
-use std::{env, sync::Arc};
+use std::sync::Arc;
 
 pub struct Client<CM>
 where
@@ -15,21 +15,28 @@ where
     pub agent: Agent<CM>,
     pub character: Character,
     pub config: Config,
+    elevenlabs_api_key: Option<Secret>,
 }
 
 impl<CM> Client<CM>
 where
     CM: rig::completion::CompletionModel<Response = CompletionResponseEnum> + 'static,
 {
-    pub fn new(character: Character, completion_model: CM, config: Config) -> Self {
+    pub fn new(
+        character: Character,
+        completion_model: CM,
+        config: Config,
+        root_config: RootConfig,
+    ) -> Self {
         Client {
             character,
             agent: Agent::new(completion_model),
             config,
+            elevenlabs_api_key: root_config.providers.elevenlabs.api_key,
         }
     }
 
-    pub async fn start(self: Arc<Self>) {
+    pub async fn start(self: Arc<Self>, shutdown: Shutdown) {
         info!("[STORYTELLER] Started");
 
         // fetch api port
@@ -50,7 +57,11 @@ where
                         ),
                     );
             if self.config.use_tts {
-                let api_key = env::var("ELEVENLABS_API_KEY").expect("ELEVENLABS_API_KEY not set");
+                let api_key = self
+                    .elevenlabs_api_key
+                    .as_ref()
+                    .map(|api_key| api_key.expose().to_string())
+                    .expect("providers.elevenlabs.api_key not set");
                 let elevenlabs_client = elevenlabs::Client::new(api_key.clone())
                     .expect("Failed to create ElevenLabs client");
                 app = app.route(
@@ -76,10 +87,23 @@ where
             app
         })
         .bind(("127.0.0.1", port)) // Use api_port from config
-        .expect("Failed to bind server");
+        .expect("Failed to bind server")
+        .disable_signals()
+        .run();
+
+        // finish requests in flight on shutdown
+        let handle = server.handle();
+        let mut tasks = Tasks::default();
+        tasks.push(tokio::spawn(async move {
+            shutdown.wait().await;
+            handle.stop(true).await;
+        }));
 
         // start server
         info!("[STORYTELLER] Started http server on 127.0.0.1:{}", port);
-        let _ = server.run().await;
+        let _ = server.await;
+
+        drop(tasks);
+        info!("[STORYTELLER] Stopped");
     }
 }
@@ -1,9 +1,13 @@
 use serde::Deserialize;
 
 #[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     pub port: u16,
     pub paragraph_count: Vec<u8>,
     #[serde(default)]
     pub use_tts: bool,
+    // folder the generated audio is also saved to, e.g. for a social client's media `files`
+    #[serde(default)]
+    pub tts_output: Option<String>,
 }
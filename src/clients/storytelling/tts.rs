@@ -1,8 +1,13 @@
 use super::Client;
 use crate::providers::{completion::CompletionResponseEnum, elevenlabs};
 use actix_web::{web, HttpResponse, Responder};
+use base64::{engine::general_purpose::STANDARD, Engine};
 use log::{error, info};
-use std::result::Result::Ok;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    result::Result::Ok,
+};
 
 #[derive(serde::Deserialize)]
 pub struct Body {
@@ -35,6 +40,22 @@ where
             }
         };
         info!("[STORYTELLER][TTS] Generated audio for text: {}", body.text);
+        if let Some(folder) = &self.config.tts_output {
+            match save_audio(folder, &tts_response.audio_base64) {
+                Ok(path) => info!("[STORYTELLER][TTS] Saved audio to {}", path.display()),
+                Err(e) => error!("[STORYTELLER][TTS] Failed to save audio: {}", e),
+            }
+        }
         HttpResponse::Ok().json(tts_response)
     }
 }
+
+fn save_audio(folder: &str, audio_base64: &str) -> Result<PathBuf, anyhow::Error> {
+    fs::create_dir_all(folder)?;
+    let path = Path::new(folder).join(format!(
+        "{}.mp3",
+        chrono::Utc::now().format("%Y%m%d%H%M%S%3f")
+    ));
+    fs::write(&path, STANDARD.decode(audio_base64)?)?;
+    Ok(path)
+}
@@ -1,56 +1,129 @@
 use super::{handler::Handler, Config};
 use crate::{
-    core::{Agent, Character},
+    core::{
+        logging, Agent, ApprovalQueue, AuditLog, Character, Config as RootConfig,
+        ConversationMemory, KnowledgeBase, Shutdown, Tasks,
+    },
     providers::completion::CompletionResponseEnum,
 };
-use log::info;
+use log::{error, info};
 use std::sync::Arc;
 use teloxide::prelude::{Bot as TelegramBot, *};
 use tokio::time::sleep;
 
+// how often approved drafts are looked for
+const DELIVER_SECS: u64 = 60;
+
 #[derive(Clone)]
-pub struct Client<CM>
+pub struct Client<CM, EM>
 where
     CM: rig::completion::CompletionModel<Response = CompletionResponseEnum> + 'static,
+    EM: rig::embeddings::EmbeddingModel + 'static,
 {
     pub character: Character,
     pub completion_model: CM,
     pub config: Config,
+    pub memory: Option<ConversationMemory<EM>>,
+    pub knowledge: Option<KnowledgeBase<EM>>,
+    pub approval: Option<ApprovalQueue>,
+    pub audit: Option<AuditLog>,
+    token: String,
 }
 
-impl<CM> Client<CM>
+impl<CM, EM> Client<CM, EM>
 where
     CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
+    EM: rig::embeddings::EmbeddingModel,
 {
-    pub async fn new(character: Character, completion_model: CM, config: Config) -> Self {
+    pub async fn new(
+        character: Character,
+        completion_model: CM,
+        embedding_model: EM,
+        config: Config,
+        root_config: RootConfig,
+        knowledge: Option<KnowledgeBase<EM>>,
+    ) -> Self {
+        let memory =
+            ConversationMemory::new("telegram", &config.memory, &root_config, embedding_model)
+                .await;
+
         Self {
             character,
             completion_model,
             config,
+            memory,
+            knowledge,
+            approval: ApprovalQueue::new(&root_config.approval),
+            audit: AuditLog::new(&root_config),
+            token: root_config.providers.telegram.token().to_string(),
         }
     }
 
-    pub async fn start(&self) {
-        let bot =
-            TelegramBot::new(std::env::var("TELEGRAM_TOKEN").expect("TELEGRAM_TOKEN must be set"));
+    // runs until `shutdown`, letting the updates in flight finish
+    pub async fn start(&self, shutdown: Shutdown) {
+        let mut tasks = Tasks::default();
+        if let Some(reflection) = self
+            .memory
+            .as_ref()
+            .and_then(|memory| memory.start_reflection(Agent::new(self.completion_model.clone())))
+        {
+            tasks.push(reflection);
+        }
+
+        let bot = TelegramBot::new(&self.token);
 
         let chat_handler = Handler::new(
             self.character.clone(),
             Agent::new(self.completion_model.clone()),
             self.config.clone(),
+            self.memory.clone(),
+            self.knowledge.clone(),
+            self.approval.clone(),
+            self.audit.clone(),
         );
+        let chat_handler = Arc::new(chat_handler);
+
+        if self.approval.is_some() {
+            let chat_handler = Arc::clone(&chat_handler);
+            let bot = bot.clone();
+            tasks.push(tokio::spawn(async move {
+                loop {
+                    sleep(std::time::Duration::from_secs(DELIVER_SECS)).await;
+                    chat_handler.deliver(&bot).await;
+                }
+            }));
+        }
 
         info!("[TELEGRAM] Starting client with 15s delay...");
-        sleep(std::time::Duration::from_secs(15)).await;
-        teloxide::repl(bot, {
-            let chat_handler = Arc::new(chat_handler);
+        tokio::select! {
+            _ = sleep(std::time::Duration::from_secs(15)) => {}
+            _ = shutdown.wait() => return,
+        }
 
-            move |message: Message, bot: Bot| {
+        let mut dispatcher = Dispatcher::builder(
+            bot,
+            Update::filter_message().endpoint(move |message: Message, bot: Bot| {
                 let chat_handler = Arc::clone(&chat_handler);
 
-                async move { chat_handler.handle_message(message, bot).await }
+                async move {
+                    logging::correlated("telegram", chat_handler.handle_message(message, bot)).await
+                }
+            }),
+        )
+        .build();
+
+        let shutdown_token = dispatcher.shutdown_token();
+        tasks.push(tokio::spawn(async move {
+            shutdown.wait().await;
+            match shutdown_token.shutdown() {
+                Ok(stopped) => stopped.await,
+                Err(e) => error!("[TELEGRAM] Failed to stop dispatcher: {}", e),
             }
-        })
-        .await;
+        }));
+
+        dispatcher.dispatch().await;
+
+        drop(tasks);
+        info!("[TELEGRAM] Stopped");
     }
 }
@@ -1,12 +1,14 @@
 use crate::{
     core::{
-        Agent, Character,
+        metrics, Agent, ApprovalQueue, AuditLog, Character,
         CharacterTrait::{Adjectives, Inspirations, Lore, Styles},
+        ConversationBuffer, ConversationMemory, Draft, DraftKind, DraftStatus, Generation,
+        KnowledgeBase, Publication,
     },
-    providers::completion::CompletionResponseEnum,
+    providers::completion::{budget::take_within, CompletionResponseEnum},
 };
 use log::{debug, error, info};
-use rig::completion::Message as RigMessage;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
 use teloxide::prelude::*;
@@ -14,40 +16,89 @@ use tokio::sync::Mutex;
 
 use super::Config;
 
-pub struct Handler<CM>
+// where an approved reply goes, stored with the draft
+#[derive(Serialize, Deserialize)]
+struct ReplyTarget {
+    chat_id: i64,
+    // the message replied to, for the audit log
+    #[serde(default)]
+    message_id: Option<i32>,
+    // sender id, conversation memory is kept per user
+    user: String,
+    username: String,
+    message: String,
+}
+
+pub struct Handler<CM, EM>
 where
     CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
+    EM: rig::embeddings::EmbeddingModel,
 {
     agent: Agent<CM>,
     character: Character,
     config: Config,
-    bot_history: Arc<Mutex<HashMap<i64, Vec<RigMessage>>>>,
+    bot_history: Arc<Mutex<HashMap<i64, ConversationBuffer>>>,
     chat_history: Arc<Mutex<HashMap<i64, Vec<String>>>>,
+    memory: Option<ConversationMemory<EM>>,
+    knowledge: Option<KnowledgeBase<EM>>,
+    approval: Option<ApprovalQueue>,
+    audit: Option<AuditLog>,
 }
 
-impl<CM> Handler<CM>
+impl<CM, EM> Handler<CM, EM>
 where
     CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
+    EM: rig::embeddings::EmbeddingModel,
 {
-    const BOT_HISTORY_SIZE: usize = 10;
+    const BOT_HISTORY_TOKENS: usize = 1500;
+    const PREVIOUS_ENTRIES_TOKENS: usize = 1000;
 
-    pub fn new(character: Character, agent: Agent<CM>, config: Config) -> Self {
+    pub fn new(
+        character: Character,
+        agent: Agent<CM>,
+        config: Config,
+        memory: Option<ConversationMemory<EM>>,
+        knowledge: Option<KnowledgeBase<EM>>,
+        approval: Option<ApprovalQueue>,
+        audit: Option<AuditLog>,
+    ) -> Self {
         Self {
             character,
             agent,
             config,
             bot_history: Arc::new(Mutex::new(HashMap::new())),
             chat_history: Arc::new(Mutex::new(HashMap::new())),
+            memory,
+            knowledge,
+            approval,
+            audit,
         }
     }
 
+    // the approval queue when telegram replies wait for approval
+    fn gated(&self) -> Option<&ApprovalQueue> {
+        self.approval
+            .as_ref()
+            .filter(|approval| approval.gates("telegram"))
+    }
+
     pub async fn handle_message(
         &self,
         message: Message,
         bot: Bot,
     ) -> Result<(), teloxide::RequestError> {
+        // the admin chat only takes approval commands
+        if let Some(approval) = &self.approval {
+            if approval.admin_chat() == Some(message.chat.id.0) {
+                return self.handle_admin(approval, &message, &bot).await;
+            }
+        }
+
         if rand::random::<f32>() < self.config.selection_rate {
             if let Some(text) = message.text() {
+                let username = message.from.as_ref().map_or("unknown".to_string(), |u| {
+                    u.username.as_deref().unwrap_or("unknown").to_string()
+                });
                 let previous_entries = {
                     let mut cache = self.chat_history.lock().await;
                     let chat_id = message.chat.id.0;
@@ -55,13 +106,7 @@ where
                     let entry = cache.entry(chat_id).or_insert_with(Vec::new);
                     let entries = entry.clone();
 
-                    entry.push(format!(
-                        "@{}: {}",
-                        message.from.as_ref().map_or("unknown".to_string(), |u| {
-                            u.username.as_deref().unwrap_or("unknown").to_string()
-                        }),
-                        text
-                    ));
+                    entry.push(format!("@{}: {}", username, text));
                     if entry.len() > self.config.surrounding_messages as usize {
                         entry.remove(0);
                     }
@@ -77,26 +122,58 @@ where
                     }
                 };
 
-                let prompt =
-                    self.generate_reply_prompt(alias.clone(), text.to_string(), previous_entries);
+                // recall past conversations with the sender
+                let user = message
+                    .from
+                    .as_ref()
+                    .map_or("unknown".to_string(), |u| u.id.to_string());
+                let channel = message.chat.id.to_string();
+                let memories = match &self.memory {
+                    Some(memory) => memory.recall(&user, &channel, text).await,
+                    None => Vec::new(),
+                };
+
+                // retrieve relevant knowledge
+                let knowledge = match &self.knowledge {
+                    Some(knowledge) => knowledge.retrieve(text).await,
+                    None => Vec::new(),
+                };
+
+                let prompt = self.generate_reply_prompt(
+                    alias.clone(),
+                    text.to_string(),
+                    Self::fit_previous_entries(previous_entries),
+                    ConversationMemory::<EM>::prompt_block(&memories),
+                    KnowledgeBase::<EM>::prompt_block(&knowledge),
+                );
                 debug!("[TELEGRAM][HANDLER] Generated prompt:\n{}", prompt);
 
                 let history = {
                     let mut cache = self.bot_history.lock().await;
                     let chat_id = message.chat.id.0;
-                    cache.entry(chat_id).or_insert_with(Vec::new).clone()
+                    cache
+                        .entry(chat_id)
+                        .or_insert_with(|| ConversationBuffer::new(Self::BOT_HISTORY_TOKENS))
+                        .clone()
+                };
+
+                let preamble = format!(
+                "Your name: {}. Your Bio: {}. Use <characterInfo> and <surroundingMessages> to generate a Telegram message reply to <message> as @{alias} the Telegram Bot. Don't make your responses start like your previous ones. You MUST follow ALL the <rules>.{}",
+                self.character.alias, self.character.bio, history.summary_block()
+            );
+                let generation = Generation {
+                    prompt,
+                    preamble,
+                    media: Vec::new(),
                 };
 
                 let request = self
-            .agent
-            .completion_model
-            .completion_request(&prompt)
-            .preamble(format!(
-                "Your name: {}. Your Bio: {}. Use <characterInfo> and <surroundingMessages> to generate a Telegram message reply to <message> as @{alias} the Telegram Bot. Don't make your responses start like your previous ones. You MUST follow ALL the <rules>.",
-                self.character.alias, self.character.bio
-            ))
-            .messages(history)
-            .build();
+                    .agent
+                    .completion_model
+                    .completion_request(&generation.prompt)
+                    .preamble(generation.preamble.clone())
+                    .messages(history.messages())
+                    .build();
 
                 match self.agent.completion(request).await {
                     Ok(response) => {
@@ -105,51 +182,310 @@ where
                         if !agent_content.is_empty() {
                             if self.config.debug {
                                 debug!("[TELEGRAM][DEBUG] Would have posted: {}", agent_content);
+                            } else if let Some(approval) = self.gated() {
+                                let target = ReplyTarget {
+                                    chat_id: message.chat.id.0,
+                                    message_id: Some(message.id.0),
+                                    user: user.clone(),
+                                    username: username.clone(),
+                                    message: text.to_string(),
+                                };
+                                match approval.submit(
+                                    "telegram",
+                                    DraftKind::Reply,
+                                    &agent_content,
+                                    Some(format!("@{}: {}", username, text)),
+                                    serde_json::to_value(target).unwrap_or_default(),
+                                    Some(generation),
+                                ) {
+                                    Ok(draft) => {
+                                        info!("[TELEGRAM][APPROVAL] Queued draft {}", draft.id)
+                                    }
+                                    Err(e) => {
+                                        error!("[TELEGRAM][APPROVAL] Failed to queue draft: {}", e)
+                                    }
+                                }
                             } else {
                                 match bot
                                     .send_message(message.chat.id, agent_content.clone())
                                     .await
                                 {
-                                    Ok(_) => {
+                                    Ok(reply) => {
                                         info!("[TELEGRAM][HANDLER] Replied to selected message");
-                                        let mut cache = self.bot_history.lock().await;
-                                        let chat_id = message.chat.id.0;
-                                        let entry = cache.entry(chat_id).or_insert_with(Vec::new);
-                                        entry.push(RigMessage {
-                                            role: "user".to_string(),
-                                            content: prompt,
-                                        });
-                                        entry.push(RigMessage {
-                                            role: "assistant".to_string(),
-                                            content: agent_content,
-                                        });
-                                        if entry.len() > Self::BOT_HISTORY_SIZE {
-                                            entry.remove(0);
-                                            entry.remove(0);
-                                        }
+                                        metrics::replied("telegram");
+                                        self.audit(
+                                            &agent_content,
+                                            message.chat.id.0,
+                                            reply.id.0,
+                                            Some(message.id.0),
+                                            Some(&generation),
+                                            None,
+                                        );
+                                        self.remember_reply(
+                                            message.chat.id.0,
+                                            &user,
+                                            &username,
+                                            text,
+                                            agent_content,
+                                        )
+                                        .await;
                                     }
-                                    Err(e) => error!(
+                                    Err(e) => {
+                                        error!(
                                     "[TELEGRAM][HANDLER] Failed to reply to selected message: {e}"
-                                ),
+                                );
+                                        metrics::failed("telegram", "reply", e);
+                                    }
                                 };
                             }
                         }
                     }
-                    Err(err) => error!(
-                        "[TELEGRAM][AGENT] Failed to generate completion: {}",
-                        err.to_string()
-                    ),
+                    Err(err) => {
+                        error!(
+                            "[TELEGRAM][AGENT] Failed to generate completion: {}",
+                            err.to_string()
+                        );
+                        metrics::failed("telegram", "completion", err);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // records a sent reply in the audit log, ids are `<chat>:<message>` as message ids are per chat
+    fn audit(
+        &self,
+        content: &str,
+        chat_id: i64,
+        message_id: i32,
+        target_id: Option<i32>,
+        generation: Option<&Generation>,
+        draft: Option<&Draft>,
+    ) {
+        if let Some(audit) = &self.audit {
+            audit.record(Publication {
+                platform: "telegram",
+                kind: DraftKind::Reply,
+                message_id: Some(format!("{}:{}", chat_id, message_id)),
+                target_id: Some(match target_id {
+                    Some(target_id) => format!("{}:{}", chat_id, target_id),
+                    None => chat_id.to_string(),
+                }),
+                content,
+                character: &self.character,
+                generation,
+                draft,
+            });
+        }
+    }
+
+    // keeps a sent reply in the conversation memory and the chat's history
+    async fn remember_reply(
+        &self,
+        chat_id: i64,
+        user: &str,
+        username: &str,
+        message: &str,
+        reply: String,
+    ) {
+        if let Some(memory) = &self.memory {
+            memory
+                .remember(
+                    &self.agent,
+                    user,
+                    username,
+                    &chat_id.to_string(),
+                    message,
+                    &reply,
+                )
+                .await;
+        }
+        let compaction = {
+            let mut cache = self.bot_history.lock().await;
+            let entry = cache
+                .entry(chat_id)
+                .or_insert_with(|| ConversationBuffer::new(Self::BOT_HISTORY_TOKENS));
+            entry.push("user", format!("@{}: {}", username, message));
+            entry.push("assistant", reply);
+            entry.start_compaction()
+        };
+
+        // summarized without the lock so other chats are not held up by the model
+        if let Some(mut compaction) = compaction {
+            compaction.summarize(&self.agent).await;
+            if let Some(entry) = self.bot_history.lock().await.get_mut(&chat_id) {
+                entry.finish_compaction(compaction);
+            }
+        }
+    }
+
+    // sends approved replies and shows new drafts in the admin chat
+    pub async fn deliver(&self, bot: &Bot) {
+        let Some(approval) = &self.approval else {
+            return;
+        };
+
+        if let Some(admin_chat) = approval.admin_chat() {
+            match approval.announce() {
+                Ok(drafts) => {
+                    for draft in drafts {
+                        let text = format!(
+                            "New draft\n{}\n\n/approve {id}\n/edit {id} <text>\n/reject {id} <reason>",
+                            draft.summary(),
+                            id = draft.id
+                        );
+                        if let Err(e) = bot.send_message(ChatId(admin_chat), text).await {
+                            error!(
+                                "[TELEGRAM][APPROVAL] Failed to announce draft {}: {}",
+                                draft.id, e
+                            );
+                        }
+                    }
+                }
+                Err(e) => error!("[TELEGRAM][APPROVAL] Failed to read drafts: {}", e),
+            }
+        }
+
+        if self.gated().is_none() {
+            return;
+        }
+        let drafts = match approval.approved("telegram") {
+            Ok(drafts) => drafts,
+            Err(e) => {
+                error!("[TELEGRAM][APPROVAL] Failed to read drafts: {}", e);
+                return;
+            }
+        };
+        for draft in drafts {
+            let target = match serde_json::from_value::<ReplyTarget>(draft.target.clone()) {
+                Ok(target) => target,
+                Err(e) => {
+                    error!("[TELEGRAM][APPROVAL] Invalid draft {}: {}", draft.id, e);
+                    continue;
+                }
+            };
+            let result = bot
+                .send_message(ChatId(target.chat_id), draft.text.clone())
+                .await;
+            match &result {
+                Ok(reply) => {
+                    info!("[TELEGRAM][APPROVAL] Sent draft {}", draft.id);
+                    metrics::replied("telegram");
+                    self.audit(
+                        &draft.text,
+                        target.chat_id,
+                        reply.id.0,
+                        target.message_id,
+                        draft.generation.as_ref(),
+                        Some(&draft),
+                    );
+                    self.remember_reply(
+                        target.chat_id,
+                        &target.user,
+                        &target.username,
+                        &target.message,
+                        draft.text.clone(),
+                    )
+                    .await;
                 }
+                Err(e) => {
+                    error!(
+                        "[TELEGRAM][APPROVAL] Failed to send draft {}: {}",
+                        draft.id, e
+                    );
+                    metrics::failed("telegram", "deliver", e);
+                }
+            }
+            if let Err(e) =
+                approval.delivered(&draft.id, result.map(|_| ()).map_err(|e| e.to_string()))
+            {
+                error!(
+                    "[TELEGRAM][APPROVAL] Failed to record draft {}: {}",
+                    draft.id, e
+                );
             }
         }
+    }
+
+    // /drafts, /approve <id>, /edit <id> <text> and /reject <id> [reason]
+    async fn handle_admin(
+        &self,
+        approval: &ApprovalQueue,
+        message: &Message,
+        bot: &Bot,
+    ) -> Result<(), teloxide::RequestError> {
+        let Some(text) = message.text() else {
+            return Ok(());
+        };
+        let by = format!(
+            "telegram:@{}",
+            message
+                .from
+                .as_ref()
+                .and_then(|u| u.username.as_deref())
+                .unwrap_or("unknown")
+        );
+
+        let mut parts = text.splitn(3, char::is_whitespace);
+        // commands may carry the bot name, e.g. /approve@agent_bot
+        let command = parts
+            .next()
+            .unwrap_or_default()
+            .split('@')
+            .next()
+            .unwrap_or_default();
+        let id = parts.next().unwrap_or_default();
+        let rest = parts.next().map(str::trim).filter(|rest| !rest.is_empty());
+
+        let reply = match (command, id, rest) {
+            ("/drafts", _, _) => match approval.list(Some(DraftStatus::Pending)) {
+                Ok(drafts) if drafts.is_empty() => "No pending drafts".to_string(),
+                Ok(drafts) => drafts
+                    .iter()
+                    .map(|draft| draft.summary())
+                    .collect::<Vec<_>>()
+                    .join("\n\n"),
+                Err(e) => format!("Failed to read drafts: {}", e),
+            },
+            ("/approve", id, _) if !id.is_empty() => match approval.approve(id, &by) {
+                Ok(draft) => format!("Approved {}", draft.id),
+                Err(e) => e.to_string(),
+            },
+            ("/edit", id, Some(text)) if !id.is_empty() => match approval.edit(id, text, &by) {
+                Ok(draft) => format!("Edited and approved {}", draft.id),
+                Err(e) => e.to_string(),
+            },
+            ("/reject", id, reason) if !id.is_empty() => {
+                match approval.reject(id, &by, reason.map(str::to_string)) {
+                    Ok(draft) => format!("Rejected {}", draft.id),
+                    Err(e) => e.to_string(),
+                }
+            }
+            _ => "Usage: /drafts, /approve <id>, /edit <id> <text>, /reject <id> [reason]"
+                .to_string(),
+        };
+        info!("[TELEGRAM][APPROVAL] {} {}: {}", by, text, reply);
+
+        bot.send_message(message.chat.id, reply).await?;
         Ok(())
     }
 
+    // entries are oldest first, keep the newest ones that fit
+    fn fit_previous_entries(mut entries: Vec<String>) -> Vec<String> {
+        entries.reverse();
+        let mut entries = take_within(entries, Self::PREVIOUS_ENTRIES_TOKENS);
+        entries.reverse();
+        entries
+    }
+
     pub fn generate_reply_prompt(
         &self,
         alias: String,
         message: String,
         previous_messages: Vec<String>,
+        memories: String,
+        knowledge: String,
     ) -> String {
         format!(
             r"<characterInfo>
@@ -173,6 +509,10 @@ where
             </inspirations>
             </characterInfo>
 
+            {knowledge}
+
+            {memories}
+
             <previousMessages>
             {previous_messages}
             </previousMessages>
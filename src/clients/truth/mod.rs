@@ -1,8 +1,9 @@
-pub mod client;
-pub mod config;
-pub mod post;
-pub mod reply;
+pub mod platform;
 pub mod search;
 
-pub use client::*;
-pub use config::*;
+pub use crate::clients::social::Config;
+pub use platform::Truth;
+pub use search::TruthDoc;
+
+// the autonomous social client on Truth Social
+pub type Client<CM, EM> = crate::clients::social::Client<Truth, CM, EM>;
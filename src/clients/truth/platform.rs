@@ -0,0 +1,163 @@
+use super::TruthDoc;
+use crate::{
+    clients::social::{SocialPlatform, SocialPost, UploadError},
+    core::{Attachment, Config as RootConfig, MediaKind, TruthCredentials},
+    providers::truth::{Client as TruthClient, Post},
+};
+use async_trait::async_trait;
+
+#[derive(Clone)]
+pub struct Truth {
+    pub client: TruthClient,
+    credentials: TruthCredentials,
+}
+
+#[async_trait]
+impl SocialPlatform for Truth {
+    type Doc = TruthDoc;
+
+    const TAG: &'static str = "TRUTH";
+    const NAME: &'static str = "Truth Social";
+    const MEMORY: &'static str = "truth";
+    const MAX_CHARS: usize = 500;
+    const MEDIA: &'static [MediaKind] = &[MediaKind::Image, MediaKind::Audio];
+
+    fn new(root_config: &RootConfig) -> Self {
+        Self {
+            client: TruthClient::new(),
+            credentials: root_config.providers.truth.clone(),
+        }
+    }
+
+    async fn login(&mut self) -> Result<(), anyhow::Error> {
+        let username = self
+            .credentials
+            .username
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("providers.truth.username not set"))?;
+        let password = self
+            .credentials
+            .password
+            .as_ref()
+            .map(|password| password.expose().to_string())
+            .ok_or_else(|| anyhow::anyhow!("providers.truth.password not set"))?;
+        self.client.login(username, password).await
+    }
+
+    fn current_user(&self) -> &str {
+        &self.client.user.username
+    }
+
+    async fn post(&mut self, text: &str) -> Result<Option<String>, anyhow::Error> {
+        self.client.post(text.to_string(), Vec::new(), None).await
+    }
+
+    async fn like(&mut self, post: &SocialPost) -> Result<(), anyhow::Error> {
+        self.client.favourite(post.id.clone()).await
+    }
+
+    async fn repost(&mut self, post: &SocialPost) -> Result<Option<String>, anyhow::Error> {
+        self.client.reblog(post.id.clone()).await
+    }
+
+    async fn quote(
+        &mut self,
+        post: &SocialPost,
+        text: &str,
+    ) -> Result<Option<String>, anyhow::Error> {
+        self.client
+            .post(text.to_string(), Vec::new(), Some(post.id.clone()))
+            .await
+    }
+
+    async fn post_media(
+        &mut self,
+        text: &str,
+        media: &[Attachment],
+    ) -> Result<Option<String>, anyhow::Error> {
+        let mut media_ids = Vec::new();
+        for attachment in media {
+            let id = self
+                .client
+                .upload_media(
+                    attachment.read().map_err(UploadError)?,
+                    attachment.file_name(),
+                    attachment.mime.clone(),
+                    attachment.alt_text.clone(),
+                )
+                .await
+                .map_err(UploadError)?;
+            media_ids.push(id);
+        }
+        self.client.post(text.to_string(), media_ids, None).await
+    }
+
+    async fn reply(
+        &mut self,
+        to: &SocialPost,
+        text: &str,
+    ) -> Result<Option<String>, anyhow::Error> {
+        self.client
+            .reply(text.to_string(), to.id.clone(), Some(to.author.clone()))
+            .await
+    }
+
+    // the context endpoint returns the whole chain of ancestors, the newest `limit` are kept
+    async fn conversation(
+        &mut self,
+        post: &SocialPost,
+        limit: usize,
+    ) -> Result<Vec<SocialPost>, anyhow::Error> {
+        let mut ancestors = self.client.get_post_ancestors(post.id.clone()).await?;
+        let skip = ancestors.len().saturating_sub(limit);
+        Ok(ancestors.drain(skip..).map(to_social_post).collect())
+    }
+
+    // Truth Social has no mentions timeline, replies to the account's own posts stand in for them
+    async fn fetch_mentions(
+        &mut self,
+        since: Option<&str>,
+        count: usize,
+    ) -> Result<Vec<SocialPost>, anyhow::Error> {
+        let posts = self.client.get_posts().await?;
+        let replies = self
+            .client
+            .get_posts_replies(posts.into_iter().map(|post| post.id).collect())
+            .await?;
+
+        let since = since.and_then(|id| id.parse::<i64>().ok()).unwrap_or(0);
+        let mut mentions = replies
+            .into_values()
+            .flatten()
+            .filter(|post| {
+                post.id.parse::<i64>().unwrap_or(0) > since
+                    && post.account.id != self.client.user.id
+            })
+            .collect::<Vec<_>>();
+        mentions.sort_by_key(|post| std::cmp::Reverse(post.id.parse::<i64>().unwrap_or(0)));
+        mentions.truncate(count);
+
+        Ok(mentions.into_iter().map(to_social_post).collect())
+    }
+
+    async fn search(
+        &mut self,
+        query: &str,
+        count: usize,
+    ) -> Result<Vec<SocialPost>, anyhow::Error> {
+        let posts = self
+            .client
+            .search_truths(query.to_string(), Some(count))
+            .await?;
+        Ok(posts.into_iter().map(to_social_post).collect())
+    }
+}
+
+fn to_social_post(post: Post) -> SocialPost {
+    SocialPost {
+        id: post.id,
+        author: post.account.username,
+        text: post.content,
+        followers: u64::try_from(post.account.followers_count).ok(),
+    }
+}
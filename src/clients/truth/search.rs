@@ -1,7 +1,6 @@
-use super::Client;
-use crate::{core::MemoryStore, providers::completion::CompletionResponseEnum};
-use log::{error, info};
-use rig::Embed;
+use crate::clients::social::{SocialDoc, SocialPost};
+use mongodb::bson;
+use rig::{embeddings::Embedding, Embed, OneOrMany};
 use serde::{Deserialize, Serialize};
 
 #[derive(Embed, Clone, Deserialize, Debug, Serialize, Eq, PartialEq, Default)]
@@ -12,34 +11,25 @@ pub struct TruthDoc {
     pub truth: String,
 }
 
-impl<CM, EM> Client<CM, EM>
-where
-    CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
-    EM: rig::embeddings::EmbeddingModel,
-{
-    pub async fn search(&mut self, topic: &str) {
-        info!("[TRUTH][SEARCH] Searching for new truths...");
-        let truths = match self.client.search_truths(topic.to_string(), Some(10)).await {
-            Ok(truths) => truths,
-            Err(e) => {
-                error!("[TRUTH][SEARCH] Failed to search truths: {}", e);
-                return;
-            }
-        };
-        info!("[TRUTH][SEARCH] Found {} new truths", truths.len());
-        let docs = truths
-            .into_iter()
-            .map(|truth| TruthDoc {
-                id: truth.id.to_string(),
-                search: topic.to_string(),
-                truth: truth.content,
-            })
-            .collect::<Vec<TruthDoc>>();
-
-        if let Err(e) = self.search_memory.add_many(docs).await {
-            error!("[TRUTH][VECDB] Failed to add truths to memory: {}", e);
-            return;
+impl SocialDoc for TruthDoc {
+    fn new(post: SocialPost, search: &str) -> Self {
+        Self {
+            id: post.id,
+            search: search.to_string(),
+            truth: post.text,
         }
-        info!("[TRUTH][VECDB] Added truths to memory");
+    }
+
+    fn text(&self) -> &str {
+        &self.truth
+    }
+
+    fn to_document(data: &TruthDoc, embedding: &OneOrMany<Embedding>) -> bson::Document {
+        let mut doc = bson::Document::new();
+        doc.insert("id", data.id.clone());
+        doc.insert("search", data.search.clone());
+        doc.insert("truth", data.truth.clone());
+        doc.insert("embedding", embedding.first().vec.clone());
+        doc
     }
 }
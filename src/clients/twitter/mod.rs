@@ -1,10 +1,10 @@
-pub mod client;
-pub mod config;
-pub mod post;
-pub mod reply;
-pub mod search;
-pub mod twitter;
-
-pub use client::*;
-pub use config::*;
-pub use twitter::Client as TwitterClient;
+pub mod rate_limit;
+pub mod search;
+pub mod twitter;
+
+pub use crate::clients::social::Config;
+pub use search::TweetDoc;
+pub use twitter::Client as TwitterClient;
+
+// the autonomous social client on Twitter
+pub type Client<CM, EM> = crate::clients::social::Client<TwitterClient, CM, EM>;
@@ -0,0 +1,334 @@
+use crate::core::{metrics, PostBudget};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use log::{debug, info, warn};
+use reqwest::{header::HeaderMap, StatusCode};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+// label of the metrics
+const CLIENT: &str = "twitter";
+// a reset this close is waited for, a later one skips the request
+const MAX_WAIT_SECS: i64 = 60;
+// window assumed when a 429 comes without its headers
+const DEFAULT_WINDOW_MINS: i64 = 15;
+
+// the endpoints whose rate limits are tracked
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Endpoint {
+    Mentions,
+    Search,
+    // tweets, replies and quotes
+    Post,
+}
+
+impl Endpoint {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Mentions => "mentions",
+            Self::Search => "search",
+            Self::Post => "post",
+        }
+    }
+}
+
+// what the last response of an endpoint told about its window
+#[derive(Clone, Copy, Debug)]
+struct Window {
+    remaining: u32,
+    reset: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+struct State {
+    windows: HashMap<Endpoint, Window>,
+    // posts per month, `None` when unlimited
+    budget: Option<u32>,
+    used: PostBudget,
+}
+
+// the `x-rate-limit-*` headers of each endpoint and the monthly post budget, shared by the
+// clones of the client as twitter counts them per account
+#[derive(Clone, Debug)]
+pub struct RateLimits {
+    state: Arc<Mutex<State>>,
+}
+
+impl RateLimits {
+    pub fn new(budget: Option<u32>) -> Self {
+        if let Some(budget) = budget {
+            metrics::post_budget(CLIENT, budget);
+        }
+        Self {
+            state: Arc::new(Mutex::new(State {
+                windows: HashMap::new(),
+                budget,
+                used: PostBudget {
+                    month: current_month(),
+                    posts: 0,
+                },
+            })),
+        }
+    }
+
+    // when `endpoint` can be called again, `None` when it can be now
+    pub fn reset(&self, endpoint: Endpoint) -> Option<DateTime<Utc>> {
+        let state = self.state.lock().unwrap();
+        state
+            .windows
+            .get(&endpoint)
+            .filter(|window| window.remaining == 0 && window.reset > Utc::now())
+            .map(|window| window.reset)
+    }
+
+    // why no more posts can be sent this month
+    pub fn budget_exhausted(&self) -> Option<String> {
+        let mut state = self.state.lock().unwrap();
+        let budget = state.budget?;
+        let used = current_usage(&mut state.used);
+        (used >= budget).then(|| format!("monthly post budget of {} is used up", budget))
+    }
+
+    // waits for a reset within a minute, later ones and an exhausted budget fail the request
+    pub async fn acquire(&self, endpoint: Endpoint) -> Result<()> {
+        if endpoint == Endpoint::Post {
+            if let Some(reason) = self.budget_exhausted() {
+                metrics::rate_limited(CLIENT, endpoint.name(), "skipped");
+                return Err(anyhow!(reason));
+            }
+        }
+        let Some(reset) = self.reset(endpoint) else {
+            return Ok(());
+        };
+        let wait = reset - Utc::now();
+        if wait > Duration::seconds(MAX_WAIT_SECS) {
+            warn!(
+                "[TWITTER][RATE] Skipping {}, rate limited until {}",
+                endpoint.name(),
+                reset.to_rfc3339()
+            );
+            metrics::rate_limited(CLIENT, endpoint.name(), "skipped");
+            return Err(anyhow!(
+                "{} rate limited until {}",
+                endpoint.name(),
+                reset.to_rfc3339()
+            ));
+        }
+        info!(
+            "[TWITTER][RATE] Waiting {}s for the {} rate limit to reset",
+            wait.num_seconds(),
+            endpoint.name()
+        );
+        metrics::rate_limited(CLIENT, endpoint.name(), "delayed");
+        tokio::time::sleep(wait.to_std().unwrap_or_default()).await;
+        Ok(())
+    }
+
+    // reads the window from the response headers
+    pub fn update(&self, endpoint: Endpoint, status: StatusCode, headers: &HeaderMap) {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<i64>().ok())
+        };
+        let reset = header("x-rate-limit-reset").and_then(|secs| DateTime::from_timestamp(secs, 0));
+        let window = match (header("x-rate-limit-remaining"), reset) {
+            (Some(remaining), Some(reset)) => Window {
+                remaining: u32::try_from(remaining).unwrap_or(0),
+                reset,
+            },
+            _ if status == StatusCode::TOO_MANY_REQUESTS => Window {
+                remaining: 0,
+                reset: Utc::now() + Duration::minutes(DEFAULT_WINDOW_MINS),
+            },
+            _ => return,
+        };
+        // a 429 means the window is used up whatever the headers say
+        let window = if status == StatusCode::TOO_MANY_REQUESTS {
+            Window {
+                remaining: 0,
+                ..window
+            }
+        } else {
+            window
+        };
+
+        if window.remaining == 0 {
+            warn!(
+                "[TWITTER][RATE] {} rate limit used up until {}",
+                endpoint.name(),
+                window.reset.to_rfc3339()
+            );
+        } else {
+            debug!(
+                "[TWITTER][RATE] {} requests of {} left until {}",
+                window.remaining,
+                endpoint.name(),
+                window.reset.to_rfc3339()
+            );
+        }
+        metrics::rate_limit(CLIENT, endpoint.name(), window.remaining);
+        self.state.lock().unwrap().windows.insert(endpoint, window);
+    }
+
+    // counts a sent post against the budget
+    pub fn posted(&self) {
+        let mut state = self.state.lock().unwrap();
+        current_usage(&mut state.used);
+        state.used.posts += 1;
+        let Some(budget) = state.budget else {
+            return;
+        };
+        let remaining = budget.saturating_sub(state.used.posts);
+        metrics::post_budget(CLIENT, remaining);
+        if remaining == 0 {
+            warn!(
+                "[TWITTER][RATE] Monthly post budget of {} used up, posting again next month",
+                budget
+            );
+        } else {
+            debug!(
+                "[TWITTER][RATE] {} of {} monthly posts left",
+                remaining, budget
+            );
+        }
+    }
+
+    // what is saved with the client state
+    pub fn post_budget(&self) -> PostBudget {
+        let mut state = self.state.lock().unwrap();
+        current_usage(&mut state.used);
+        state.used.clone()
+    }
+
+    pub fn restore_post_budget(&self, used: PostBudget) {
+        let mut state = self.state.lock().unwrap();
+        state.used = used;
+        let posts = current_usage(&mut state.used);
+        if let Some(budget) = state.budget {
+            info!("[TWITTER][RATE] {} of {} monthly posts used", posts, budget);
+            metrics::post_budget(CLIENT, budget.saturating_sub(posts));
+        }
+    }
+}
+
+fn current_month() -> String {
+    Utc::now().format("%Y-%m").to_string()
+}
+
+// the posts of the current month, starting over when a new one began
+fn current_usage(used: &mut PostBudget) -> u32 {
+    let month = current_month();
+    if used.month != month {
+        *used = PostBudget { month, posts: 0 };
+    }
+    used.posts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(remaining: &str, reset: i64) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-rate-limit-remaining",
+            HeaderValue::from_str(remaining).unwrap(),
+        );
+        headers.insert(
+            "x-rate-limit-reset",
+            HeaderValue::from_str(&reset.to_string()).unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn update_keeps_an_open_window_usable() {
+        let limits = RateLimits::new(None);
+        let reset = (Utc::now() + Duration::minutes(10)).timestamp();
+        limits.update(Endpoint::Search, StatusCode::OK, &headers("3", reset));
+        assert_eq!(limits.reset(Endpoint::Search), None);
+    }
+
+    #[test]
+    fn update_blocks_a_used_up_window_until_its_reset() {
+        let limits = RateLimits::new(None);
+        let reset = (Utc::now() + Duration::minutes(10)).timestamp();
+        limits.update(Endpoint::Search, StatusCode::OK, &headers("0", reset));
+        assert_eq!(
+            limits.reset(Endpoint::Search),
+            DateTime::from_timestamp(reset, 0)
+        );
+        // endpoints are limited separately
+        assert_eq!(limits.reset(Endpoint::Mentions), None);
+    }
+
+    #[test]
+    fn update_ignores_a_reset_in_the_past() {
+        let limits = RateLimits::new(None);
+        let reset = (Utc::now() - Duration::minutes(1)).timestamp();
+        limits.update(Endpoint::Post, StatusCode::OK, &headers("0", reset));
+        assert_eq!(limits.reset(Endpoint::Post), None);
+    }
+
+    #[test]
+    fn update_treats_too_many_requests_as_used_up() {
+        let limits = RateLimits::new(None);
+        let reset = (Utc::now() + Duration::minutes(5)).timestamp();
+        limits.update(
+            Endpoint::Mentions,
+            StatusCode::TOO_MANY_REQUESTS,
+            &headers("7", reset),
+        );
+        assert_eq!(
+            limits.reset(Endpoint::Mentions),
+            DateTime::from_timestamp(reset, 0)
+        );
+    }
+
+    #[test]
+    fn update_assumes_a_window_for_too_many_requests_without_headers() {
+        let limits = RateLimits::new(None);
+        limits.update(
+            Endpoint::Mentions,
+            StatusCode::TOO_MANY_REQUESTS,
+            &HeaderMap::new(),
+        );
+        let reset = limits.reset(Endpoint::Mentions).unwrap();
+        let expected = Utc::now() + Duration::minutes(DEFAULT_WINDOW_MINS);
+        assert!((expected - reset).num_seconds().abs() < 5);
+    }
+
+    #[test]
+    fn update_without_headers_changes_nothing() {
+        let limits = RateLimits::new(None);
+        let reset = (Utc::now() + Duration::minutes(10)).timestamp();
+        limits.update(Endpoint::Search, StatusCode::OK, &headers("0", reset));
+        limits.update(Endpoint::Search, StatusCode::OK, &HeaderMap::new());
+        assert!(limits.reset(Endpoint::Search).is_some());
+    }
+
+    #[test]
+    fn posts_count_against_the_budget() {
+        let limits = RateLimits::new(Some(2));
+        limits.posted();
+        assert_eq!(limits.budget_exhausted(), None);
+        limits.posted();
+        assert!(limits.budget_exhausted().is_some());
+    }
+
+    #[test]
+    fn a_budget_from_another_month_starts_over() {
+        let limits = RateLimits::new(Some(1));
+        limits.restore_post_budget(PostBudget {
+            month: "2000-01".to_string(),
+            posts: 5,
+        });
+        assert_eq!(limits.budget_exhausted(), None);
+        assert_eq!(limits.post_budget().posts, 0);
+    }
+}
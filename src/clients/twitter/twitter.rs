@@ -1,97 +1,607 @@
-use anyhow::{Error, Result};
-use std::env;
-use twitter_v2::{authorization::Oauth1aToken, id::NumericId, query, Tweet, TwitterApi, User};
-
-#[derive(Clone)]
-pub struct Client {
-    api: TwitterApi<Oauth1aToken>,
-    pub user: User,
-}
-
-impl Client {
-    pub async fn new() -> Self {
-        // load vars
-        let api_key = env::var("TWITTER_API_KEY").expect("TWITTER_API_KEY not set");
-        let api_secret = env::var("TWITTER_API_SECRET").expect("TWITTER_API_SECRET not set");
-        let access_token = env::var("TWITTER_ACCESS_TOKEN").expect("TWITTER_ACCESS_TOKEN not set");
-        let access_token_secret =
-            env::var("TWITTER_ACCESS_TOKEN_SECRET").expect("TWITTER_ACCESS_TOKEN_SECRET not set");
-
-        // create api handler
-        let api = TwitterApi::new(Oauth1aToken::new(
-            api_key,
-            api_secret,
-            access_token,
-            access_token_secret,
-        ));
-
-        let user = api
-            .get_users_me()
-            .send()
-            .await
-            .unwrap()
-            .into_data()
-            .expect("[TWITTER] Failed to get userID");
-
-        Self { api, user }
-    }
-
-    pub async fn post(&self, response: &str) -> Result<Tweet> {
-        Ok(self
-            .api
-            .post_tweet()
-            .text(response.to_string())
-            .send()
-            .await?
-            .into_data()
-            .ok_or_else(|| Error::msg("Failed to post tweet"))?)
-    }
-
-    pub async fn reply(&self, id: NumericId, response: &str) -> Result<Tweet> {
-        Ok(self
-            .api
-            .post_tweet()
-            .in_reply_to_tweet_id(id)
-            .text(response.to_string())
-            .send()
-            .await?
-            .into_data()
-            .ok_or_else(|| Error::msg("failed to get reply data"))?)
-    }
-
-    pub async fn fetch_mentions(&self, count: usize, latest_id: NumericId) -> Result<Vec<Tweet>> {
-        Ok(self
-            .api
-            .get_user_mentions(self.user.id)
-            .since_id(latest_id)
-            .max_results(count)
-            .send()
-            .await?
-            .into_data()
-            .ok_or_else(|| Error::msg("Failed to fetch mentions"))?)
-    }
-
-    pub async fn fetch_timeline(&self, count: usize, latest_id: NumericId) -> Result<Vec<Tweet>> {
-        Ok(self
-            .api
-            .get_user_tweets(self.user.id)
-            .since_id(latest_id)
-            .max_results(count)
-            .send()
-            .await?
-            .into_data()
-            .ok_or_else(|| Error::msg("Failed to fetch timeline"))?)
-    }
-
-    pub async fn search(&self, query: String) -> Result<Vec<Tweet>> {
-        Ok(self
-            .api
-            .get_tweets_search_recent(query)
-            .sort_order(query::SortOrder::Relevancy)
-            .max_results(10)
-            .send()
-            .await?
-            .into_data()
-            .ok_or_else(|| Error::msg("Failed to fetch search results"))?)
-    }
-}
+use super::{
+    rate_limit::{Endpoint, RateLimits},
+    TweetDoc,
+};
+use crate::{
+    clients::social::{SocialPlatform, SocialPost, ThreadError, UploadError},
+    core::{Attachment, Config as RootConfig, MediaKind, PostBudget, Secret, TwitterCredentials},
+};
+use anyhow::{Error, Result};
+use async_trait::async_trait;
+use log::warn;
+use reqwest::{header::AUTHORIZATION, multipart};
+use serde::de::DeserializeOwned;
+use std::collections::{BTreeSet, HashMap};
+use twitter_v2::{
+    authorization::Oauth1aToken,
+    data::ReferencedTweetKind,
+    id::NumericId,
+    meta::TweetsMeta,
+    query::{TweetExpansion, TweetField},
+    ApiPayload, Tweet, TwitterApi, User,
+};
+
+#[derive(Clone)]
+pub struct Client {
+    api: TwitterApi<Oauth1aToken>,
+    // signs the requests twitter_v2 does not cover, media uploads are only on the v1.1 api
+    token: oauth1::Token,
+    http_client: reqwest::Client,
+    // mentions, search and posts are sent with `http_client` to read the rate limit headers
+    limits: RateLimits,
+    // set by `login`
+    pub user: Option<User>,
+}
+
+impl Client {
+    const API_URL: &str = "https://api.twitter.com/2";
+    const TWEETS_URL: &str = "https://api.twitter.com/2/tweets";
+    const SEARCH_URL: &str = "https://api.twitter.com/2/tweets/search/recent";
+    const UPLOAD_URL: &str = "https://upload.twitter.com/1.1/media/upload.json";
+    const METADATA_URL: &str = "https://upload.twitter.com/1.1/media/metadata/create.json";
+    const MAX_ALT_TEXT: usize = 1000;
+    // results per page the v2 api takes
+    const MIN_PAGE: usize = 5;
+    const MAX_PAGE: usize = 100;
+    // what walking a conversation needs beyond the text
+    const CONVERSATION_FIELDS: [TweetField; 3] = [
+        TweetField::AuthorId,
+        TweetField::ConversationId,
+        TweetField::ReferencedTweets,
+    ];
+
+    pub fn new(credentials: &TwitterCredentials) -> Self {
+        // load vars
+        let credential = |secret: &Option<Secret>, name: &str| {
+            secret
+                .as_ref()
+                .map(|secret| secret.expose().to_string())
+                .unwrap_or_else(|| panic!("providers.twitter.{name} not set"))
+        };
+        let api_key = credential(&credentials.api_key, "api_key");
+        let api_secret = credential(&credentials.api_secret, "api_secret");
+        let access_token = credential(&credentials.access_token, "access_token");
+        let access_token_secret =
+            credential(&credentials.access_token_secret, "access_token_secret");
+
+        // create api handler
+        let api = TwitterApi::new(Oauth1aToken::new(
+            &api_key,
+            &api_secret,
+            &access_token,
+            &access_token_secret,
+        ));
+        let token =
+            oauth1::Token::from_parts(api_key, api_secret, access_token, access_token_secret);
+
+        Self {
+            api,
+            token,
+            http_client: reqwest::Client::new(),
+            limits: RateLimits::new(credentials.post_budget()),
+            user: None,
+        }
+    }
+
+    pub async fn get_me(&self) -> Result<User> {
+        self.api
+            .get_users_me()
+            .send()
+            .await?
+            .into_data()
+            .ok_or_else(|| Error::msg("Failed to get user"))
+    }
+
+    fn user_id(&self) -> Result<NumericId> {
+        self.user
+            .as_ref()
+            .map(|user| user.id)
+            .ok_or_else(|| Error::msg("Not logged in"))
+    }
+
+    pub async fn post(&self, response: &str, media_ids: Vec<NumericId>) -> Result<Tweet> {
+        let mut body = serde_json::json!({ "text": response });
+        if !media_ids.is_empty() {
+            body["media"] = serde_json::json!({
+                "media_ids": media_ids.iter().map(|id| id.to_string()).collect::<Vec<_>>(),
+            });
+        }
+        self.post_tweet(body).await
+    }
+
+    // uploads an image and sets its alt text, the id goes into `post`
+    pub async fn upload(&self, attachment: &Attachment) -> Result<NumericId> {
+        let part = multipart::Part::bytes(attachment.read()?)
+            .file_name(attachment.file_name())
+            .mime_str(&attachment.mime)?;
+        let response = self
+            .http_client
+            .post(Self::UPLOAD_URL)
+            .header(AUTHORIZATION, self.authorization(Self::UPLOAD_URL))
+            .multipart(multipart::Form::new().part("media", part))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<serde_json::Value>()
+            .await?;
+        let id = response["media_id_string"]
+            .as_str()
+            .and_then(|id| id.parse::<u64>().ok())
+            .ok_or_else(|| Error::msg("Failed to upload media"))?;
+
+        if let Some(alt_text) = &attachment.alt_text {
+            let alt_text = alt_text
+                .chars()
+                .take(Self::MAX_ALT_TEXT)
+                .collect::<String>();
+            self.http_client
+                .post(Self::METADATA_URL)
+                .header(AUTHORIZATION, self.authorization(Self::METADATA_URL))
+                .json(&serde_json::json!({
+                    "media_id": id.to_string(),
+                    "alt_text": { "text": alt_text },
+                }))
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        Ok(NumericId::new(id))
+    }
+
+    // multipart and json bodies are not part of the oauth 1.0a signature
+    fn authorization(&self, url: &str) -> String {
+        oauth1::authorize("POST", url, &(), &self.token, oauth1::HmacSha1)
+    }
+
+    pub async fn reply(&self, id: NumericId, response: &str) -> Result<Tweet> {
+        self.post_tweet(serde_json::json!({
+            "text": response,
+            "reply": { "in_reply_to_tweet_id": id.to_string() },
+        }))
+        .await
+    }
+
+    pub async fn quote(&self, id: NumericId, response: &str) -> Result<Tweet> {
+        self.post_tweet(serde_json::json!({
+            "text": response,
+            "quote_tweet_id": id.to_string(),
+        }))
+        .await
+    }
+
+    // counted against the post rate limit and the monthly budget
+    async fn post_tweet(&self, body: serde_json::Value) -> Result<Tweet> {
+        let request = self
+            .http_client
+            .post(Self::TWEETS_URL)
+            .header(AUTHORIZATION, self.authorization(Self::TWEETS_URL))
+            .json(&body);
+        let tweet = self
+            .send::<Tweet, ()>(Endpoint::Post, request)
+            .await?
+            .into_data()
+            .ok_or_else(|| Error::msg("Failed to post tweet"))?;
+        self.limits.posted();
+        Ok(tweet)
+    }
+
+    pub async fn like(&self, id: NumericId) -> Result<()> {
+        self.api.post_user_like(self.user_id()?, id).await?;
+        Ok(())
+    }
+
+    pub async fn retweet(&self, id: NumericId) -> Result<()> {
+        self.api.post_user_retweet(self.user_id()?, id).await?;
+        Ok(())
+    }
+
+    // pages through the mentions newer than `latest_id` until `count` are fetched, with their
+    // authors for the usernames and follower counts
+    pub async fn fetch_mentions(
+        &self,
+        count: usize,
+        latest_id: Option<NumericId>,
+    ) -> Result<(Vec<Tweet>, Vec<User>)> {
+        let url = format!("{}/users/{}/mentions", Self::API_URL, self.user_id()?);
+        let mut tweets = Vec::new();
+        let mut users = Vec::new();
+        let mut pagination_token: Option<String> = None;
+        while tweets.len() < count {
+            let page = (count - tweets.len()).clamp(Self::MIN_PAGE, Self::MAX_PAGE);
+            let mut params = Self::params([
+                ("tweet.fields", "author_id".to_string()),
+                ("expansions", "author_id".to_string()),
+                ("user.fields", "username,public_metrics".to_string()),
+                ("max_results", page.to_string()),
+            ]);
+            if let Some(latest_id) = latest_id {
+                params.insert(("since_id".to_string(), latest_id.to_string()));
+            }
+            if let Some(token) = pagination_token.take() {
+                params.insert(("pagination_token".to_string(), token));
+            }
+            let payload = self
+                .send::<Vec<Tweet>, TweetsMeta>(Endpoint::Mentions, self.get(&url, &params))
+                .await?;
+            users.extend(
+                payload
+                    .includes
+                    .and_then(|includes| includes.users)
+                    .unwrap_or_default(),
+            );
+            pagination_token = payload.meta.and_then(|meta| meta.next_token);
+            // no data means no mentions
+            tweets.extend(payload.data.unwrap_or_default());
+            if pagination_token.is_none() {
+                break;
+            }
+        }
+        tweets.truncate(count);
+        Ok((tweets, users))
+    }
+
+    pub async fn fetch_timeline(&self, count: usize, latest_id: NumericId) -> Result<Vec<Tweet>> {
+        Ok(self
+            .api
+            .get_user_tweets(self.user_id()?)
+            .since_id(latest_id)
+            .max_results(count)
+            .send()
+            .await?
+            .into_data()
+            .ok_or_else(|| Error::msg("Failed to fetch timeline"))?)
+    }
+
+    // the tweets `id` replies into with their authors' usernames, oldest first; the conversation
+    // is searched once, parents it misses (older than a week) are fetched one by one
+    pub async fn fetch_conversation(
+        &self,
+        id: NumericId,
+        limit: usize,
+    ) -> Result<Vec<(Tweet, String)>> {
+        let (tweet, _) = self.fetch_tweet(id).await?;
+
+        let mut known = HashMap::new();
+        if let Some(conversation_id) = tweet
+            .conversation_id
+            .filter(|conversation| *conversation != id)
+        {
+            match self.search_conversation(conversation_id).await {
+                Ok(tweets) => known.extend(tweets.into_iter().map(|tweet| (tweet.0.id, tweet))),
+                Err(e) => warn!(
+                    "[TWITTER] Failed to search conversation {}: {}",
+                    conversation_id, e
+                ),
+            }
+        }
+
+        let mut chain = Vec::new();
+        let mut parent = replied_to(&tweet);
+        while let Some(parent_id) = parent {
+            if chain.len() >= limit {
+                break;
+            }
+            let (tweet, username) = match known.remove(&parent_id) {
+                Some(tweet) => tweet,
+                None => self.fetch_tweet(parent_id).await?,
+            };
+            parent = replied_to(&tweet);
+            chain.push((tweet, username));
+        }
+        chain.reverse();
+        Ok(chain)
+    }
+
+    async fn fetch_tweet(&self, id: NumericId) -> Result<(Tweet, String)> {
+        let response = self
+            .api
+            .get_tweet(id)
+            .tweet_fields(Self::CONVERSATION_FIELDS)
+            .expansions([TweetExpansion::AuthorId])
+            .send()
+            .await?;
+        let users = response
+            .includes()
+            .and_then(|includes| includes.users.clone())
+            .unwrap_or_default();
+        let tweet = response
+            .into_data()
+            .ok_or_else(|| Error::msg(format!("Failed to fetch tweet {}", id)))?;
+        let username = username(&tweet, &users);
+        Ok((tweet, username))
+    }
+
+    // the recent search only reaches back a week
+    async fn search_conversation(
+        &self,
+        conversation_id: NumericId,
+    ) -> Result<Vec<(Tweet, String)>> {
+        let params = Self::params([
+            ("query", format!("conversation_id:{}", conversation_id)),
+            (
+                "tweet.fields",
+                "author_id,conversation_id,referenced_tweets".to_string(),
+            ),
+            ("expansions", "author_id".to_string()),
+            ("max_results", Self::MAX_PAGE.to_string()),
+        ]);
+        let payload = self
+            .send::<Vec<Tweet>, TweetsMeta>(Endpoint::Search, self.get(Self::SEARCH_URL, &params))
+            .await?;
+        let users = payload
+            .includes
+            .and_then(|includes| includes.users)
+            .unwrap_or_default();
+        // no data means no replies
+        Ok(payload
+            .data
+            .unwrap_or_default()
+            .into_iter()
+            .map(|tweet| {
+                let username = username(&tweet, &users);
+                (tweet, username)
+            })
+            .collect())
+    }
+
+    pub async fn search(&self, query: String, count: usize) -> Result<Vec<Tweet>> {
+        let params = Self::params([
+            ("query", query),
+            ("sort_order", "relevancy".to_string()),
+            ("max_results", count.to_string()),
+        ]);
+        self.send::<Vec<Tweet>, TweetsMeta>(Endpoint::Search, self.get(Self::SEARCH_URL, &params))
+            .await?
+            .data
+            .ok_or_else(|| Error::msg("Failed to fetch search results"))
+    }
+
+    fn params<const N: usize>(params: [(&str, String); N]) -> BTreeSet<(String, String)> {
+        params
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect()
+    }
+
+    // query parameters are part of the oauth 1.0a signature
+    fn get(&self, url: &str, params: &BTreeSet<(String, String)>) -> reqwest::RequestBuilder {
+        self.http_client
+            .get(oauth1::to_uri_query(url.to_string(), params))
+            .header(
+                AUTHORIZATION,
+                oauth1::authorize("GET", url, params, &self.token, oauth1::HmacSha1),
+            )
+    }
+
+    // sends a request through the rate limit of `endpoint`, twitter_v2 drops the headers it needs
+    async fn send<T, M>(
+        &self,
+        endpoint: Endpoint,
+        request: reqwest::RequestBuilder,
+    ) -> Result<ApiPayload<T, M>>
+    where
+        T: DeserializeOwned,
+        M: DeserializeOwned,
+    {
+        self.limits.acquire(endpoint).await?;
+        let response = request.send().await?;
+        let status = response.status();
+        self.limits.update(endpoint, status, response.headers());
+        if !status.is_success() {
+            return Err(anyhow::anyhow!(
+                "{} request failed with {}: {}",
+                endpoint.name(),
+                status,
+                response.text().await.unwrap_or_default()
+            ));
+        }
+        Ok(response.json::<ApiPayload<T, M>>().await?)
+    }
+}
+
+#[async_trait]
+impl SocialPlatform for Client {
+    type Doc = TweetDoc;
+
+    const TAG: &'static str = "TWITTER";
+    const NAME: &'static str = "Twitter";
+    const MEMORY: &'static str = "twitter";
+    const MAX_CHARS: usize = 280;
+    // twitter only takes audio as video
+    const MEDIA: &'static [MediaKind] = &[MediaKind::Image];
+
+    fn new(root_config: &RootConfig) -> Self {
+        Client::new(&root_config.providers.twitter)
+    }
+
+    async fn login(&mut self) -> Result<()> {
+        self.user = Some(self.get_me().await?);
+        Ok(())
+    }
+
+    fn current_user(&self) -> &str {
+        self.user
+            .as_ref()
+            .map(|user| user.username.as_str())
+            .unwrap_or_default()
+    }
+
+    async fn post(&mut self, text: &str) -> Result<Option<String>> {
+        Ok(Some(
+            Client::post(self, text, Vec::new()).await?.id.to_string(),
+        ))
+    }
+
+    async fn like(&mut self, post: &SocialPost) -> Result<()> {
+        let id = post.id.parse::<u64>()?;
+        Client::like(self, NumericId::new(id)).await
+    }
+
+    // the retweet endpoint does not return the new tweet
+    async fn repost(&mut self, post: &SocialPost) -> Result<Option<String>> {
+        let id = post.id.parse::<u64>()?;
+        Client::retweet(self, NumericId::new(id)).await?;
+        Ok(None)
+    }
+
+    async fn quote(&mut self, post: &SocialPost, text: &str) -> Result<Option<String>> {
+        let id = post.id.parse::<u64>()?;
+        Ok(Some(
+            Client::quote(self, NumericId::new(id), text)
+                .await?
+                .id
+                .to_string(),
+        ))
+    }
+
+    async fn post_media(&mut self, text: &str, media: &[Attachment]) -> Result<Option<String>> {
+        let mut media_ids = Vec::new();
+        for attachment in media {
+            media_ids.push(self.upload(attachment).await.map_err(UploadError)?);
+        }
+        Ok(Some(
+            Client::post(self, text, media_ids).await?.id.to_string(),
+        ))
+    }
+
+    async fn reply(&mut self, to: &SocialPost, text: &str) -> Result<Option<String>> {
+        let id = to.id.parse::<u64>()?;
+        Ok(Some(
+            Client::reply(self, NumericId::new(id), text)
+                .await?
+                .id
+                .to_string(),
+        ))
+    }
+
+    async fn post_thread(&mut self, parts: &[String]) -> Result<Vec<String>, ThreadError> {
+        let mut sent: Vec<NumericId> = Vec::new();
+        for part in parts {
+            let tweet = match sent.last() {
+                None => Client::post(self, part, Vec::new()).await,
+                Some(previous) => Client::reply(self, *previous, part).await,
+            };
+            match tweet {
+                Ok(tweet) => sent.push(tweet.id),
+                Err(error) => {
+                    return Err(ThreadError {
+                        sent: sent.iter().map(|id| id.to_string()).collect(),
+                        error,
+                    })
+                }
+            }
+        }
+        Ok(sent.iter().map(|id| id.to_string()).collect())
+    }
+
+    async fn delete(&mut self, id: &str) -> Result<()> {
+        let id = id.parse::<u64>()?;
+        self.api.delete_tweet(NumericId::new(id)).await?;
+        Ok(())
+    }
+
+    async fn conversation(&mut self, post: &SocialPost, limit: usize) -> Result<Vec<SocialPost>> {
+        let id = post.id.parse::<u64>()?;
+        let tweets = Client::fetch_conversation(self, NumericId::new(id), limit).await?;
+        Ok(tweets
+            .into_iter()
+            .map(|(tweet, username)| SocialPost {
+                id: tweet.id.to_string(),
+                author: username,
+                text: tweet.text,
+                followers: None,
+            })
+            .collect())
+    }
+
+    fn blocked(&self, action: &str) -> Option<String> {
+        let endpoints: &[Endpoint] = match action {
+            "post" | "thread" => &[Endpoint::Post],
+            "reply" => &[Endpoint::Mentions, Endpoint::Post],
+            // searches and reads mentions for candidates, then likes, reposts or quotes
+            "engage" => &[Endpoint::Search, Endpoint::Mentions, Endpoint::Post],
+            _ => &[],
+        };
+        if endpoints.contains(&Endpoint::Post) {
+            if let Some(reason) = self.limits.budget_exhausted() {
+                return Some(reason);
+            }
+        }
+        endpoints.iter().find_map(|endpoint| {
+            self.limits.reset(*endpoint).map(|reset| {
+                format!(
+                    "{} rate limited until {}",
+                    endpoint.name(),
+                    reset.to_rfc3339()
+                )
+            })
+        })
+    }
+
+    fn post_budget(&self) -> Option<PostBudget> {
+        Some(self.limits.post_budget())
+    }
+
+    fn restore_post_budget(&mut self, budget: PostBudget) {
+        self.limits.restore_post_budget(budget);
+    }
+
+    async fn fetch_mentions(
+        &mut self,
+        since: Option<&str>,
+        count: usize,
+    ) -> Result<Vec<SocialPost>> {
+        let since = since
+            .and_then(|id| id.parse::<u64>().ok())
+            .map(NumericId::new);
+        let (tweets, users) = Client::fetch_mentions(self, count, since).await?;
+        Ok(tweets
+            .into_iter()
+            .map(|tweet| to_social_post(tweet, &users))
+            .collect())
+    }
+
+    async fn search(&mut self, query: &str, count: usize) -> Result<Vec<SocialPost>> {
+        let tweets = Client::search(self, query.to_string(), count).await?;
+        Ok(tweets
+            .into_iter()
+            .map(|tweet| to_social_post(tweet, &[]))
+            .collect())
+    }
+}
+
+// `users` are the expanded authors, without them the author is its id
+fn to_social_post(tweet: Tweet, users: &[User]) -> SocialPost {
+    let author = tweet
+        .author_id
+        .and_then(|author_id| users.iter().find(|user| user.id == author_id));
+    SocialPost {
+        id: tweet.id.to_string(),
+        author: username(&tweet, users),
+        text: tweet.text,
+        followers: author
+            .and_then(|user| user.public_metrics.as_ref())
+            .map(|metrics| metrics.followers_count as u64),
+    }
+}
+
+fn replied_to(tweet: &Tweet) -> Option<NumericId> {
+    tweet
+        .referenced_tweets
+        .as_ref()?
+        .iter()
+        .find(|referenced| referenced.kind == ReferencedTweetKind::RepliedTo)
+        .map(|referenced| referenced.id)
+}
+
+// the author's username from the expanded users, the author id when it is missing
+fn username(tweet: &Tweet, users: &[User]) -> String {
+    let Some(author_id) = tweet.author_id else {
+        return String::new();
+    };
+    users
+        .iter()
+        .find(|user| user.id == author_id)
+        .map(|user| user.username.clone())
+        .unwrap_or_else(|| author_id.to_string())
+}
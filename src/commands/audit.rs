@@ -0,0 +1,18 @@
+use crate::core::{audit, AuditQuery, Config};
+use log::info;
+
+// reads the audit file directly, the agent may be running
+pub fn run(query: AuditQuery, json: bool, config: &Config) -> Result<(), anyhow::Error> {
+    let records = audit::search(&config.audit, &query)?;
+    if records.is_empty() {
+        info!("[AUDIT] No records in {}", config.audit.path);
+    }
+    for record in records {
+        if json {
+            println!("{}", serde_json::to_string(&record)?);
+        } else {
+            println!("{}", record.summary());
+        }
+    }
+    Ok(())
+}
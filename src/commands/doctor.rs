@@ -0,0 +1,306 @@
+use crate::{
+    clients::twitter::TwitterClient,
+    core::{
+        layers, Character, Clients, Config, DatabaseProvider, CHARACTERS_FOLDER,
+        MEMORY_SNAPSHOT_FOLDER,
+    },
+    providers::{
+        completion::CompletionModelEnum, embedding::EmbeddingModelEnum,
+        truth::Client as TruthClient,
+    },
+};
+use colored::Colorize;
+use mongodb::bson::doc;
+use rig::{completion::CompletionModel, embeddings::EmbeddingModel};
+use serenity::http::Http;
+use std::{fmt::Display, fs, path::Path};
+use teloxide::{prelude::Requester, Bot};
+
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+// one line per check, printed together once every check ran
+#[derive(Default)]
+struct Report {
+    lines: Vec<(Status, String, String)>,
+}
+
+impl Report {
+    fn ok(&mut self, check: &str, message: impl Display) {
+        self.lines
+            .push((Status::Ok, check.to_string(), message.to_string()));
+    }
+
+    fn warn(&mut self, check: &str, message: impl Display) {
+        self.lines
+            .push((Status::Warn, check.to_string(), message.to_string()));
+    }
+
+    fn fail(&mut self, check: &str, message: impl Display) {
+        self.lines
+            .push((Status::Fail, check.to_string(), message.to_string()));
+    }
+
+    fn check(&mut self, check: &str, result: Result<impl Display, impl Display>) {
+        match result {
+            Ok(message) => self.ok(check, message),
+            Err(e) => self.fail(check, e),
+        }
+    }
+
+    fn failures(&self) -> usize {
+        self.lines
+            .iter()
+            .filter(|(status, _, _)| matches!(status, Status::Fail))
+            .count()
+    }
+
+    fn print(&self) {
+        println!();
+        for (status, check, message) in &self.lines {
+            let status = match status {
+                Status::Ok => "OK  ".green(),
+                Status::Warn => "WARN".yellow(),
+                Status::Fail => "FAIL".red(),
+            };
+            println!("{} {:<12} {}", status, check, message);
+        }
+        println!();
+    }
+}
+
+// checks the config, env, characters, database and every credential the config uses, printing
+// one report. Errors when any check failed.
+pub async fn run(
+    config_path: &str,
+    overrides: &[String],
+    character_path: &str,
+) -> Result<(), anyhow::Error> {
+    let mut report = Report::default();
+
+    check_env(&mut report);
+    check_characters(&mut report, character_path);
+
+    match Config::load(config_path, overrides) {
+        Ok(config) => {
+            report.ok("config", format!("{} parsed", config_path));
+            check_config(&mut report, &config);
+            check_database(&mut report, &config).await;
+            check_models(&mut report, &config).await;
+            check_clients(&mut report, &config).await;
+        }
+        Err(e) => report.fail("config", format!("{}: {}", config_path, e)),
+    }
+
+    report.print();
+    match report.failures() {
+        0 => Ok(()),
+        failures => Err(anyhow::anyhow!("{} checks failed", failures)),
+    }
+}
+
+fn check_env(report: &mut Report) {
+    if Path::new(".env").exists() {
+        report.ok("env", ".env found");
+    } else {
+        report.warn("env", "no .env, only the process environment is used");
+    }
+
+    let overrides = layers::env_overrides();
+    if overrides.is_empty() {
+        report.ok("env", "no config values set from env vars");
+    } else {
+        report.ok(
+            "env",
+            format!("config values set from {}", overrides.join(", ")),
+        );
+    }
+}
+
+fn check_characters(report: &mut Report, character_path: &str) {
+    if !Path::new(character_path).exists() {
+        report.fail("character", format!("{} does not exist", character_path));
+    }
+
+    let entries = match fs::read_dir(CHARACTERS_FOLDER) {
+        Ok(entries) => entries,
+        Err(e) => {
+            report.fail("character", format!("{}: {}", CHARACTERS_FOLDER, e));
+            return;
+        }
+    };
+    let mut paths = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    for path in paths {
+        let path = path.to_string_lossy().to_string();
+        let mut character = Character::new(path.clone());
+        match character.load() {
+            Ok(_) => report.ok("character", format!("{} ({})", path, character.alias)),
+            // only the selected character stops the agent from starting
+            Err(e) if path == character_path => {
+                report.fail("character", format!("{}: {}", path, e))
+            }
+            Err(e) => report.warn("character", format!("{}: {}", path, e)),
+        }
+    }
+}
+
+fn check_config(report: &mut Report, config: &Config) {
+    let missing = config.missing(true);
+    let invalid = config.invalid();
+    for path in &missing {
+        report.fail("config", format!("missing {}", path));
+    }
+    for problem in &invalid {
+        report.fail("config", problem);
+    }
+    if missing.is_empty() && invalid.is_empty() {
+        report.ok("config", "every required value is set");
+    }
+
+    let clients = &config.client_configs;
+    let configured = [
+        (Clients::Api, clients.api.is_some()),
+        (Clients::Cli, clients.cli.is_some()),
+        (Clients::Discord, clients.discord.is_some()),
+        (Clients::Storytelling, clients.storytelling.is_some()),
+        (Clients::Telegram, clients.telegram.is_some()),
+        (Clients::Twitter, clients.twitter.is_some()),
+        (Clients::Truth, clients.truth.is_some()),
+    ];
+    for (client, configured) in configured {
+        if configured && !config.enabled_clients.contains(&client) {
+            report.warn(
+                "config",
+                format!(
+                    "client_configs.{} is set but {} is not in enabled_clients",
+                    client.name(),
+                    client.name()
+                ),
+            );
+        }
+    }
+}
+
+async fn check_database(report: &mut Report, config: &Config) {
+    match config.db {
+        DatabaseProvider::Local => {
+            let probe = Path::new(MEMORY_SNAPSHOT_FOLDER).join(".doctor");
+            let result = fs::create_dir_all(MEMORY_SNAPSHOT_FOLDER)
+                .and_then(|_| fs::write(&probe, b""))
+                .and_then(|_| fs::remove_file(&probe))
+                .map(|_| format!("{} is writable", MEMORY_SNAPSHOT_FOLDER))
+                .map_err(|e| format!("{} is not writable: {}", MEMORY_SNAPSHOT_FOLDER, e));
+            report.check("database", result);
+        }
+        DatabaseProvider::MongoDB => {
+            let mongodb = &config.providers.mongodb;
+            let (Some(_), Some(db)) = (&mongodb.conn_url, &mongodb.db) else {
+                report.warn("database", "skipped, mongodb is not configured");
+                return;
+            };
+            let result = async {
+                mongodb::Client::with_uri_str(mongodb.conn_url())
+                    .await?
+                    .database(db)
+                    .run_command(doc! { "ping": 1 })
+                    .await
+            }
+            .await
+            .map(|_| format!("mongodb {} is reachable", db));
+            report.check("database", result);
+        }
+    }
+}
+
+async fn check_models(report: &mut Report, config: &Config) {
+    if config.missing(false).is_empty() {
+        match EmbeddingModelEnum::from_config(config) {
+            Ok(model) => {
+                let result = model
+                    .embed_text("ping")
+                    .await
+                    .map(|_| format!("{} ({} dims)", model.model_id(), model.ndims()));
+                report.check("embedding", result);
+            }
+            Err(e) => report.fail("embedding", e),
+        }
+    } else {
+        report.warn("embedding", "skipped, required values are missing");
+    }
+
+    let provider = config
+        .providers
+        .model_provider(config.completion_provider.name());
+    if provider.api_key.is_some() && provider.completion_model.is_some() {
+        let model = CompletionModelEnum::from_config(config);
+        let name = model.model_name().to_string();
+        let result = model
+            .completion_request("ping")
+            .max_tokens(1)
+            .send()
+            .await
+            .map(|_| name);
+        report.check("completion", result);
+    } else {
+        report.warn("completion", "skipped, required values are missing");
+    }
+}
+
+async fn check_clients(report: &mut Report, config: &Config) {
+    let providers = &config.providers;
+    let enabled = |client: Clients| config.enabled_clients.contains(&client);
+
+    if enabled(Clients::Discord) {
+        if let Some(token) = &providers.discord.token {
+            let result = Http::new(token.expose())
+                .get_current_user()
+                .await
+                .map(|user| format!("logged in as {}", user.name));
+            report.check("discord", result);
+        }
+    }
+
+    if enabled(Clients::Telegram) {
+        if let Some(token) = &providers.telegram.token {
+            let result = Bot::new(token.expose())
+                .get_me()
+                .await
+                .map(|me| format!("logged in as {}", me.username()));
+            report.check("telegram", result);
+        }
+    }
+
+    if enabled(Clients::Twitter) {
+        let twitter = &providers.twitter;
+        if twitter.api_key.is_some()
+            && twitter.api_secret.is_some()
+            && twitter.access_token.is_some()
+            && twitter.access_token_secret.is_some()
+        {
+            let result = TwitterClient::new(twitter)
+                .get_me()
+                .await
+                .map(|user| format!("logged in as {}", user.username));
+            report.check("twitter", result);
+        }
+    }
+
+    if enabled(Clients::Truth) {
+        let truth = &providers.truth;
+        if let (Some(username), Some(password)) = (&truth.username, &truth.password) {
+            let result = TruthClient::new()
+                .login(username.clone(), password.expose().to_string())
+                .await
+                .map(|_| format!("logged in as {}", username));
+            report.check("truth", result);
+        }
+    }
+}
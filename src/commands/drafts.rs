@@ -0,0 +1,53 @@
+use crate::core::{ApprovalQueue, Config, DraftStatus};
+use clap::Subcommand;
+use log::info;
+
+#[derive(Subcommand, Debug)]
+pub enum DraftsCommand {
+    /// List drafts, newest first
+    List {
+        #[arg(long, value_enum)]
+        status: Option<DraftStatus>,
+    },
+    /// Approve a pending draft
+    Approve { id: String },
+    /// Replace the text of a pending draft and approve it
+    Edit { id: String, text: String },
+    /// Reject a pending draft
+    Reject {
+        id: String,
+        #[arg(long)]
+        reason: Option<String>,
+    },
+}
+
+// decisions are picked up by the running agent on its next delivery
+pub fn run(command: DraftsCommand, config: &Config) -> Result<(), anyhow::Error> {
+    let approval = ApprovalQueue::new(&config.approval)
+        .ok_or_else(|| anyhow::anyhow!("approval.enabled is not set"))?;
+
+    match command {
+        DraftsCommand::List { status } => {
+            let drafts = approval.list(status)?;
+            if drafts.is_empty() {
+                info!("[DRAFTS] No drafts");
+            }
+            for draft in drafts {
+                println!("{}", draft.summary());
+            }
+        }
+        DraftsCommand::Approve { id } => {
+            let draft = approval.approve(&id, "cli")?;
+            info!("[DRAFTS] Approved {}", draft.id);
+        }
+        DraftsCommand::Edit { id, text } => {
+            let draft = approval.edit(&id, &text, "cli")?;
+            info!("[DRAFTS] Edited and approved {}", draft.id);
+        }
+        DraftsCommand::Reject { id, reason } => {
+            let draft = approval.reject(&id, "cli", reason)?;
+            info!("[DRAFTS] Rejected {}", draft.id);
+        }
+    }
+    Ok(())
+}
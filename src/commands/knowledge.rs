@@ -0,0 +1,38 @@
+use crate::core::{Character, Config, KnowledgeBase};
+use clap::{Args, Subcommand};
+use log::info;
+use rig::embeddings::EmbeddingModel;
+
+#[derive(Subcommand, Debug)]
+pub enum KnowledgeCommand {
+    /// Chunk and embed the markdown, text, HTML and PDF files in a folder, skipping unchanged files
+    Ingest(IngestArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct IngestArgs {
+    dir: String,
+}
+
+pub async fn run<EM: EmbeddingModel>(
+    command: KnowledgeCommand,
+    character: &Character,
+    embedding_model: EM,
+    config: &Config,
+) -> Result<(), anyhow::Error> {
+    match command {
+        KnowledgeCommand::Ingest(args) => {
+            let knowledge = KnowledgeBase::open(&character.alias, config, embedding_model).await?;
+            info!(
+                "[KNOWLEDGE][INGEST] Ingesting {} for {}",
+                args.dir, character.alias
+            );
+            let stats = knowledge.ingest(&args.dir).await?;
+            info!(
+                "[KNOWLEDGE][INGEST] Done, {} files ingested into {} chunks, {} unchanged, {} removed, {} failed",
+                stats.ingested, stats.chunks, stats.unchanged, stats.removed, stats.failed
+            );
+            Ok(())
+        }
+    }
+}
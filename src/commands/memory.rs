@@ -0,0 +1,277 @@
+use crate::{
+    clients::{social::SocialDoc, truth::TruthDoc, twitter::TweetDoc},
+    core::{Config, MemoryStore, MongoDbConfig, MEMORY_SNAPSHOT_FOLDER, MIGRATION_STATE_FOLDER},
+    dbs::{LocalMemoryStore, MemoryStoreEnum, MongoDbMemoryStore},
+};
+use clap::{Args, Subcommand, ValueEnum};
+use log::info;
+use mongodb::bson::Document;
+use rig::{
+    embeddings::{Embedding, EmbeddingModel},
+    Embed, OneOrMany,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashSet,
+    fs::{self, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+#[derive(Subcommand, Debug)]
+pub enum MemoryCommand {
+    /// Re-embed every document of a store with the configured embedding model into a target store
+    Migrate(MigrateArgs),
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum MemoryKind {
+    Twitter,
+    Truth,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    Local,
+    Mongodb,
+}
+
+#[derive(Args, Debug)]
+pub struct MigrateArgs {
+    #[arg(long, value_enum)]
+    kind: MemoryKind,
+    #[arg(long, value_enum)]
+    from: Backend,
+    #[arg(long, value_enum)]
+    to: Backend,
+    /// Snapshot path (local) or collection name (mongodb) to read from
+    #[arg(long)]
+    from_location: Option<String>,
+    /// Snapshot path (local) or collection name (mongodb) to write to
+    #[arg(long)]
+    to_location: Option<String>,
+    #[arg(long, default_value_t = 64)]
+    batch_size: usize,
+    /// Skip documents an interrupted run already migrated
+    #[arg(long)]
+    resume: bool,
+    /// Delete everything in the target store before migrating
+    #[arg(long, conflicts_with = "resume")]
+    clear_target: bool,
+}
+
+pub async fn run<EM: EmbeddingModel>(
+    command: MemoryCommand,
+    embedding_model: EM,
+    config: &Config,
+) -> Result<(), anyhow::Error> {
+    let mongodb = &config.providers.mongodb;
+    match command {
+        MemoryCommand::Migrate(args) => match args.kind {
+            MemoryKind::Twitter => {
+                migrate::<TweetDoc, EM>(args, embedding_model, mongodb, TweetDoc::to_document).await
+            }
+            MemoryKind::Truth => {
+                migrate::<TruthDoc, EM>(args, embedding_model, mongodb, TruthDoc::to_document).await
+            }
+        },
+    }
+}
+
+async fn migrate<D, EM>(
+    args: MigrateArgs,
+    embedding_model: EM,
+    mongodb: &MongoDbConfig,
+    to_document: fn(&D, &OneOrMany<Embedding>) -> Document,
+) -> Result<(), anyhow::Error>
+where
+    D: Embed + Serialize + DeserializeOwned + Send + Sync + Eq + Clone,
+    EM: EmbeddingModel,
+{
+    let from_location = location(args.from, args.kind, args.from_location, mongodb)?;
+    let to_location = location(args.to, args.kind, args.to_location, mongodb)?;
+    if args.from == args.to && from_location == to_location {
+        return Err(anyhow::anyhow!(
+            "Source and target are both {from_location}, migrate into a new location and swap it in afterwards"
+        ));
+    }
+    if args.batch_size == 0 {
+        return Err(anyhow::anyhow!("--batch-size must be greater than 0"));
+    }
+
+    // the source is only read, so its embedding model is never used
+    let source = open_store::<D, EM>(
+        args.from,
+        &from_location,
+        embedding_model.clone(),
+        mongodb,
+        to_document,
+    )
+    .await?;
+    let mut target = open_store::<D, EM>(
+        args.to,
+        &to_location,
+        embedding_model.clone(),
+        mongodb,
+        to_document,
+    )
+    .await?;
+
+    if args.clear_target {
+        info!("[MEMORY][MIGRATE] Clearing {}", to_location);
+        target.clear().await?;
+    }
+    target.check_ndims(embedding_model.ndims()).await?;
+
+    // one state per source and target, so a resume never skips ids another migration recorded
+    let locations: String = Sha256::digest(format!("{from_location}\n{to_location}"))
+        .iter()
+        .take(6)
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    let state_path = PathBuf::from(MIGRATION_STATE_FOLDER).join(format!(
+        "{:?}-{:?}-{:?}-{}.ids",
+        args.kind, args.from, args.to, locations
+    ));
+    let mut state = MigrationState::open(state_path, args.resume)?;
+
+    let total = source.count().await?;
+    info!(
+        "[MEMORY][MIGRATE] Migrating {} documents from {} to {} ({} already done)",
+        total,
+        from_location,
+        to_location,
+        state.done.len()
+    );
+
+    let mut offset = 0;
+    loop {
+        let page = source.documents::<D>(offset, args.batch_size).await?;
+        if page.is_empty() {
+            break;
+        }
+        offset += page.len();
+
+        let (ids, documents): (Vec<_>, Vec<_>) = page
+            .into_iter()
+            .filter(|(id, _)| !state.done.contains(id))
+            .unzip();
+        if !documents.is_empty() {
+            target.add_many(documents).await?;
+            state.record(ids)?;
+        }
+
+        info!(
+            "[MEMORY][MIGRATE] {}/{} documents",
+            offset.min(total),
+            total
+        );
+    }
+
+    state.finish()?;
+    info!(
+        "[MEMORY][MIGRATE] Done, {} now holds {} documents",
+        to_location,
+        target.count().await?
+    );
+    Ok(())
+}
+
+fn location(
+    backend: Backend,
+    kind: MemoryKind,
+    location: Option<String>,
+    mongodb: &MongoDbConfig,
+) -> Result<String, anyhow::Error> {
+    if let Some(location) = location {
+        return Ok(location);
+    }
+    match backend {
+        Backend::Local => {
+            let name = match kind {
+                MemoryKind::Twitter => "twitter",
+                MemoryKind::Truth => "truth",
+            };
+            Ok(format!("{}/{}.jsonl", MEMORY_SNAPSHOT_FOLDER, name))
+        }
+        Backend::Mongodb => mongodb
+            .collection
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("providers.mongodb.collection not set")),
+    }
+}
+
+async fn open_store<D, EM>(
+    backend: Backend,
+    location: &str,
+    embedding_model: EM,
+    mongodb: &MongoDbConfig,
+    to_document: fn(&D, &OneOrMany<Embedding>) -> Document,
+) -> Result<MemoryStoreEnum<D, EM>, anyhow::Error>
+where
+    D: Embed + Serialize + DeserializeOwned + Send + Sync + Eq + Clone,
+    EM: EmbeddingModel,
+{
+    Ok(match backend {
+        Backend::Local => MemoryStoreEnum::Local {
+            memory_store: LocalMemoryStore::with_snapshot(embedding_model, location)?,
+        },
+        Backend::Mongodb => MemoryStoreEnum::MongoDB {
+            memory_store: MongoDbMemoryStore::new(
+                mongodb.conn_url(),
+                mongodb.db(),
+                &location.to_string(),
+                embedding_model,
+                to_document,
+            )
+            .await?,
+        },
+    })
+}
+
+// ids of already migrated documents, appended after every batch so an interrupted run can resume
+struct MigrationState {
+    path: PathBuf,
+    file: fs::File,
+    done: HashSet<String>,
+}
+
+impl MigrationState {
+    fn open(path: PathBuf, resume: bool) -> Result<Self, anyhow::Error> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut done = HashSet::new();
+        if resume && Path::new(&path).exists() {
+            let reader = BufReader::new(fs::File::open(&path)?);
+            for line in reader.lines() {
+                done.insert(line?);
+            }
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(resume)
+            .write(true)
+            .truncate(!resume)
+            .open(&path)?;
+
+        Ok(Self { path, file, done })
+    }
+
+    fn record(&mut self, ids: Vec<String>) -> Result<(), anyhow::Error> {
+        for id in ids {
+            writeln!(self.file, "{}", id)?;
+            self.done.insert(id);
+        }
+        self.file.flush()?;
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), anyhow::Error> {
+        fs::remove_file(self.path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,41 @@
+pub mod audit;
+pub mod doctor;
+pub mod drafts;
+pub mod knowledge;
+pub mod memory;
+
+pub use drafts::DraftsCommand;
+pub use knowledge::KnowledgeCommand;
+pub use memory::MemoryCommand;
+
+use crate::core::AuditQuery;
+use clap::Subcommand;
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Manage the vector memory stores
+    Memory {
+        #[command(subcommand)]
+        command: MemoryCommand,
+    },
+    /// Manage the character's knowledge base
+    Knowledge {
+        #[command(subcommand)]
+        command: KnowledgeCommand,
+    },
+    /// Review the posts and replies waiting for approval
+    Drafts {
+        #[command(subcommand)]
+        command: DraftsCommand,
+    },
+    /// Search everything the character sent, newest first
+    Audit {
+        #[command(flatten)]
+        query: AuditQuery,
+        /// Print whole records as json lines, with their prompts
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check the config, env, characters, database and provider credentials and print a report
+    Doctor,
+}
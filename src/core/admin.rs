@@ -0,0 +1,151 @@
+use super::{
+    logging,
+    metrics::{self, ClientHealth, ClientStatus},
+    AdminConfig, Shutdown,
+};
+use actix_web::{web, App, HttpResponse, HttpServer, Responder};
+use chrono::{Duration, Utc};
+use log::{error, info, LevelFilter};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct LogLevelBody {
+    level: String,
+    // the default level when unset
+    module: Option<String>,
+}
+
+// health, readiness and prometheus metrics of every client, for probes and scrapers
+pub async fn start(config: AdminConfig, shutdown: Shutdown) {
+    let data = web::Data::new(config.clone());
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(data.clone())
+            .route("/healthz", web::get().to(healthz))
+            .route("/readyz", web::get().to(readyz))
+            .route("/metrics", web::get().to(metrics_route))
+            .route("/log-level", web::get().to(log_level_get))
+            .route("/log-level", web::put().to(log_level_put))
+    })
+    .bind((config.host.as_str(), config.port))
+    .expect("Failed to bind admin server")
+    .disable_signals()
+    .run();
+
+    let handle = server.handle();
+    let stop = tokio::spawn(async move {
+        shutdown.wait().await;
+        handle.stop(true).await;
+    });
+
+    info!(
+        "[ADMIN] Started http server on {}:{}",
+        config.host, config.port
+    );
+    let _ = server.await;
+
+    stop.abort();
+    info!("[ADMIN] Stopped");
+}
+
+// alive unless the supervisor gave up on a client
+async fn healthz() -> impl Responder {
+    let clients = metrics::health();
+    let healthy = clients
+        .values()
+        .all(|health| health.status != ClientStatus::Failed);
+
+    let body = serde_json::json!({
+        "status": if healthy { "ok" } else { "failing" },
+        "clients": clients,
+    });
+    if healthy {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+// ready once every client runs and, with `stale_after_mins`, recently did something
+async fn readyz(config: web::Data<AdminConfig>) -> impl Responder {
+    let clients = metrics::health()
+        .into_iter()
+        .map(|(client, health)| {
+            let reason = not_ready(&config, &health);
+            let mut value = serde_json::to_value(&health).unwrap_or_default();
+            value["ready"] = reason.is_none().into();
+            if let Some(reason) = reason {
+                value["reason"] = reason.into();
+            }
+            (client, value)
+        })
+        .collect::<serde_json::Map<_, _>>();
+    let ready = !clients.is_empty() && clients.values().all(|client| client["ready"] == true);
+
+    let body = serde_json::json!({
+        "status": if ready { "ready" } else { "not_ready" },
+        "clients": clients,
+    });
+    if ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+fn not_ready(config: &AdminConfig, health: &ClientHealth) -> Option<String> {
+    if health.status != ClientStatus::Running {
+        return Some(format!("client is {:?}", health.status).to_lowercase());
+    }
+    let stale_after = Duration::minutes(config.stale_after_mins? as i64);
+    // a client that just started has until `stale_after` for its first success
+    let last = health.last_success.unwrap_or(health.since);
+    (Utc::now() - last > stale_after).then(|| {
+        format!(
+            "no successful action for {} minutes",
+            (Utc::now() - last).num_minutes()
+        )
+    })
+}
+
+async fn log_level_get() -> HttpResponse {
+    let (level, modules) = logging::levels();
+    HttpResponse::Ok().json(serde_json::json!({
+        "level": level.as_str().to_lowercase(),
+        "modules": modules
+            .into_iter()
+            .map(|(module, level)| (module, level.as_str().to_lowercase()))
+            .collect::<std::collections::BTreeMap<_, _>>(),
+    }))
+}
+
+// e.g. `{"level": "debug", "module": "agent::clients::discord"}`, lasts until the agent restarts
+async fn log_level_put(body: web::Json<LogLevelBody>) -> HttpResponse {
+    let level = match body.level.parse::<LevelFilter>() {
+        Ok(level) => level,
+        Err(_) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": format!("unknown level {}", body.level),
+            }))
+        }
+    };
+    logging::set_level(body.module.as_deref(), level);
+    info!(
+        "[ADMIN] Set log level of {} to {}",
+        body.module.as_deref().unwrap_or("every module"),
+        body.level
+    );
+    log_level_get().await
+}
+
+async fn metrics_route() -> impl Responder {
+    match metrics::encode() {
+        Ok(body) => HttpResponse::Ok()
+            .content_type("text/plain; version=0.0.4")
+            .body(body),
+        Err(e) => {
+            error!("[ADMIN] Failed to encode metrics: {}", e);
+            HttpResponse::InternalServerError().body(e.to_string())
+        }
+    }
+}
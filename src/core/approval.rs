@@ -0,0 +1,361 @@
+use super::{ApprovalConfig, Generation};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File, OpenOptions},
+    path::PathBuf,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum DraftKind {
+    Post,
+    Reply,
+    // the parts of a thread, separated by `---` lines
+    Thread,
+    Quote,
+    // likes and reposts are only audited, they never wait for approval
+    Like,
+    Repost,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum DraftStatus {
+    Pending,
+    Approved,
+    Rejected,
+    Sent,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DecisionAction {
+    Approved,
+    Edited,
+    Rejected,
+    AutoApproved,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Decision {
+    pub action: DecisionAction,
+    // who decided, e.g. `cli`, `api` or `telegram:@admin`
+    pub by: String,
+    pub at: DateTime<Utc>,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+// generated content held back until an operator decides on it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Draft {
+    pub id: String,
+    pub client: String,
+    pub kind: DraftKind,
+    pub text: String,
+    // the text as generated, kept when the draft is edited
+    pub original: String,
+    // what the draft replies to, shown to the operator
+    #[serde(default)]
+    pub context: Option<String>,
+    // where the client delivers it, only the client reads it
+    #[serde(default)]
+    pub target: serde_json::Value,
+    // kept for the audit log once the draft is sent
+    #[serde(default)]
+    pub generation: Option<Generation>,
+    pub status: DraftStatus,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub decision: Option<Decision>,
+    #[serde(default)]
+    pub sent_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub error: Option<String>,
+    // sent to the telegram admin chat
+    #[serde(default)]
+    pub announced: bool,
+}
+
+impl Draft {
+    // one line summary for the cli and the admin chat
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "[{}] {} {} ({:?}): {}",
+            self.id,
+            self.client,
+            match self.kind {
+                DraftKind::Post => "post",
+                DraftKind::Reply => "reply",
+                DraftKind::Thread => "thread",
+                DraftKind::Quote => "quote",
+                DraftKind::Like => "like",
+                DraftKind::Repost => "repost",
+            },
+            self.status,
+            self.text
+        );
+        if let Some(context) = &self.context {
+            summary.push_str(&format!("\n  replying to: {}", context));
+        }
+        summary
+    }
+}
+
+// persistent queue of outgoing posts and replies, clients submit drafts and deliver them once an
+// operator approved them
+#[derive(Clone, Debug)]
+pub struct ApprovalQueue {
+    config: ApprovalConfig,
+    path: PathBuf,
+}
+
+impl ApprovalQueue {
+    // `None` when approval is disabled
+    pub fn new(config: &ApprovalConfig) -> Option<Self> {
+        config.enabled.then(|| Self {
+            config: config.clone(),
+            path: PathBuf::from(&config.path),
+        })
+    }
+
+    // whether drafts of `client` wait for approval
+    pub fn gates(&self, client: &str) -> bool {
+        self.config.clients.is_empty() || self.config.clients.iter().any(|c| c.name() == client)
+    }
+
+    pub fn admin_chat(&self) -> Option<i64> {
+        self.config.telegram_admin_chat
+    }
+
+    pub fn submit(
+        &self,
+        client: &str,
+        kind: DraftKind,
+        text: &str,
+        context: Option<String>,
+        target: serde_json::Value,
+        generation: Option<Generation>,
+    ) -> Result<Draft, anyhow::Error> {
+        self.update(|drafts| {
+            let id = loop {
+                let id = format!("{:08x}", rand::random::<u32>());
+                if !drafts.iter().any(|draft| draft.id == id) {
+                    break id;
+                }
+            };
+            let draft = Draft {
+                id,
+                client: client.to_string(),
+                kind,
+                text: text.to_string(),
+                original: text.to_string(),
+                context,
+                target,
+                generation,
+                status: DraftStatus::Pending,
+                created_at: Utc::now(),
+                decision: None,
+                sent_at: None,
+                error: None,
+                announced: false,
+            };
+            drafts.push(draft.clone());
+            Ok(draft)
+        })
+    }
+
+    // newest first
+    pub fn list(&self, status: Option<DraftStatus>) -> Result<Vec<Draft>, anyhow::Error> {
+        let _lock = self.lock()?;
+        let mut drafts = self.read()?;
+        drafts.retain(|draft| status.is_none_or(|status| draft.status == status));
+        drafts.reverse();
+        Ok(drafts)
+    }
+
+    pub fn approve(&self, id: &str, by: &str) -> Result<Draft, anyhow::Error> {
+        self.decide(id, by, DecisionAction::Approved, None, None)
+    }
+
+    // replaces the text and approves the draft
+    pub fn edit(&self, id: &str, text: &str, by: &str) -> Result<Draft, anyhow::Error> {
+        if text.trim().is_empty() {
+            return Err(anyhow::anyhow!("edited text must not be empty"));
+        }
+        self.decide(id, by, DecisionAction::Edited, Some(text), None)
+    }
+
+    pub fn reject(
+        &self,
+        id: &str,
+        by: &str,
+        reason: Option<String>,
+    ) -> Result<Draft, anyhow::Error> {
+        self.decide(id, by, DecisionAction::Rejected, None, reason)
+    }
+
+    // approved drafts of `client` that still need to be sent, approving the ones that waited
+    // longer than `auto_approve_mins` first
+    pub fn approved(&self, client: &str) -> Result<Vec<Draft>, anyhow::Error> {
+        let auto_approve = self
+            .config
+            .auto_approve_mins
+            .map(|mins| Utc::now() - Duration::minutes(mins.into()));
+        self.update_if(|drafts| {
+            let mut changed = false;
+            for draft in drafts.iter_mut() {
+                if draft.status == DraftStatus::Pending
+                    && auto_approve.is_some_and(|before| draft.created_at < before)
+                {
+                    draft.status = DraftStatus::Approved;
+                    draft.decision = Some(Decision {
+                        action: DecisionAction::AutoApproved,
+                        by: "timeout".to_string(),
+                        at: Utc::now(),
+                        reason: None,
+                    });
+                    changed = true;
+                }
+            }
+            let approved = drafts
+                .iter()
+                .filter(|draft| draft.client == client && draft.status == DraftStatus::Approved)
+                .cloned()
+                .collect();
+            Ok((approved, changed))
+        })
+    }
+
+    // records whether delivering an approved draft worked
+    pub fn delivered(&self, id: &str, result: Result<(), String>) -> Result<(), anyhow::Error> {
+        self.update(|drafts| {
+            let draft = find(drafts, id)?;
+            match result {
+                Ok(()) => {
+                    draft.status = DraftStatus::Sent;
+                    draft.sent_at = Some(Utc::now());
+                }
+                Err(e) => {
+                    draft.status = DraftStatus::Failed;
+                    draft.error = Some(e);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    // pending drafts the telegram admin chat has not seen yet, marked as seen
+    pub fn announce(&self) -> Result<Vec<Draft>, anyhow::Error> {
+        self.update_if(|drafts| {
+            let announced = drafts
+                .iter_mut()
+                .filter(|draft| draft.status == DraftStatus::Pending && !draft.announced)
+                .map(|draft| {
+                    draft.announced = true;
+                    draft.clone()
+                })
+                .collect::<Vec<_>>();
+            let changed = !announced.is_empty();
+            Ok((announced, changed))
+        })
+    }
+
+    fn decide(
+        &self,
+        id: &str,
+        by: &str,
+        action: DecisionAction,
+        text: Option<&str>,
+        reason: Option<String>,
+    ) -> Result<Draft, anyhow::Error> {
+        self.update(|drafts| {
+            let draft = find(drafts, id)?;
+            if draft.status != DraftStatus::Pending {
+                return Err(anyhow::anyhow!(
+                    "draft {} is {:?}, only pending drafts can be decided on",
+                    id,
+                    draft.status
+                ));
+            }
+            if let Some(text) = text {
+                draft.text = text.to_string();
+            }
+            draft.status = match action {
+                DecisionAction::Rejected => DraftStatus::Rejected,
+                _ => DraftStatus::Approved,
+            };
+            draft.decision = Some(Decision {
+                action,
+                by: by.to_string(),
+                at: Utc::now(),
+                reason,
+            });
+            Ok(draft.clone())
+        })
+    }
+
+    fn update<T>(
+        &self,
+        f: impl FnOnce(&mut Vec<Draft>) -> Result<T, anyhow::Error>,
+    ) -> Result<T, anyhow::Error> {
+        self.update_if(|drafts| Ok((f(drafts)?, true)))
+    }
+
+    // writes the queue back only when `f` says it changed it
+    fn update_if<T>(
+        &self,
+        f: impl FnOnce(&mut Vec<Draft>) -> Result<(T, bool), anyhow::Error>,
+    ) -> Result<T, anyhow::Error> {
+        let _lock = self.lock()?;
+        let mut drafts = self.read()?;
+        let (result, changed) = f(&mut drafts)?;
+        if changed {
+            self.write(&drafts)?;
+        }
+        Ok(result)
+    }
+
+    // the agent and the drafts command are separate processes, an exclusive lock on a file next
+    // to the queue is held around every read-modify-write so neither overwrites the other's
+    // decisions; released when the file is dropped
+    fn lock(&self) -> Result<File, anyhow::Error> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.path.with_extension("json.lock"))?;
+        file.lock()?;
+        Ok(file)
+    }
+
+    fn read(&self) -> Result<Vec<Draft>, anyhow::Error> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(&self.path)?)?)
+    }
+
+    // written to a temporary file first so a crash never leaves half a queue behind
+    fn write(&self, drafts: &[Draft]) -> Result<(), anyhow::Error> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(drafts)?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+fn find<'a>(drafts: &'a mut [Draft], id: &str) -> Result<&'a mut Draft, anyhow::Error> {
+    drafts
+        .iter_mut()
+        .find(|draft| draft.id == id)
+        .ok_or_else(|| anyhow::anyhow!("no draft {}", id))
+}
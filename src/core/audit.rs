@@ -0,0 +1,387 @@
+use super::{
+    Attachment, AuditConfig, Character, Config, Decision, DecisionAction, Draft, DraftKind,
+};
+use chrono::{DateTime, Utc};
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+    sync::Mutex,
+};
+
+static FILE_LOCK: Mutex<()> = Mutex::new(());
+
+const DEFAULT_LIMIT: usize = 100;
+
+// what generated content was generated from, and the media made to go with it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Generation {
+    pub prompt: String,
+    pub preamble: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub media: Vec<Attachment>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditApproval {
+    // approval did not gate the platform
+    NotRequired,
+    Approved,
+    Edited,
+    AutoApproved,
+}
+
+// one message the character sent
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuditRecord {
+    pub timestamp: DateTime<Utc>,
+    pub platform: String,
+    pub kind: DraftKind,
+    // id of the sent post or message, when the platform tells it
+    #[serde(default)]
+    pub message_id: Option<String>,
+    // what it replied to, e.g. the mention or the chat
+    #[serde(default)]
+    pub target_id: Option<String>,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub media: Vec<Attachment>,
+    #[serde(default)]
+    pub prompt: Option<String>,
+    #[serde(default)]
+    pub preamble: Option<String>,
+    pub provider: String,
+    #[serde(default)]
+    pub model: Option<String>,
+    pub character: String,
+    pub character_version: String,
+    pub approval: AuditApproval,
+    #[serde(default)]
+    pub draft_id: Option<String>,
+    #[serde(default)]
+    pub decision: Option<Decision>,
+}
+
+impl AuditRecord {
+    // one line summary for the cli
+    pub fn summary(&self) -> String {
+        format!(
+            "{} {} {}{}{} ({:?}): {}",
+            self.timestamp.to_rfc3339(),
+            self.platform,
+            match self.kind {
+                DraftKind::Post => "post",
+                DraftKind::Reply => "reply",
+                DraftKind::Thread => "thread",
+                DraftKind::Quote => "quote",
+                DraftKind::Like => "like",
+                DraftKind::Repost => "repost",
+            },
+            self.message_id
+                .as_ref()
+                .map(|id| format!(" {}", id))
+                .unwrap_or_default(),
+            self.target_id
+                .as_ref()
+                .map(|id| format!(" to {}", id))
+                .unwrap_or_default(),
+            self.approval,
+            self.content
+        )
+    }
+}
+
+// what a client hands over after sending something
+pub struct Publication<'a> {
+    pub platform: &'a str,
+    pub kind: DraftKind,
+    pub message_id: Option<String>,
+    pub target_id: Option<String>,
+    pub content: &'a str,
+    pub character: &'a Character,
+    pub generation: Option<&'a Generation>,
+    // the approved draft it was sent from
+    pub draft: Option<&'a Draft>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default, clap::Args)]
+pub struct AuditQuery {
+    /// Only what was sent on this platform, e.g. twitter
+    #[arg(long)]
+    pub platform: Option<String>,
+    #[arg(long, value_enum)]
+    pub kind: Option<DraftKind>,
+    /// Id of the sent message or of what it replied to
+    #[arg(long)]
+    pub id: Option<String>,
+    /// Text the content contains, ignoring case
+    #[arg(long)]
+    pub text: Option<String>,
+    /// RFC 3339, e.g. 2025-01-31T00:00:00Z
+    #[arg(long)]
+    pub since: Option<DateTime<Utc>>,
+    #[arg(long)]
+    pub until: Option<DateTime<Utc>>,
+    /// Newest records returned, 100 by default
+    #[arg(long)]
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    fn matches(&self, record: &AuditRecord) -> bool {
+        self.platform
+            .as_ref()
+            .is_none_or(|platform| &record.platform == platform)
+            && self.kind.is_none_or(|kind| record.kind == kind)
+            && self.id.as_ref().is_none_or(|id| {
+                record.message_id.as_ref() == Some(id) || record.target_id.as_ref() == Some(id)
+            })
+            && self
+                .text
+                .as_ref()
+                .is_none_or(|text| record.content.to_lowercase().contains(&text.to_lowercase()))
+            && self.since.is_none_or(|since| record.timestamp >= since)
+            && self.until.is_none_or(|until| record.timestamp < until)
+    }
+}
+
+// append-only jsonl file of everything the clients sent, records are never changed or removed
+#[derive(Clone, Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    provider: String,
+    model: Option<String>,
+}
+
+impl AuditLog {
+    // `None` when auditing is disabled
+    pub fn new(config: &Config) -> Option<Self> {
+        let provider = config.completion_provider.name();
+        config.audit.enabled.then(|| Self {
+            path: PathBuf::from(&config.audit.path),
+            provider: provider.to_string(),
+            model: config
+                .providers
+                .model_provider(provider)
+                .completion_model
+                .clone(),
+        })
+    }
+
+    // a failed write is logged, it never stops the client
+    pub fn record(&self, publication: Publication) {
+        let approval = match publication
+            .draft
+            .and_then(|draft| draft.decision.as_ref())
+            .map(|decision| decision.action)
+        {
+            None => AuditApproval::NotRequired,
+            Some(DecisionAction::Edited) => AuditApproval::Edited,
+            Some(DecisionAction::AutoApproved) => AuditApproval::AutoApproved,
+            Some(_) => AuditApproval::Approved,
+        };
+        let record = AuditRecord {
+            timestamp: Utc::now(),
+            platform: publication.platform.to_string(),
+            kind: publication.kind,
+            message_id: publication.message_id,
+            target_id: publication.target_id,
+            content: publication.content.to_string(),
+            media: publication
+                .generation
+                .map(|generation| generation.media.clone())
+                .unwrap_or_default(),
+            prompt: publication
+                .generation
+                .map(|generation| generation.prompt.clone()),
+            preamble: publication
+                .generation
+                .map(|generation| generation.preamble.clone()),
+            provider: self.provider.clone(),
+            model: self.model.clone(),
+            character: publication.character.alias.clone(),
+            character_version: publication.character.version(),
+            approval,
+            draft_id: publication.draft.map(|draft| draft.id.clone()),
+            decision: publication.draft.and_then(|draft| draft.decision.clone()),
+        };
+        if let Err(e) = self.append(&record) {
+            error!(
+                "[AUDIT] Failed to record {} {:?}: {}",
+                record.platform, record.kind, e
+            );
+        }
+    }
+
+    fn append(&self, record: &AuditRecord) -> Result<(), anyhow::Error> {
+        let _lock = FILE_LOCK.lock().unwrap();
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(record)?)?;
+        file.sync_data()?;
+        Ok(())
+    }
+}
+
+// newest first, readable while the agent appends to the file
+pub fn search(config: &AuditConfig, query: &AuditQuery) -> Result<Vec<AuditRecord>, anyhow::Error> {
+    let path = PathBuf::from(&config.path);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut records = Vec::new();
+    for (i, line) in BufReader::new(fs::File::open(&path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<AuditRecord>(&line) {
+            Ok(record) if query.matches(&record) => records.push(record),
+            Ok(_) => {}
+            Err(e) => warn!("[AUDIT] Skipping line {} of {}: {}", i + 1, config.path, e),
+        }
+    }
+    records.reverse();
+    records.truncate(query.limit.unwrap_or(DEFAULT_LIMIT));
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(timestamp: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(timestamp)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn record(platform: &str, kind: DraftKind, message_id: &str, content: &str) -> AuditRecord {
+        AuditRecord {
+            timestamp: at("2025-01-15T12:00:00Z"),
+            platform: platform.to_string(),
+            kind,
+            message_id: Some(message_id.to_string()),
+            target_id: Some("target".to_string()),
+            content: content.to_string(),
+            media: Vec::new(),
+            prompt: None,
+            preamble: None,
+            provider: "anthropic".to_string(),
+            model: None,
+            character: "test".to_string(),
+            character_version: "v1".to_string(),
+            approval: AuditApproval::NotRequired,
+            draft_id: None,
+            decision: None,
+        }
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        let record = record("twitter", DraftKind::Post, "1", "Hello");
+        assert!(AuditQuery::default().matches(&record));
+    }
+
+    #[test]
+    fn query_filters_combine() {
+        let record = record("twitter", DraftKind::Reply, "1", "Hello World");
+        let query = |query: AuditQuery| query.matches(&record);
+
+        assert!(query(AuditQuery {
+            platform: Some("twitter".to_string()),
+            kind: Some(DraftKind::Reply),
+            ..Default::default()
+        }));
+        assert!(!query(AuditQuery {
+            platform: Some("twitter".to_string()),
+            kind: Some(DraftKind::Post),
+            ..Default::default()
+        }));
+        assert!(!query(AuditQuery {
+            platform: Some("truth".to_string()),
+            ..Default::default()
+        }));
+    }
+
+    #[test]
+    fn id_matches_the_message_or_its_target() {
+        let record = record("discord", DraftKind::Reply, "1", "Hello");
+        for (id, matches) in [("1", true), ("target", true), ("2", false)] {
+            let query = AuditQuery {
+                id: Some(id.to_string()),
+                ..Default::default()
+            };
+            assert_eq!(query.matches(&record), matches, "id {id}");
+        }
+    }
+
+    #[test]
+    fn text_ignores_case() {
+        let record = record("discord", DraftKind::Reply, "1", "Hello World");
+        let query = |text: &str| AuditQuery {
+            text: Some(text.to_string()),
+            ..Default::default()
+        };
+        assert!(query("hello world").matches(&record));
+        assert!(query("WORLD").matches(&record));
+        assert!(!query("goodbye").matches(&record));
+    }
+
+    #[test]
+    fn since_is_inclusive_and_until_exclusive() {
+        let record = record("discord", DraftKind::Reply, "1", "Hello");
+        let query = |since: &str, until: &str| AuditQuery {
+            since: Some(at(since)),
+            until: Some(at(until)),
+            ..Default::default()
+        };
+        assert!(query("2025-01-15T12:00:00Z", "2025-01-15T12:00:01Z").matches(&record));
+        assert!(!query("2025-01-15T11:00:00Z", "2025-01-15T12:00:00Z").matches(&record));
+        assert!(!query("2025-01-15T12:00:01Z", "2025-01-16T00:00:00Z").matches(&record));
+    }
+
+    #[test]
+    fn search_returns_the_newest_matches_first() {
+        let path = std::env::temp_dir().join(format!("audit_test_{}.jsonl", std::process::id()));
+        let lines = [
+            serde_json::to_string(&record("twitter", DraftKind::Post, "1", "first")).unwrap(),
+            "not a record".to_string(),
+            serde_json::to_string(&record("truth", DraftKind::Post, "2", "second")).unwrap(),
+            String::new(),
+            serde_json::to_string(&record("twitter", DraftKind::Post, "3", "third")).unwrap(),
+        ];
+        fs::write(&path, lines.join("\n")).unwrap();
+        let config = AuditConfig {
+            enabled: true,
+            path: path.to_string_lossy().to_string(),
+        };
+
+        let ids = |query: AuditQuery| {
+            search(&config, &query)
+                .unwrap()
+                .into_iter()
+                .filter_map(|record| record.message_id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(AuditQuery::default()), ["3", "2", "1"]);
+        assert_eq!(
+            ids(AuditQuery {
+                platform: Some("twitter".to_string()),
+                limit: Some(1),
+                ..Default::default()
+            }),
+            ["3"]
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
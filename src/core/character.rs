@@ -1,5 +1,6 @@
 use rand::seq::SliceRandom;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::{fs, io::Write};
 
 #[derive(Deserialize, Clone, Serialize)]
@@ -46,6 +47,16 @@ impl Character {
         Ok(())
     }
 
+    // short hash of the loaded character, changes with every edit of its file
+    pub fn version(&self) -> String {
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        Sha256::digest(json)
+            .iter()
+            .take(6)
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+
     pub fn choose_random_traits(&self, trait_type: CharacterTrait, count: usize) -> String {
         match trait_type {
             CharacterTrait::Adjectives => self
@@ -1,26 +1,391 @@
+use super::{layers, APPROVAL_FOLDER, AUDIT_FOLDER, EMBEDDING_CACHE_FOLDER, LOG_FOLDER};
 use crate::clients::{
-    ApiConfig, DiscordConfig, StorytellingConfig, TelegramConfig, TruthConfig, TwitterConfig,
+    social::MediaSource, ApiConfig, CliConfig, DiscordConfig, StorytellingConfig, TelegramConfig,
+    TruthConfig, TwitterConfig,
 };
 use serde::Deserialize;
-use std::fs;
+use std::{collections::HashMap, fmt};
 
 #[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     pub client_configs: ClientConfigs,
     pub enabled_clients: Vec<Clients>,
     pub completion_provider: CompletionProvider,
     #[serde(default = "default_embedding_provider")]
     pub embedding_provider: EmbeddingProvider,
+    #[serde(default)]
+    pub embedding: EmbeddingConfig,
     #[serde(default = "default_db")]
     pub db: DatabaseProvider,
+    #[serde(default)]
+    pub retrieval: RetrievalConfig,
+    #[serde(default)]
+    pub context: ContextConfig,
+    #[serde(default)]
+    pub knowledge: KnowledgeConfig,
+    #[serde(default)]
+    pub approval: ApprovalConfig,
+    #[serde(default)]
+    pub audit: AuditConfig,
+    #[serde(default)]
+    pub supervisor: SupervisorConfig,
+    #[serde(default)]
+    pub admin: AdminConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub providers: ProvidersConfig,
 }
 
 impl Config {
-    pub fn new(path: String) -> Result<Self, anyhow::Error> {
-        let config_content = fs::read_to_string(path)?;
-        let config: Config = serde_json::from_str(&config_content)?;
+    // defaults, then the file, then environment variables, then `--set key=value` overrides
+    pub fn load(path: &str, overrides: &[String]) -> Result<Self, anyhow::Error> {
+        let value = layers::load(path, overrides)?;
+        let config: Config = layers::deserialize(value)?;
         Ok(config)
     }
+
+    // reports every missing value and invalid combination at once, `agent` is false for
+    // subcommands that never talk to the completion model or clients
+    pub fn check(&self, agent: bool) -> Result<(), anyhow::Error> {
+        let mut problems = self
+            .missing(agent)
+            .into_iter()
+            .map(|path| format!("missing {}", path))
+            .collect::<Vec<_>>();
+        problems.extend(self.invalid());
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(
+                "Invalid configuration:\n  {}",
+                problems.join("\n  ")
+            ))
+        }
+    }
+
+    // every credential and model name the configured providers and clients need but are not set
+    pub fn missing(&self, agent: bool) -> Vec<String> {
+        let providers = &self.providers;
+        let mut missing = Vec::new();
+        let mut require = |path: &str, set: bool| {
+            if !set {
+                missing.push(layers::describe(path));
+            }
+        };
+
+        let embedding = match self.embedding_provider {
+            EmbeddingProvider::Local => None,
+            EmbeddingProvider::OpenAI => Some("openai"),
+            EmbeddingProvider::XAI => Some("xai"),
+            EmbeddingProvider::Cohere => Some("cohere"),
+            EmbeddingProvider::Gemini => Some("gemini"),
+            EmbeddingProvider::OpenAICompatible => Some("openai_compatible"),
+        };
+        if let Some(name) = embedding {
+            let provider = providers.model_provider(name);
+            require(
+                &format!("providers.{name}.api_key"),
+                is_set(&provider.api_key),
+            );
+        }
+        if matches!(self.embedding_provider, EmbeddingProvider::OpenAICompatible) {
+            require("embedding.base_url", self.embedding.base_url.is_some());
+        }
+
+        if matches!(self.db, DatabaseProvider::MongoDB) {
+            require(
+                "providers.mongodb.conn_url",
+                is_set(&providers.mongodb.conn_url),
+            );
+            require("providers.mongodb.db", providers.mongodb.db.is_some());
+        }
+
+        if agent {
+            let name = self.completion_provider.name();
+            let provider = providers.model_provider(name);
+            require(
+                &format!("providers.{name}.api_key"),
+                is_set(&provider.api_key),
+            );
+            require(
+                &format!("providers.{name}.completion_model"),
+                provider.completion_model.is_some(),
+            );
+
+            let enabled = |client: Clients| self.enabled_clients.contains(&client);
+            if enabled(Clients::Discord) && self.client_configs.discord.is_some() {
+                require("providers.discord.token", is_set(&providers.discord.token));
+            }
+            if enabled(Clients::Telegram) && self.client_configs.telegram.is_some() {
+                require(
+                    "providers.telegram.token",
+                    is_set(&providers.telegram.token),
+                );
+            }
+            if enabled(Clients::Twitter) && self.client_configs.twitter.is_some() {
+                let twitter = &providers.twitter;
+                require("providers.twitter.api_key", is_set(&twitter.api_key));
+                require("providers.twitter.api_secret", is_set(&twitter.api_secret));
+                require(
+                    "providers.twitter.access_token",
+                    is_set(&twitter.access_token),
+                );
+                require(
+                    "providers.twitter.access_token_secret",
+                    is_set(&twitter.access_token_secret),
+                );
+            }
+            if enabled(Clients::Truth) && self.client_configs.truth.is_some() {
+                require(
+                    "providers.truth.username",
+                    providers.truth.username.is_some(),
+                );
+                require(
+                    "providers.truth.password",
+                    is_set(&providers.truth.password),
+                );
+            }
+            if (enabled(Clients::Twitter) && self.client_configs.twitter.is_some()
+                || enabled(Clients::Truth) && self.client_configs.truth.is_some())
+                && matches!(self.db, DatabaseProvider::MongoDB)
+            {
+                require(
+                    "providers.mongodb.collection",
+                    providers.mongodb.collection.is_some(),
+                );
+            }
+            if enabled(Clients::Storytelling)
+                && self
+                    .client_configs
+                    .storytelling
+                    .as_ref()
+                    .is_some_and(|config| config.use_tts)
+            {
+                require(
+                    "providers.elevenlabs.api_key",
+                    is_set(&providers.elevenlabs.api_key),
+                );
+            }
+            let social = [
+                (Clients::Twitter, &self.client_configs.twitter),
+                (Clients::Truth, &self.client_configs.truth),
+            ];
+            for (client, config) in social {
+                let Some(media) = config.as_ref().and_then(|config| config.media.as_ref()) else {
+                    continue;
+                };
+                if !enabled(client) {
+                    continue;
+                }
+                for source in &media.sources {
+                    match source {
+                        MediaSource::Image { .. } => require(
+                            "providers.openai.api_key",
+                            is_set(&providers.openai.api_key),
+                        ),
+                        MediaSource::Tts { .. } => require(
+                            "providers.elevenlabs.api_key",
+                            is_set(&providers.elevenlabs.api_key),
+                        ),
+                        MediaSource::Files { .. } => {}
+                    }
+                }
+            }
+        }
+
+        missing
+    }
+
+    // values that parse but cannot work, alone or together with other values
+    pub fn invalid(&self) -> Vec<String> {
+        let mut invalid = Vec::new();
+        let clients = &self.client_configs;
+
+        for client in &self.enabled_clients {
+            let configured = match client {
+                Clients::Api => clients.api.is_some(),
+                Clients::Cli => clients.cli.is_some(),
+                Clients::Discord => clients.discord.is_some(),
+                Clients::Storytelling => clients.storytelling.is_some(),
+                Clients::Telegram => clients.telegram.is_some(),
+                Clients::Twitter => clients.twitter.is_some(),
+                Clients::Truth => clients.truth.is_some(),
+            };
+            if !configured {
+                invalid.push(format!(
+                    "enabled_clients has {} but client_configs.{} is not set",
+                    client.name(),
+                    client.name()
+                ));
+            }
+        }
+        if self.enabled_clients.contains(&Clients::Cli) && self.enabled_clients.len() > 1 {
+            invalid.push("the cli client cannot run together with other clients".to_string());
+        }
+        if let (Some(api), Some(storytelling)) = (&clients.api, &clients.storytelling) {
+            if self.enabled_clients.contains(&Clients::Api)
+                && self.enabled_clients.contains(&Clients::Storytelling)
+                && api.port == storytelling.port
+            {
+                invalid.push(format!(
+                    "client_configs.api.port and client_configs.storytelling.port are both {}",
+                    api.port
+                ));
+            }
+        }
+        if let Some(storytelling) = &clients.storytelling {
+            match storytelling.paragraph_count[..] {
+                [min, max] if min <= max => {}
+                _ => invalid.push(
+                    "client_configs.storytelling.paragraph_count must be [min, max]".to_string(),
+                ),
+            }
+        }
+        if clients.twitter.as_ref().is_some_and(|twitter| {
+            twitter.media.as_ref().is_some_and(|media| {
+                media
+                    .sources
+                    .iter()
+                    .any(|source| matches!(source, MediaSource::Tts { .. }))
+            })
+        }) {
+            invalid.push(
+                "client_configs.twitter.media cannot use tts, twitter takes no audio".to_string(),
+            );
+        }
+        if clients
+            .truth
+            .as_ref()
+            .is_some_and(|truth| truth.thread.is_some())
+        {
+            invalid.push(
+                "client_configs.truth.thread is not supported, only twitter posts threads"
+                    .to_string(),
+            );
+        }
+
+        let memories = [
+            ("api", clients.api.as_ref().map(|c| &c.memory)),
+            ("cli", clients.cli.as_ref().map(|c| &c.memory)),
+            ("discord", clients.discord.as_ref().map(|c| &c.memory)),
+            ("telegram", clients.telegram.as_ref().map(|c| &c.memory)),
+        ];
+        for (name, memory) in memories {
+            let Some(memory) = memory else { continue };
+            if memory.scoring.decay <= 0.0 || memory.scoring.decay > 1.0 {
+                invalid.push(format!(
+                    "client_configs.{name}.memory.scoring.decay must be within 0.0 < x <= 1.0"
+                ));
+            }
+            if memory.reflection.enabled && !memory.enabled {
+                invalid.push(format!(
+                    "client_configs.{name}.memory.reflection needs client_configs.{name}.memory.enabled"
+                ));
+            }
+            if memory.reflection.interval_mins == 0 {
+                invalid.push(format!(
+                    "client_configs.{name}.memory.reflection.interval_mins must be above 0"
+                ));
+            }
+        }
+
+        if !(0.0..=1.0).contains(&self.retrieval.keyword_weight) {
+            invalid.push("retrieval.keyword_weight must be within 0.0 <= x <= 1.0".to_string());
+        }
+        if self.knowledge.chunk_overlap >= self.knowledge.chunk_tokens {
+            invalid
+                .push("knowledge.chunk_overlap must be below knowledge.chunk_tokens".to_string());
+        }
+        if let Some(context_length) = self.context.context_length {
+            if self.context.reserve_tokens >= context_length {
+                invalid.push(
+                    "context.reserve_tokens must be below context.context_length".to_string(),
+                );
+            }
+        }
+        if let Some(dims) = self.embedding.dimensions {
+            if dims == 0 {
+                invalid.push("embedding.dimensions must be above 0".to_string());
+            }
+        }
+        if self.embedding.base_url.is_some()
+            && !matches!(self.embedding_provider, EmbeddingProvider::OpenAICompatible)
+        {
+            invalid.push(
+                "embedding.base_url is only used by the openai_compatible embedding provider"
+                    .to_string(),
+            );
+        }
+        if self.admin.enabled {
+            let ports = [
+                ("api", clients.api.as_ref().map(|api| api.port)),
+                (
+                    "storytelling",
+                    clients
+                        .storytelling
+                        .as_ref()
+                        .map(|storytelling| storytelling.port),
+                ),
+            ];
+            for (name, port) in ports {
+                if port == Some(self.admin.port) {
+                    invalid.push(format!(
+                        "admin.port and client_configs.{name}.port are both {}",
+                        self.admin.port
+                    ));
+                }
+            }
+        }
+        if self.admin.stale_after_mins == Some(0) {
+            invalid.push("admin.stale_after_mins must be above 0".to_string());
+        }
+        for (name, level) in std::iter::once(("logging.level".to_string(), &self.logging.level))
+            .chain(
+                self.logging
+                    .modules
+                    .iter()
+                    .map(|(module, level)| (format!("logging.modules.{module}"), level)),
+            )
+        {
+            if level.parse::<log::LevelFilter>().is_err() {
+                invalid.push(format!(
+                    "{name} must be one of off, error, warn, info, debug or trace, not {level}"
+                ));
+            }
+        }
+        if !self.logging.stdout && self.logging.file.is_none() {
+            invalid.push("logging needs stdout or a file".to_string());
+        }
+        if self
+            .logging
+            .file
+            .as_ref()
+            .is_some_and(|file| file.max_size_mb == 0)
+        {
+            invalid.push("logging.file.max_size_mb must be above 0".to_string());
+        }
+        if self.supervisor.backoff_secs == 0 {
+            invalid.push("supervisor.backoff_secs must be above 0".to_string());
+        }
+        if self.supervisor.max_backoff_secs < self.supervisor.backoff_secs {
+            invalid.push(
+                "supervisor.max_backoff_secs must not be below supervisor.backoff_secs".to_string(),
+            );
+        }
+        if self.approval.auto_approve_mins == Some(0) {
+            invalid.push("approval.auto_approve_mins must be above 0".to_string());
+        }
+        if self.approval.telegram_admin_chat.is_some()
+            && !self.enabled_clients.contains(&Clients::Telegram)
+        {
+            invalid.push("approval.telegram_admin_chat needs the telegram client".to_string());
+        }
+
+        invalid
+    }
 }
 
 // DBS
@@ -36,6 +401,567 @@ fn default_db() -> DatabaseProvider {
     DatabaseProvider::Local
 }
 
+// RETRIEVAL
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RetrievalConfig {
+    #[serde(default = "default_retrieval_candidates")]
+    pub candidates: usize,
+    #[serde(default = "default_keyword_weight")]
+    pub keyword_weight: f64,
+    #[serde(default)]
+    pub reranker: Option<String>,
+}
+
+impl Default for RetrievalConfig {
+    fn default() -> Self {
+        Self {
+            candidates: default_retrieval_candidates(),
+            keyword_weight: default_keyword_weight(),
+            reranker: None,
+        }
+    }
+}
+
+fn default_retrieval_candidates() -> usize {
+    20
+}
+
+fn default_keyword_weight() -> f64 {
+    0.3
+}
+
+// CONTEXT
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ContextConfig {
+    // overrides the context window looked up from the model name
+    #[serde(default)]
+    pub context_length: Option<usize>,
+    // tokens kept free for the response
+    #[serde(default = "default_reserve_tokens")]
+    pub reserve_tokens: usize,
+    // summarize documents that do not fit instead of dropping them
+    #[serde(default = "default_summarize")]
+    pub summarize: bool,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            context_length: None,
+            reserve_tokens: default_reserve_tokens(),
+            summarize: default_summarize(),
+        }
+    }
+}
+
+fn default_reserve_tokens() -> usize {
+    1024
+}
+
+fn default_summarize() -> bool {
+    true
+}
+
+// KNOWLEDGE
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct KnowledgeConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    // re-ingested on startup so edited files are picked up without running the command
+    #[serde(default)]
+    pub dir: Option<String>,
+    // chunks put into the prompt
+    #[serde(default = "default_knowledge_top_n")]
+    pub top_n: usize,
+    #[serde(default = "default_chunk_tokens")]
+    pub chunk_tokens: usize,
+    // tokens repeated between neighbouring chunks
+    #[serde(default = "default_chunk_overlap")]
+    pub chunk_overlap: usize,
+    // mongodb collection prefix, suffixed with the character and needs its own `vector_index`
+    #[serde(default = "default_knowledge_collection")]
+    pub collection: String,
+}
+
+impl Default for KnowledgeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: None,
+            top_n: default_knowledge_top_n(),
+            chunk_tokens: default_chunk_tokens(),
+            chunk_overlap: default_chunk_overlap(),
+            collection: default_knowledge_collection(),
+        }
+    }
+}
+
+fn default_knowledge_top_n() -> usize {
+    4
+}
+
+fn default_chunk_tokens() -> usize {
+    400
+}
+
+fn default_chunk_overlap() -> usize {
+    50
+}
+
+fn default_knowledge_collection() -> String {
+    "knowledge".to_string()
+}
+
+// APPROVAL
+// outgoing posts and replies wait in a draft queue until an operator approves them
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ApprovalConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    // clients whose drafts wait for approval, all of them when empty
+    #[serde(default)]
+    pub clients: Vec<Clients>,
+    // pending drafts are approved after this long, never when unset
+    #[serde(default)]
+    pub auto_approve_mins: Option<u32>,
+    // telegram chat that is sent new drafts and takes /approve, /edit and /reject
+    #[serde(default)]
+    pub telegram_admin_chat: Option<i64>,
+    #[serde(default = "default_approval_path")]
+    pub path: String,
+}
+
+impl Default for ApprovalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            clients: Vec::new(),
+            auto_approve_mins: None,
+            telegram_admin_chat: None,
+            path: default_approval_path(),
+        }
+    }
+}
+
+fn default_approval_path() -> String {
+    format!("{}/drafts.json", APPROVAL_FOLDER)
+}
+
+// AUDIT
+// append-only record of everything the character sent
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AuditConfig {
+    #[serde(default = "default_audit_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_audit_path")]
+    pub path: String,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_audit_enabled(),
+            path: default_audit_path(),
+        }
+    }
+}
+
+fn default_audit_enabled() -> bool {
+    true
+}
+
+fn default_audit_path() -> String {
+    format!("{}/published.jsonl", AUDIT_FOLDER)
+}
+
+// SUPERVISOR
+// failed clients are restarted after `backoff_secs`, doubling up to `max_backoff_secs`
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SupervisorConfig {
+    // restarts in a row before a client is given up on
+    #[serde(default = "default_max_restarts")]
+    pub max_restarts: u32,
+    #[serde(default = "default_backoff_secs")]
+    pub backoff_secs: u64,
+    #[serde(default = "default_max_backoff_secs")]
+    pub max_backoff_secs: u64,
+    // a client that ran this long before failing has its restarts reset
+    #[serde(default = "default_reset_after_secs")]
+    pub reset_after_secs: u64,
+    // how long clients get to finish on SIGINT or SIGTERM
+    #[serde(default = "default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u64,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        Self {
+            max_restarts: default_max_restarts(),
+            backoff_secs: default_backoff_secs(),
+            max_backoff_secs: default_max_backoff_secs(),
+            reset_after_secs: default_reset_after_secs(),
+            shutdown_timeout_secs: default_shutdown_timeout_secs(),
+        }
+    }
+}
+
+fn default_max_restarts() -> u32 {
+    5
+}
+
+fn default_backoff_secs() -> u64 {
+    5
+}
+
+fn default_max_backoff_secs() -> u64 {
+    300
+}
+
+fn default_reset_after_secs() -> u64 {
+    600
+}
+
+fn default_shutdown_timeout_secs() -> u64 {
+    30
+}
+
+// ADMIN
+// health, readiness and prometheus metrics over http
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AdminConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_admin_host")]
+    pub host: String,
+    #[serde(default = "default_admin_port")]
+    pub port: u16,
+    // clients without a successful action for this long are not ready, never checked when unset
+    #[serde(default)]
+    pub stale_after_mins: Option<u64>,
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: default_admin_host(),
+            port: default_admin_port(),
+            stale_after_mins: None,
+        }
+    }
+}
+
+fn default_admin_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_admin_port() -> u16 {
+    9100
+}
+
+// LOGGING
+// levels are `off`, `error`, `warn`, `info`, `debug` or `trace`
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LoggingConfig {
+    #[serde(default = "default_log_level")]
+    pub level: String,
+    // by module path, e.g. `"agent::clients::discord": "debug"` or `"serenity": "warn"`
+    #[serde(default)]
+    pub modules: HashMap<String, String>,
+    #[serde(default)]
+    pub format: LogFormat,
+    #[serde(default = "default_log_stdout")]
+    pub stdout: bool,
+    #[serde(default)]
+    pub file: Option<LogFileConfig>,
+    // masks provider credentials, bearer tokens and passwords in every line
+    #[serde(default = "default_log_redact")]
+    pub redact: bool,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: default_log_level(),
+            modules: HashMap::new(),
+            format: LogFormat::default(),
+            stdout: default_log_stdout(),
+            file: None,
+            redact: default_log_redact(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum LogFormat {
+    #[serde(rename = "text")]
+    #[default]
+    Text,
+    // one json object per line
+    #[serde(rename = "json")]
+    Json,
+}
+
+// rotated once it reaches `max_size_mb`, keeping `keep` old files as `<path>.1`, `<path>.2`, ...
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LogFileConfig {
+    #[serde(default = "default_log_path")]
+    pub path: String,
+    #[serde(default = "default_log_file_format")]
+    pub format: LogFormat,
+    #[serde(default = "default_log_max_size_mb")]
+    pub max_size_mb: u64,
+    #[serde(default = "default_log_keep")]
+    pub keep: usize,
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+fn default_log_stdout() -> bool {
+    true
+}
+
+fn default_log_redact() -> bool {
+    true
+}
+
+fn default_log_path() -> String {
+    format!("{}/agent.log", LOG_FOLDER)
+}
+
+fn default_log_file_format() -> LogFormat {
+    LogFormat::Json
+}
+
+fn default_log_max_size_mb() -> u64 {
+    10
+}
+
+fn default_log_keep() -> usize {
+    5
+}
+
+// CONVERSATION MEMORY
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ConversationMemoryConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    // memories put into the prompt
+    #[serde(default = "default_conversation_top_n")]
+    pub top_n: usize,
+    // memories pulled from the store before filtering them by scope
+    #[serde(default = "default_retrieval_candidates")]
+    pub candidates: usize,
+    #[serde(default)]
+    pub scope: ConversationScope,
+    // mongodb collection, needs its own `vector_index`
+    #[serde(default = "default_conversation_collection")]
+    pub collection: String,
+    // ask the model how important each exchange is when storing it
+    #[serde(default = "default_rate_importance")]
+    pub rate_importance: bool,
+    #[serde(default)]
+    pub scoring: MemoryScoringConfig,
+    #[serde(default)]
+    pub reflection: ReflectionConfig,
+}
+
+impl Default for ConversationMemoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            top_n: default_conversation_top_n(),
+            candidates: default_retrieval_candidates(),
+            scope: ConversationScope::default(),
+            collection: default_conversation_collection(),
+            rate_importance: default_rate_importance(),
+            scoring: MemoryScoringConfig::default(),
+            reflection: ReflectionConfig::default(),
+        }
+    }
+}
+
+// recalled memories are ranked by the weighted sum of their normalized recency, importance and
+// relevance, like in generative agents
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MemoryScoringConfig {
+    #[serde(default = "default_memory_weight")]
+    pub recency_weight: f64,
+    #[serde(default = "default_memory_weight")]
+    pub importance_weight: f64,
+    #[serde(default = "default_memory_weight")]
+    pub relevance_weight: f64,
+    // recency is `decay ^ hours since the memory was stored`
+    #[serde(default = "default_memory_decay")]
+    pub decay: f64,
+}
+
+impl Default for MemoryScoringConfig {
+    fn default() -> Self {
+        Self {
+            recency_weight: default_memory_weight(),
+            importance_weight: default_memory_weight(),
+            relevance_weight: default_memory_weight(),
+            decay: default_memory_decay(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ReflectionConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_reflection_interval_mins")]
+    pub interval_mins: u64,
+    // summed importance of a user's new memories needed before reflecting on them
+    #[serde(default = "default_reflection_threshold")]
+    pub threshold: u32,
+    // most recent memories the reflection looks at
+    #[serde(default = "default_reflection_memories")]
+    pub memories: usize,
+    // insights stored per reflection
+    #[serde(default = "default_reflection_insights")]
+    pub insights: usize,
+}
+
+impl Default for ReflectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_mins: default_reflection_interval_mins(),
+            threshold: default_reflection_threshold(),
+            memories: default_reflection_memories(),
+            insights: default_reflection_insights(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum ConversationScope {
+    // everything the speaker said to the character on this client
+    #[serde(rename = "user")]
+    #[default]
+    User,
+    // only what the speaker said in the current channel / chat
+    #[serde(rename = "channel")]
+    Channel,
+}
+
+fn default_conversation_top_n() -> usize {
+    3
+}
+
+fn default_conversation_collection() -> String {
+    "conversations".to_string()
+}
+
+fn default_rate_importance() -> bool {
+    true
+}
+
+fn default_memory_weight() -> f64 {
+    1.0
+}
+
+fn default_memory_decay() -> f64 {
+    0.995
+}
+
+fn default_reflection_interval_mins() -> u64 {
+    60
+}
+
+fn default_reflection_threshold() -> u32 {
+    40
+}
+
+fn default_reflection_memories() -> usize {
+    20
+}
+
+fn default_reflection_insights() -> usize {
+    3
+}
+
+// SCHEDULE
+// when a recurring client action runs, checked by `Scheduler::new` on startup
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleConfig {
+    // `sec min hour day month weekday`, the seconds field can be left out
+    #[serde(default)]
+    pub cron: Option<String>,
+    // minutes between runs, picked at random from `[min, max]`
+    #[serde(default)]
+    pub every: Option<Vec<u32>>,
+    // IANA name the cron expression, windows and quiet hours are read in
+    #[serde(default = "default_schedule_timezone")]
+    pub timezone: String,
+    // runs only happen inside one of these, any time when empty
+    #[serde(default)]
+    pub windows: Vec<TimeWindow>,
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+    // up to this many minutes are added to every run
+    #[serde(default)]
+    pub jitter: u32,
+    // runs per day in `timezone`
+    #[serde(default)]
+    pub daily_cap: Option<u32>,
+}
+
+impl ScheduleConfig {
+    // a plain random interval, what `post_delay` / `reply_delay` used to mean
+    pub fn every(min: u32, max: u32) -> Self {
+        Self {
+            cron: None,
+            every: Some(vec![min, max]),
+            timezone: default_schedule_timezone(),
+            windows: Vec::new(),
+            quiet_hours: None,
+            jitter: 0,
+            daily_cap: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TimeWindow {
+    // `mon`..`sun`, every day when empty
+    #[serde(default)]
+    pub days: Vec<String>,
+    // `HH:MM`
+    pub start: String,
+    pub end: String,
+}
+
+// `HH:MM`, may wrap past midnight
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct QuietHours {
+    pub start: String,
+    pub end: String,
+}
+
+fn default_schedule_timezone() -> String {
+    "UTC".to_string()
+}
+
 // EMBEDDING PROVIDERS
 #[derive(Deserialize, Debug, Clone)]
 pub enum EmbeddingProvider {
@@ -45,12 +971,68 @@ pub enum EmbeddingProvider {
     OpenAI,
     #[serde(rename = "xai")]
     XAI,
+    #[serde(rename = "cohere")]
+    Cohere,
+    #[serde(rename = "gemini")]
+    Gemini,
+    #[serde(rename = "openai_compatible")]
+    OpenAICompatible,
+}
+
+impl EmbeddingProvider {
+    // model name and dimensions used when `embedding.model` / `embedding.dimensions` are not set
+    pub fn default_model(&self) -> Option<(&'static str, usize)> {
+        match self {
+            Self::Local => Some(("Qdrant/all-MiniLM-L6-v2", 384)),
+            Self::OpenAI => Some(("text-embedding-ada-002", 1536)),
+            Self::XAI => Some(("text-embedding", 768)),
+            Self::Cohere => Some(("embed-english-v3.0", 1024)),
+            Self::Gemini => Some(("text-embedding-004", 768)),
+            Self::OpenAICompatible => None,
+        }
+    }
 }
 
 fn default_embedding_provider() -> EmbeddingProvider {
     EmbeddingProvider::Local
 }
 
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct EmbeddingConfig {
+    #[serde(default = "default_embedding_cache")]
+    pub cache: bool,
+    #[serde(default = "default_embedding_cache_dir")]
+    pub cache_dir: String,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub dimensions: Option<usize>,
+    // only used by the openai_compatible provider
+    #[serde(default)]
+    pub base_url: Option<String>,
+}
+
+impl Default for EmbeddingConfig {
+    fn default() -> Self {
+        Self {
+            cache: default_embedding_cache(),
+            cache_dir: default_embedding_cache_dir(),
+            model: None,
+            dimensions: None,
+            base_url: None,
+        }
+    }
+}
+
+fn default_embedding_cache() -> bool {
+    true
+}
+
+fn default_embedding_cache_dir() -> String {
+    EMBEDDING_CACHE_FOLDER.to_string()
+}
+
 // PROVIDERS
 #[derive(Deserialize, Debug, Clone)]
 pub enum CompletionProvider {
@@ -70,6 +1052,247 @@ pub enum CompletionProvider {
     DeepSeek,
 }
 
+impl CompletionProvider {
+    // key of the provider in the `providers` section
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Anthropic => "anthropic",
+            Self::Cohere => "cohere",
+            Self::Gemini => "gemini",
+            Self::OpenAI => "openai",
+            Self::Perplexity => "perplexity",
+            Self::XAI => "xai",
+            Self::DeepSeek => "deepseek",
+        }
+    }
+}
+
+// CREDENTIALS
+// credential that never shows up in logs
+#[derive(Deserialize, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"***\"")
+    }
+}
+
+fn is_set(secret: &Option<Secret>) -> bool {
+    secret.as_ref().is_some_and(|secret| !secret.0.is_empty())
+}
+
+// every credential and model name the agent uses, checked up front by `Config::check`
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ProvidersConfig {
+    #[serde(default)]
+    pub anthropic: ModelProviderConfig,
+    #[serde(default)]
+    pub cohere: ModelProviderConfig,
+    #[serde(default)]
+    pub gemini: ModelProviderConfig,
+    #[serde(default)]
+    pub openai: ModelProviderConfig,
+    #[serde(default)]
+    pub perplexity: ModelProviderConfig,
+    #[serde(default)]
+    pub xai: ModelProviderConfig,
+    #[serde(default)]
+    pub deepseek: ModelProviderConfig,
+    #[serde(default)]
+    pub openai_compatible: ModelProviderConfig,
+    #[serde(default)]
+    pub elevenlabs: ModelProviderConfig,
+    #[serde(default)]
+    pub mongodb: MongoDbConfig,
+    #[serde(default)]
+    pub discord: BotConfig,
+    #[serde(default)]
+    pub telegram: BotConfig,
+    #[serde(default)]
+    pub twitter: TwitterCredentials,
+    #[serde(default)]
+    pub truth: TruthCredentials,
+}
+
+impl ProvidersConfig {
+    // every credential that is set, masked in the logs
+    pub fn secrets(&self) -> Vec<String> {
+        let model_providers = [
+            &self.anthropic,
+            &self.cohere,
+            &self.gemini,
+            &self.openai,
+            &self.perplexity,
+            &self.xai,
+            &self.deepseek,
+            &self.openai_compatible,
+            &self.elevenlabs,
+        ];
+        model_providers
+            .iter()
+            .map(|provider| &provider.api_key)
+            .chain([
+                &self.mongodb.conn_url,
+                &self.discord.token,
+                &self.telegram.token,
+                &self.twitter.api_key,
+                &self.twitter.api_secret,
+                &self.twitter.access_token,
+                &self.twitter.access_token_secret,
+                &self.truth.password,
+            ])
+            .filter(|secret| is_set(secret))
+            .flatten()
+            .map(|secret| secret.expose().to_string())
+            .collect()
+    }
+
+    pub fn model_provider(&self, name: &str) -> &ModelProviderConfig {
+        match name {
+            "anthropic" => &self.anthropic,
+            "cohere" => &self.cohere,
+            "gemini" => &self.gemini,
+            "openai" => &self.openai,
+            "perplexity" => &self.perplexity,
+            "xai" => &self.xai,
+            "deepseek" => &self.deepseek,
+            "openai_compatible" => &self.openai_compatible,
+            "elevenlabs" => &self.elevenlabs,
+            _ => panic!("Unknown provider {name}"),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ModelProviderConfig {
+    #[serde(default)]
+    pub api_key: Option<Secret>,
+    #[serde(default)]
+    pub completion_model: Option<String>,
+}
+
+impl ModelProviderConfig {
+    pub fn api_key(&self) -> &str {
+        self.api_key
+            .as_ref()
+            .map(Secret::expose)
+            .expect("api_key not set")
+    }
+
+    pub fn completion_model(&self) -> &str {
+        self.completion_model
+            .as_deref()
+            .expect("completion_model not set")
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct MongoDbConfig {
+    #[serde(default)]
+    pub conn_url: Option<Secret>,
+    #[serde(default)]
+    pub db: Option<String>,
+    // collection of the twitter and truth search memory
+    #[serde(default)]
+    pub collection: Option<String>,
+}
+
+impl MongoDbConfig {
+    pub fn conn_url(&self) -> String {
+        self.conn_url
+            .as_ref()
+            .map(|conn_url| conn_url.expose().to_string())
+            .expect("providers.mongodb.conn_url not set")
+    }
+
+    pub fn db(&self) -> &String {
+        self.db.as_ref().expect("providers.mongodb.db not set")
+    }
+
+    pub fn collection(&self) -> &String {
+        self.collection
+            .as_ref()
+            .expect("providers.mongodb.collection not set")
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct BotConfig {
+    #[serde(default)]
+    pub token: Option<Secret>,
+}
+
+impl BotConfig {
+    pub fn token(&self) -> &str {
+        self.token
+            .as_ref()
+            .map(Secret::expose)
+            .expect("token not set")
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct TwitterCredentials {
+    #[serde(default)]
+    pub api_key: Option<Secret>,
+    #[serde(default)]
+    pub api_secret: Option<Secret>,
+    #[serde(default)]
+    pub access_token: Option<Secret>,
+    #[serde(default)]
+    pub access_token_secret: Option<Secret>,
+    // the account's api tier, sets the monthly post budget
+    #[serde(default)]
+    pub tier: TwitterTier,
+    // overrides the tier's budget
+    #[serde(default)]
+    pub monthly_posts: Option<u32>,
+}
+
+impl TwitterCredentials {
+    // posts, replies and quotes per month, `None` when unlimited
+    pub fn post_budget(&self) -> Option<u32> {
+        self.monthly_posts.or(match self.tier {
+            TwitterTier::Free => Some(500),
+            TwitterTier::Basic => Some(3_000),
+            TwitterTier::Pro => Some(300_000),
+            TwitterTier::Enterprise => None,
+        })
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TwitterTier {
+    #[default]
+    Free,
+    Basic,
+    Pro,
+    Enterprise,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct TruthCredentials {
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<Secret>,
+}
+
 // Client
 #[derive(Deserialize, Debug, Clone, PartialEq)]
 pub enum Clients {
@@ -89,14 +1312,210 @@ pub enum Clients {
     Truth,
 }
 
+impl Clients {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Api => "api",
+            Self::Cli => "cli",
+            Self::Discord => "discord",
+            Self::Storytelling => "storytelling",
+            Self::Telegram => "telegram",
+            Self::Twitter => "twitter",
+            Self::Truth => "truth",
+        }
+    }
+}
+
 // Client Configs
 #[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct ClientConfigs {
     pub api: Option<ApiConfig>,
-    pub cli: Option<bool>,
+    pub cli: Option<CliConfig>,
     pub discord: Option<DiscordConfig>,
     pub storytelling: Option<StorytellingConfig>,
     pub telegram: Option<TelegramConfig>,
     pub twitter: Option<TwitterConfig>,
     pub truth: Option<TruthConfig>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    // a valid config with `extra` on top, then what `invalid` reports about it
+    fn invalid(extra: Value) -> Vec<String> {
+        let mut config = json!({
+            "client_configs": {},
+            "enabled_clients": [],
+            "completion_provider": "anthropic",
+        });
+        for (key, value) in extra.as_object().unwrap() {
+            config[key] = value.clone();
+        }
+        let config: Config = layers::deserialize(config).unwrap();
+        config.invalid()
+    }
+
+    fn assert_invalid(extra: Value, problem: &str) {
+        let problems = invalid(extra);
+        assert!(
+            problems.len() == 1 && problems[0].contains(problem),
+            "expected only `{problem}`, got {problems:?}"
+        );
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(invalid(json!({})).is_empty());
+        assert!(invalid(json!({
+            "client_configs": { "api": { "port": 3000 }, "storytelling": { "port": 3001, "paragraph_count": [1, 3] } },
+            "enabled_clients": ["api", "storytelling"],
+        }))
+        .is_empty());
+    }
+
+    #[test]
+    fn enabled_clients_need_a_config() {
+        assert_invalid(
+            json!({ "enabled_clients": ["discord"] }),
+            "enabled_clients has discord but client_configs.discord is not set",
+        );
+    }
+
+    #[test]
+    fn cli_runs_alone() {
+        assert_invalid(
+            json!({
+                "client_configs": { "cli": true, "api": { "port": 3000 } },
+                "enabled_clients": ["cli", "api"],
+            }),
+            "the cli client cannot run together with other clients",
+        );
+    }
+
+    #[test]
+    fn enabled_servers_need_their_own_ports() {
+        assert_invalid(
+            json!({
+                "client_configs": { "api": { "port": 3000 }, "storytelling": { "port": 3000, "paragraph_count": [1, 3] } },
+                "enabled_clients": ["api", "storytelling"],
+            }),
+            "client_configs.api.port and client_configs.storytelling.port are both 3000",
+        );
+        // only clashes between enabled clients count
+        assert!(invalid(json!({
+            "client_configs": { "api": { "port": 3000 }, "storytelling": { "port": 3000, "paragraph_count": [1, 3] } },
+            "enabled_clients": ["api"],
+        }))
+        .is_empty());
+        assert_invalid(
+            json!({
+                "client_configs": { "api": { "port": 3000 } },
+                "admin": { "enabled": true, "port": 3000 },
+            }),
+            "admin.port and client_configs.api.port are both 3000",
+        );
+    }
+
+    #[test]
+    fn paragraph_count_is_a_range() {
+        assert_invalid(
+            json!({ "client_configs": { "storytelling": { "port": 3001, "paragraph_count": [3, 1] } } }),
+            "paragraph_count must be [min, max]",
+        );
+    }
+
+    #[test]
+    fn social_clients_reject_what_their_platform_lacks() {
+        let schedule = json!({ "every": [30, 60] });
+        assert_invalid(
+            json!({ "client_configs": { "twitter": {
+                "post": schedule, "reply": schedule,
+                "media": { "sources": [{ "type": "tts", "voice_id": "voice" }] },
+            } } }),
+            "client_configs.twitter.media cannot use tts",
+        );
+        assert_invalid(
+            json!({ "client_configs": { "truth": {
+                "post": schedule, "reply": schedule,
+                "thread": { "schedule": schedule },
+            } } }),
+            "client_configs.truth.thread is not supported",
+        );
+    }
+
+    #[test]
+    fn memory_settings_are_checked_per_client() {
+        assert_invalid(
+            json!({ "client_configs": { "api": { "port": 3000, "memory": { "scoring": { "decay": 0.0 } } } } }),
+            "client_configs.api.memory.scoring.decay must be within 0.0 < x <= 1.0",
+        );
+        assert_invalid(
+            json!({ "client_configs": { "cli": { "memory": { "reflection": { "enabled": true } } } } }),
+            "client_configs.cli.memory.reflection needs client_configs.cli.memory.enabled",
+        );
+    }
+
+    #[test]
+    fn token_settings_leave_room() {
+        assert_invalid(
+            json!({ "knowledge": { "chunk_tokens": 100, "chunk_overlap": 100 } }),
+            "knowledge.chunk_overlap must be below knowledge.chunk_tokens",
+        );
+        assert_invalid(
+            json!({ "context": { "context_length": 1000, "reserve_tokens": 1000 } }),
+            "context.reserve_tokens must be below context.context_length",
+        );
+        assert_invalid(
+            json!({ "retrieval": { "keyword_weight": 1.5 } }),
+            "retrieval.keyword_weight must be within 0.0 <= x <= 1.0",
+        );
+    }
+
+    #[test]
+    fn embedding_base_url_needs_openai_compatible() {
+        assert_invalid(
+            json!({ "embedding": { "base_url": "http://localhost:11434/v1" } }),
+            "embedding.base_url is only used by the openai_compatible embedding provider",
+        );
+        assert!(invalid(json!({
+            "embedding_provider": "openai_compatible",
+            "embedding": { "base_url": "http://localhost:11434/v1" },
+        }))
+        .is_empty());
+    }
+
+    #[test]
+    fn logging_needs_known_levels_and_an_output() {
+        assert_invalid(
+            json!({ "logging": { "modules": { "rig": "loud" } } }),
+            "logging.modules.rig must be one of off, error, warn, info, debug or trace, not loud",
+        );
+        assert_invalid(
+            json!({ "logging": { "stdout": false } }),
+            "logging needs stdout or a file",
+        );
+    }
+
+    #[test]
+    fn supervisor_backoff_grows() {
+        assert_invalid(
+            json!({ "supervisor": { "backoff_secs": 60, "max_backoff_secs": 30 } }),
+            "supervisor.max_backoff_secs must not be below supervisor.backoff_secs",
+        );
+        assert_invalid(
+            json!({ "supervisor": { "backoff_secs": 0 } }),
+            "supervisor.backoff_secs must be above 0",
+        );
+    }
+
+    #[test]
+    fn telegram_approval_needs_the_telegram_client() {
+        assert_invalid(
+            json!({ "approval": { "telegram_admin_chat": 42 } }),
+            "approval.telegram_admin_chat needs the telegram client",
+        );
+    }
+}
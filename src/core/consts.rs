@@ -1,2 +1,14 @@
 pub const CONFIG_PATH: &str = "config.json";
+// looked up in order when no --config is given
+pub const CONFIG_PATHS: &[&str] = &["config.json", "config.toml", "config.yaml", "config.yml"];
 pub const CHARACTERS_FOLDER: &str = "characters";
+pub const EMBEDDING_CACHE_FOLDER: &str = "cache/embeddings";
+pub const MEMORY_SNAPSHOT_FOLDER: &str = "data/memory";
+pub const APPROVAL_FOLDER: &str = "data/approval";
+pub const STATE_FOLDER: &str = "data/state";
+pub const LOG_FOLDER: &str = "logs";
+pub const AUDIT_FOLDER: &str = "data/audit";
+pub const MEDIA_FOLDER: &str = "data/media";
+// mongodb collection of the client state, next to the memory collection
+pub const STATE_COLLECTION: &str = "client_state";
+pub const MIGRATION_STATE_FOLDER: &str = "cache/migrations";
@@ -0,0 +1,430 @@
+use super::{
+    memory::{self, Memory, MemoryStore, DEFAULT_IMPORTANCE},
+    metrics, Agent, Config as RootConfig, ConversationMemoryConfig, ConversationScope,
+    DatabaseProvider, MEMORY_SNAPSHOT_FOLDER,
+};
+use crate::{
+    dbs::{LocalMemoryStore, MemoryStoreEnum, MongoDbMemoryStore},
+    providers::completion::CompletionResponseEnum,
+};
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use mongodb::bson;
+use rig::{
+    embeddings::{Embedding, EmbeddingModel},
+    Embed, OneOrMany,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
+use tokio::{sync::Mutex, task::JoinHandle};
+
+#[derive(Embed, Clone, Deserialize, Debug, Serialize, Eq, PartialEq, Default)]
+pub struct ConversationDoc {
+    pub id: String,
+    pub client: String,
+    // platform id, only used to filter by
+    pub user: String,
+    // display name at the time, what the exchange and reflections call the user
+    #[serde(default)]
+    pub name: String,
+    pub channel: String,
+    pub timestamp: String,
+    #[embed]
+    pub exchange: String,
+    #[serde(default = "default_importance")]
+    pub importance: u32,
+    // insight distilled from earlier exchanges instead of an exchange itself
+    #[serde(default)]
+    pub reflection: bool,
+}
+
+fn default_importance() -> u32 {
+    DEFAULT_IMPORTANCE
+}
+
+impl Memory for ConversationDoc {
+    fn importance(&self) -> u32 {
+        self.importance
+    }
+
+    fn timestamp(&self) -> Option<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(&self.timestamp)
+            .ok()
+            .map(|timestamp| timestamp.with_timezone(&Utc))
+    }
+}
+
+impl ConversationDoc {
+    pub fn to_document(data: &ConversationDoc, embedding: &OneOrMany<Embedding>) -> bson::Document {
+        let mut doc = bson::Document::new();
+        doc.insert("id", data.id.clone());
+        doc.insert("client", data.client.clone());
+        doc.insert("user", data.user.clone());
+        doc.insert("name", data.name.clone());
+        doc.insert("channel", data.channel.clone());
+        doc.insert("timestamp", data.timestamp.clone());
+        doc.insert("exchange", data.exchange.clone());
+        doc.insert("importance", data.importance);
+        doc.insert("reflection", data.reflection);
+        doc.insert("embedding", embedding.first().vec.clone());
+        doc
+    }
+}
+
+// long-term memory of past exchanges between the character and the people it talks to
+#[derive(Clone)]
+pub struct ConversationMemory<EM>
+where
+    EM: EmbeddingModel,
+{
+    client: String,
+    config: ConversationMemoryConfig,
+    store: Arc<Mutex<MemoryStoreEnum<ConversationDoc, EM>>>,
+    embedding_model: EM,
+}
+
+impl<EM> ConversationMemory<EM>
+where
+    EM: EmbeddingModel,
+{
+    // returns `None` when conversation memory is disabled for the client
+    pub async fn new(
+        client: &str,
+        config: &ConversationMemoryConfig,
+        root_config: &RootConfig,
+        embedding_model: EM,
+    ) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+
+        let store: MemoryStoreEnum<ConversationDoc, EM> = match root_config.db {
+            DatabaseProvider::Local => MemoryStoreEnum::Local {
+                memory_store: LocalMemoryStore::with_snapshot(
+                    embedding_model.clone(),
+                    &format!("{}/conversations_{}.jsonl", MEMORY_SNAPSHOT_FOLDER, client),
+                )
+                .expect("Failed to load local conversation memory snapshot"),
+            },
+            DatabaseProvider::MongoDB => MemoryStoreEnum::MongoDB {
+                memory_store: MongoDbMemoryStore::new(
+                    root_config.providers.mongodb.conn_url(),
+                    root_config.providers.mongodb.db(),
+                    &config.collection,
+                    embedding_model.clone(),
+                    ConversationDoc::to_document,
+                )
+                .await
+                .expect("Failed to create MongoDB conversation memory store"),
+            },
+        };
+
+        store
+            .check_ndims(embedding_model.ndims())
+            .await
+            .expect("Embedding dimensions do not match the conversation memory store");
+        info!("[MEMORY] Loaded conversation memory for {}", client);
+
+        Some(Self {
+            client: client.to_string(),
+            config: config.clone(),
+            store: Arc::new(Mutex::new(store)),
+            embedding_model,
+        })
+    }
+
+    pub async fn recall(&self, user: &str, channel: &str, query: &str) -> Vec<ConversationDoc> {
+        // embedded before locking so other exchanges are not held up by the embedding request
+        let embedding = match self.embedding_model.embed_text(query).await {
+            Ok(embedding) => embedding,
+            Err(e) => {
+                error!("[MEMORY] Failed to embed conversation query: {}", e);
+                return Vec::new();
+            }
+        };
+
+        let filter = [("client", self.client.as_str()), ("user", user)];
+        let candidates = match self
+            .store
+            .lock()
+            .await
+            .top_n_where::<ConversationDoc>(
+                &embedding,
+                self.config.candidates.max(self.config.top_n),
+                &filter,
+            )
+            .await
+        {
+            Ok(candidates) => candidates,
+            Err(e) => {
+                error!("[MEMORY] Failed to recall conversations: {}", e);
+                return Vec::new();
+            }
+        };
+
+        // reflections are about the user, not about a channel
+        let candidates = candidates
+            .into_iter()
+            .filter(|(_, _, doc)| {
+                self.config.scope == ConversationScope::User
+                    || doc.reflection
+                    || doc.channel == channel
+            })
+            .collect::<Vec<_>>();
+
+        memory::rank(candidates, &self.config.scoring)
+            .into_iter()
+            .map(|(_, _, doc)| doc)
+            .take(self.config.top_n)
+            .collect()
+    }
+
+    pub async fn remember<CM>(
+        &self,
+        agent: &Agent<CM>,
+        user: &str,
+        name: &str,
+        channel: &str,
+        message: &str,
+        reply: &str,
+    ) where
+        CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
+    {
+        let exchange = format!("{name}: {message}\nyou: {reply}");
+        let importance = if self.config.rate_importance {
+            Self::rate_importance(agent, &exchange).await
+        } else {
+            DEFAULT_IMPORTANCE
+        };
+
+        let doc = self.new_doc(user, name, channel, exchange, importance, false);
+        let mut store = self.store.lock().await;
+        if let Err(e) = store.add(doc).await {
+            error!("[MEMORY] Failed to store conversation: {}", e);
+            return;
+        }
+        if let Ok(count) = store.count().await {
+            metrics::memory_documents(&format!("conversations_{}", self.client), count);
+        }
+    }
+
+    fn new_doc(
+        &self,
+        user: &str,
+        name: &str,
+        channel: &str,
+        exchange: String,
+        importance: u32,
+        reflection: bool,
+    ) -> ConversationDoc {
+        let now = chrono::Utc::now();
+        ConversationDoc {
+            id: format!(
+                "{}-{}-{}",
+                self.client,
+                channel,
+                now.timestamp_nanos_opt().unwrap_or_default()
+            ),
+            client: self.client.clone(),
+            user: user.to_string(),
+            name: name.to_string(),
+            channel: channel.to_string(),
+            timestamp: now.to_rfc3339(),
+            exchange,
+            importance,
+            reflection,
+        }
+    }
+
+    async fn rate_importance<CM>(agent: &Agent<CM>, memory: &str) -> u32
+    where
+        CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
+    {
+        let prompt = format!(
+            r"On the scale of 1 to 10, where 1 is purely mundane (e.g., greetings, small talk) and 10 is extremely poignant (e.g., a break up, a life changing event), rate the likely poignancy of the following memory.
+            <memory>
+            {memory}
+            </memory>
+            Only output the number."
+        );
+        let request = agent.completion_model.completion_request(&prompt).build();
+        match agent.completion(request).await {
+            Ok(response) => agent
+                .response_extract_content(response)
+                .split(|c: char| !c.is_ascii_digit())
+                .find_map(|digits| digits.parse::<u32>().ok())
+                .map_or(DEFAULT_IMPORTANCE, |rating| rating.clamp(1, 10)),
+            Err(e) => {
+                error!("[MEMORY] Failed to rate importance: {}", e);
+                DEFAULT_IMPORTANCE
+            }
+        }
+    }
+
+    // distills what was said since each user's last reflection into insights once enough
+    // important memories piled up
+    pub async fn reflect<CM>(&self, agent: &Agent<CM>) -> Result<(), anyhow::Error>
+    where
+        CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
+    {
+        let config = &self.config.reflection;
+
+        // the store is locked for one page at a time, so replies can recall and remember while a
+        // large store is scanned
+        let mut by_user: HashMap<String, Vec<ConversationDoc>> = HashMap::new();
+        let mut offset = 0;
+        loop {
+            let page = self
+                .store
+                .lock()
+                .await
+                .documents::<ConversationDoc>(offset, 256)
+                .await?;
+            if page.is_empty() {
+                break;
+            }
+            offset += page.len();
+            for (_, doc) in page {
+                if doc.client == self.client {
+                    by_user.entry(doc.user.clone()).or_default().push(doc);
+                }
+            }
+        }
+
+        for (user, docs) in by_user {
+            // the latest name the user went by, older documents only have the id
+            let name = docs
+                .iter()
+                .filter(|doc| !doc.name.is_empty())
+                .max_by_key(|doc| doc.timestamp())
+                .map_or(user.clone(), |doc| doc.name.clone());
+            let last_reflection = docs
+                .iter()
+                .filter(|doc| doc.reflection)
+                .filter_map(|doc| doc.timestamp())
+                .max();
+            let mut pending = docs
+                .into_iter()
+                .filter(|doc| !doc.reflection)
+                .filter(|doc| match (doc.timestamp(), last_reflection) {
+                    (Some(timestamp), Some(last)) => timestamp > last,
+                    _ => true,
+                })
+                .collect::<Vec<_>>();
+            if pending.iter().map(|doc| doc.importance).sum::<u32>() < config.threshold {
+                continue;
+            }
+
+            pending.sort_by_key(|doc| std::cmp::Reverse(doc.timestamp()));
+            pending.truncate(config.memories);
+            pending.reverse();
+
+            let prompt = format!(
+                r"<memories>
+                {memories}
+                </memories>
+
+                What are the {insights} most high-level insights you can infer about {name} from <memories>?
+                <rules>
+                - One insight per line, no numbering.
+                - Write from your own point of view in plain sentences.
+                - Only output the insights.
+                </rules>",
+                memories = pending
+                    .iter()
+                    .map(|doc| format!("[{}]\n{}", doc.timestamp, doc.exchange))
+                    .collect::<Vec<_>>()
+                    .join("\n\n"),
+                insights = config.insights,
+            );
+            let request = agent
+                .completion_model
+                .completion_request(&prompt)
+                .preamble(
+                    "You reflect on your conversations to understand people better.".to_string(),
+                )
+                .build();
+            let response = agent.completion(request).await?;
+            let content = agent.response_extract_content(response);
+
+            let mut reflections = Vec::new();
+            for insight in content
+                .lines()
+                .map(|line| line.trim().trim_start_matches(['-', '*']).trim())
+                .filter(|line| !line.is_empty())
+                .take(config.insights)
+            {
+                let importance = if self.config.rate_importance {
+                    Self::rate_importance(agent, insight).await
+                } else {
+                    DEFAULT_IMPORTANCE
+                };
+                reflections.push(self.new_doc(
+                    &user,
+                    &name,
+                    "",
+                    insight.to_string(),
+                    importance,
+                    true,
+                ));
+            }
+
+            info!(
+                "[MEMORY] Stored {} reflections about {} on {}",
+                reflections.len(),
+                name,
+                self.client
+            );
+            if !reflections.is_empty() {
+                self.store.lock().await.add_many(reflections).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // runs `reflect` every `reflection.interval_mins` in the background when reflection is enabled
+    // callers keep the handle and abort it when their client stops
+    pub fn start_reflection<CM>(&self, agent: Agent<CM>) -> Option<JoinHandle<()>>
+    where
+        CM: rig::completion::CompletionModel<Response = CompletionResponseEnum> + 'static,
+        EM: 'static,
+    {
+        if !self.config.reflection.enabled {
+            return None;
+        }
+
+        let memory = self.clone();
+        Some(tokio::spawn(async move {
+            let interval =
+                std::time::Duration::from_secs(memory.config.reflection.interval_mins * 60);
+            loop {
+                tokio::time::sleep(interval).await;
+                if let Err(e) = memory.reflect(&agent).await {
+                    error!("[MEMORY] Failed to reflect on {}: {}", memory.client, e);
+                }
+            }
+        }))
+    }
+
+    // renders recalled memories as a prompt block, empty when there is nothing to add
+    pub fn prompt_block(memories: &[ConversationDoc]) -> String {
+        if memories.is_empty() {
+            return String::new();
+        }
+        format!(
+            "<memories>\nPast conversations with this user, use them to stay consistent:\n{}\n</memories>",
+            memories
+                .iter()
+                .map(|doc| {
+                    if doc.reflection {
+                        format!("[{}] insight\n{}", doc.timestamp, doc.exchange)
+                    } else {
+                        format!("[{}]\n{}", doc.timestamp, doc.exchange)
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n")
+        )
+    }
+}
@@ -0,0 +1,266 @@
+use super::Agent;
+use crate::providers::completion::{budget::count_tokens, CompletionResponseEnum};
+use log::{error, info};
+use rig::completion::Message;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+// chat history that folds its oldest turns into a rolling summary once it outgrows its token budget
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConversationBuffer {
+    turns: VecDeque<Message>,
+    summary: Option<String>,
+    max_tokens: usize,
+    // set while folded turns are out being summarized
+    #[serde(skip)]
+    compacting: bool,
+    // after a failed summary no other is tried until the buffer holds this many turns
+    #[serde(skip)]
+    retry_at: usize,
+}
+
+impl ConversationBuffer {
+    // most recent messages that always stay verbatim
+    const KEEP_MESSAGES: usize = 4;
+    // multiple of the budget past which the oldest turns are dropped while summaries fail
+    const HARD_CAP: usize = 2;
+
+    pub fn new(max_tokens: usize) -> Self {
+        Self {
+            turns: VecDeque::new(),
+            summary: None,
+            max_tokens,
+            compacting: false,
+            retry_at: 0,
+        }
+    }
+
+    // takes the turns and summary of a saved buffer, keeping this buffer's budget
+    pub fn restore(&mut self, saved: ConversationBuffer) {
+        self.turns = saved.turns;
+        self.summary = saved.summary;
+    }
+
+    pub fn push(&mut self, role: &str, content: impl Into<String>) {
+        self.turns.push_back(Message {
+            role: role.to_string(),
+            content: content.into(),
+        });
+    }
+
+    // turns in chronological order, ready for `.messages(...)`
+    pub fn messages(&self) -> Vec<Message> {
+        self.turns.iter().cloned().collect()
+    }
+
+    // summary of the folded turns to append to the preamble, empty until the first compaction
+    pub fn summary_block(&self) -> String {
+        match &self.summary {
+            Some(summary) => format!(
+                "\n\n<conversationSummary>\n{}\n</conversationSummary>",
+                summary
+            ),
+            None => String::new(),
+        }
+    }
+
+    pub fn tokens(&self) -> usize {
+        self.summary.as_deref().map_or(0, count_tokens)
+            + self
+                .turns
+                .iter()
+                .map(|m| count_tokens(&m.content))
+                .sum::<usize>()
+    }
+
+    pub fn needs_compaction(&self) -> bool {
+        !self.compacting
+            && self.turns.len() >= self.retry_at
+            && self.tokens() > self.max_tokens
+            && self.turns.len() > Self::KEEP_MESSAGES
+    }
+
+    pub async fn compact<CM>(&mut self, agent: &Agent<CM>)
+    where
+        CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
+    {
+        if let Some(mut compaction) = self.start_compaction() {
+            compaction.summarize(agent).await;
+            self.finish_compaction(compaction);
+        }
+    }
+
+    // takes the oldest turns out to be summarized, so a buffer behind a lock can be released while
+    // the model writes the summary
+    pub fn start_compaction(&mut self) -> Option<Compaction> {
+        if !self.needs_compaction() {
+            return None;
+        }
+
+        self.compacting = true;
+        let fold = self.turns.len() - Self::KEEP_MESSAGES;
+        Some(Compaction {
+            folded: self.turns.drain(..fold).collect(),
+            previous: self.summary.clone(),
+            words: (self.max_tokens / 4).max(50),
+            summary: None,
+        })
+    }
+
+    // stores the new summary, or puts the folded turns back when there is none; those are then
+    // kept under a hard cap and summarized again only after a few more turns
+    pub fn finish_compaction(&mut self, compaction: Compaction) {
+        self.compacting = false;
+        match compaction.summary {
+            Some(summary) => {
+                info!(
+                    "[HISTORY] Folded {} messages into the summary",
+                    compaction.folded.len()
+                );
+                self.summary = Some(summary);
+                self.retry_at = 0;
+            }
+            None => {
+                for message in compaction.folded.into_iter().rev() {
+                    self.turns.push_front(message);
+                }
+                let mut dropped = 0;
+                while self.tokens() > self.max_tokens * Self::HARD_CAP
+                    && self.turns.len() > Self::KEEP_MESSAGES
+                {
+                    self.turns.pop_front();
+                    dropped += 1;
+                }
+                if dropped > 0 {
+                    error!(
+                        "[HISTORY] Dropped the {} oldest messages over the hard cap",
+                        dropped
+                    );
+                }
+                self.retry_at = self.turns.len() + Self::KEEP_MESSAGES;
+            }
+        }
+    }
+}
+
+// turns taken out of a buffer to be folded into its summary
+pub struct Compaction {
+    folded: Vec<Message>,
+    previous: Option<String>,
+    words: usize,
+    summary: Option<String>,
+}
+
+impl Compaction {
+    pub async fn summarize<CM>(&mut self, agent: &Agent<CM>)
+    where
+        CM: rig::completion::CompletionModel<Response = CompletionResponseEnum>,
+    {
+        let prompt = format!(
+            r"<previousSummary>
+            {previous}
+            </previousSummary>
+
+            <conversation>
+            {conversation}
+            </conversation>
+
+            Rewrite <previousSummary> so it also covers <conversation>.
+            <rules>
+            - Keep names, facts, promises and open questions, drop small talk.
+            - Write from the assistant's point of view in plain sentences.
+            - Less than {words} words.
+            - Only output the summary.
+            </rules>
+            ",
+            previous = self.previous.as_deref().unwrap_or("None"),
+            conversation = self
+                .folded
+                .iter()
+                .map(|m| format!("{}: {}", m.role, m.content))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            words = self.words,
+        );
+
+        let request = agent
+            .completion_model
+            .completion_request(&prompt)
+            .preamble("You summarize conversations for your own long-term context.".to_string())
+            .build();
+        match agent.completion(request).await {
+            Ok(response) => {
+                let summary = agent.response_extract_content(response);
+                if summary.trim().is_empty() {
+                    error!(
+                        "[HISTORY] Summary was empty, kept {} messages",
+                        self.folded.len()
+                    );
+                } else {
+                    self.summary = Some(summary.trim().to_string());
+                }
+            }
+            Err(e) => error!(
+                "[HISTORY] Failed to summarize, kept {} messages: {}",
+                self.folded.len(),
+                e
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(max_tokens: usize, turns: usize) -> ConversationBuffer {
+        let mut buffer = ConversationBuffer::new(max_tokens);
+        for i in 0..turns {
+            buffer.push("user", format!("message number {}", i));
+        }
+        buffer
+    }
+
+    #[test]
+    fn compaction_keeps_the_latest_turns() {
+        let mut buffer = buffer(10, 8);
+        let mut compaction = buffer.start_compaction().unwrap();
+        assert_eq!(compaction.folded.len(), 4);
+        assert!(buffer.start_compaction().is_none());
+
+        compaction.summary = Some("earlier messages".to_string());
+        buffer.finish_compaction(compaction);
+        assert_eq!(buffer.messages().len(), 4);
+        assert_eq!(buffer.messages()[0].content, "message number 4");
+        assert!(buffer.summary_block().contains("earlier messages"));
+    }
+
+    #[test]
+    fn failed_compaction_puts_turns_back_and_waits() {
+        let mut buffer = buffer(20, 6);
+        let compaction = buffer.start_compaction().unwrap();
+        buffer.finish_compaction(compaction);
+        assert_eq!(buffer.messages().len(), 6);
+        assert_eq!(buffer.messages()[0].content, "message number 0");
+
+        for i in 6..9 {
+            buffer.push("user", format!("message number {}", i));
+            assert!(!buffer.needs_compaction());
+        }
+        buffer.push("user", "message number 9");
+        assert!(buffer.needs_compaction());
+    }
+
+    #[test]
+    fn failed_compaction_drops_turns_over_the_hard_cap() {
+        let mut buffer = buffer(10, 30);
+        let compaction = buffer.start_compaction().unwrap();
+        buffer.finish_compaction(compaction);
+        assert!(buffer.tokens() <= 10 * ConversationBuffer::HARD_CAP);
+        assert!(buffer.messages().len() >= ConversationBuffer::KEEP_MESSAGES);
+        assert_eq!(
+            buffer.messages().last().unwrap().content,
+            "message number 29"
+        );
+    }
+}
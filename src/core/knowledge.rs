@@ -0,0 +1,412 @@
+use super::{
+    memory::MemoryStore, metrics, Config as RootConfig, DatabaseProvider, KnowledgeConfig,
+    MEMORY_SNAPSHOT_FOLDER,
+};
+use crate::{
+    dbs::{LocalMemoryStore, MemoryStoreEnum, MongoDbMemoryStore},
+    providers::completion::budget::{count_tokens, token_windows},
+};
+use log::{error, info, warn};
+use mongodb::bson;
+use rig::{
+    embeddings::{Embedding, EmbeddingModel},
+    Embed, OneOrMany,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::sync::Mutex;
+
+const SUPPORTED_EXTENSIONS: &[&str] = &["md", "markdown", "txt", "html", "htm", "pdf"];
+
+#[derive(Embed, Clone, Deserialize, Debug, Serialize, Eq, PartialEq, Default)]
+pub struct KnowledgeChunk {
+    pub id: String,
+    pub character: String,
+    // path of the file the chunk was cut from
+    pub source: String,
+    // sha256 of the whole source file, used to skip files that did not change
+    pub hash: String,
+    pub position: u32,
+    #[embed]
+    pub text: String,
+}
+
+impl KnowledgeChunk {
+    pub fn to_document(data: &KnowledgeChunk, embedding: &OneOrMany<Embedding>) -> bson::Document {
+        let mut doc = bson::Document::new();
+        doc.insert("id", data.id.clone());
+        doc.insert("character", data.character.clone());
+        doc.insert("source", data.source.clone());
+        doc.insert("hash", data.hash.clone());
+        doc.insert("position", data.position);
+        doc.insert("text", data.text.clone());
+        doc.insert("embedding", embedding.first().vec.clone());
+        doc
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct IngestStats {
+    pub ingested: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+    pub failed: usize,
+    pub chunks: usize,
+}
+
+// documents a character can draw on beyond its lore, chunked and embedded per character
+#[derive(Clone)]
+pub struct KnowledgeBase<EM>
+where
+    EM: EmbeddingModel,
+{
+    character: String,
+    config: KnowledgeConfig,
+    store: Arc<Mutex<MemoryStoreEnum<KnowledgeChunk, EM>>>,
+}
+
+impl<EM> KnowledgeBase<EM>
+where
+    EM: EmbeddingModel,
+{
+    pub async fn open(
+        character: &str,
+        root_config: &RootConfig,
+        embedding_model: EM,
+    ) -> Result<Self, anyhow::Error> {
+        let config = &root_config.knowledge;
+        let name = Self::store_name(character);
+        let store: MemoryStoreEnum<KnowledgeChunk, EM> = match root_config.db {
+            DatabaseProvider::Local => MemoryStoreEnum::Local {
+                memory_store: LocalMemoryStore::with_snapshot(
+                    embedding_model.clone(),
+                    &format!("{}/knowledge_{}.jsonl", MEMORY_SNAPSHOT_FOLDER, name),
+                )?,
+            },
+            DatabaseProvider::MongoDB => MemoryStoreEnum::MongoDB {
+                memory_store: MongoDbMemoryStore::new(
+                    root_config.providers.mongodb.conn_url(),
+                    root_config.providers.mongodb.db(),
+                    &format!("{}_{}", config.collection, name),
+                    embedding_model.clone(),
+                    KnowledgeChunk::to_document,
+                )
+                .await?,
+            },
+        };
+        store.check_ndims(embedding_model.ndims()).await?;
+
+        Ok(Self {
+            character: character.to_string(),
+            config: config.clone(),
+            store: Arc::new(Mutex::new(store)),
+        })
+    }
+
+    // returns `None` when the knowledge base is disabled, re-ingests `knowledge.dir` if set
+    pub async fn new(
+        character: &str,
+        root_config: &RootConfig,
+        embedding_model: EM,
+    ) -> Option<Self> {
+        if !root_config.knowledge.enabled {
+            return None;
+        }
+
+        let knowledge = Self::open(character, root_config, embedding_model)
+            .await
+            .expect("Failed to open knowledge base");
+        if let Some(dir) = &root_config.knowledge.dir {
+            match knowledge.ingest(dir).await {
+                Ok(stats) => info!("[KNOWLEDGE] Ingested {}: {:?}", dir, stats),
+                Err(e) => error!("[KNOWLEDGE] Failed to ingest {}: {}", dir, e),
+            }
+        }
+        info!("[KNOWLEDGE] Loaded knowledge base for {}", character);
+
+        Some(knowledge)
+    }
+
+    // lowercase alphanumerics so the name works as a file name and a collection name
+    fn store_name(character: &str) -> String {
+        character
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    }
+
+    // embeds new and changed files under `dir` and drops chunks of files that were removed
+    pub async fn ingest(&self, dir: &str) -> Result<IngestStats, anyhow::Error> {
+        let root = Path::new(dir);
+        let mut files = Vec::new();
+        collect_files(root, &mut files)?;
+        files.sort();
+
+        let mut store = self.store.lock().await;
+
+        // hash of every source already in the store
+        let mut known = HashMap::new();
+        let mut offset = 0;
+        loop {
+            let page = store.documents::<KnowledgeChunk>(offset, 256).await?;
+            if page.is_empty() {
+                break;
+            }
+            offset += page.len();
+            for (_, chunk) in page {
+                known.insert(chunk.source, chunk.hash);
+            }
+        }
+
+        let mut stats = IngestStats::default();
+        for path in files {
+            let source = path.to_string_lossy().to_string();
+            let bytes = fs::read(&path)?;
+            let hash = Sha256::digest(&bytes)
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<String>();
+
+            let previous = known.remove(&source);
+            if previous.as_deref() == Some(hash.as_str()) {
+                stats.unchanged += 1;
+                continue;
+            }
+
+            let text = match extract_text(&path, &bytes) {
+                Ok(text) => text,
+                Err(e) => {
+                    warn!("[KNOWLEDGE] Skipping {}: {}", source, e);
+                    stats.failed += 1;
+                    continue;
+                }
+            };
+            let chunks = chunk_text(&text, self.config.chunk_tokens, self.config.chunk_overlap)
+                .into_iter()
+                .enumerate()
+                .map(|(i, text)| KnowledgeChunk {
+                    id: format!("{}#{}", source, i),
+                    character: self.character.clone(),
+                    source: source.clone(),
+                    hash: hash.clone(),
+                    position: i as u32,
+                    text,
+                })
+                .collect::<Vec<_>>();
+
+            if previous.is_some() {
+                store.delete_where("source", &source).await?;
+            }
+            info!("[KNOWLEDGE] {} -> {} chunks", source, chunks.len());
+            stats.chunks += chunks.len();
+            if !chunks.is_empty() {
+                store.add_many(chunks).await?;
+            }
+            stats.ingested += 1;
+        }
+
+        // whatever is left was ingested from this folder before but no longer exists
+        for source in known.keys() {
+            if Path::new(source).starts_with(root) {
+                store.delete_where("source", source).await?;
+                info!("[KNOWLEDGE] Removed {}", source);
+                stats.removed += 1;
+            }
+        }
+
+        metrics::memory_documents("knowledge", store.count().await?);
+        Ok(stats)
+    }
+
+    pub async fn retrieve(&self, query: &str) -> Vec<KnowledgeChunk> {
+        let store = self.store.lock().await;
+        match store
+            .top_n::<KnowledgeChunk>(query, self.config.top_n)
+            .await
+        {
+            Ok(chunks) => chunks.into_iter().map(|(_, _, chunk)| chunk).collect(),
+            Err(e) => {
+                error!("[KNOWLEDGE] Failed to retrieve knowledge: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    // renders retrieved chunks as a prompt block, empty when there is nothing to add
+    pub fn prompt_block(chunks: &[KnowledgeChunk]) -> String {
+        if chunks.is_empty() {
+            return String::new();
+        }
+        format!(
+            "<knowledge>\nThings you know that may be relevant, only use them when they fit:\n{}\n</knowledge>",
+            chunks
+                .iter()
+                .map(|chunk| format!("[{}]\n{}", chunk.source, chunk.text))
+                .collect::<Vec<_>>()
+                .join("\n\n")
+        )
+    }
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), anyhow::Error> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else if path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| SUPPORTED_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn extract_text(path: &Path, bytes: &[u8]) -> Result<String, anyhow::Error> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    match extension.as_str() {
+        "pdf" => Ok(pdf_extract::extract_text_from_mem(bytes)?),
+        "html" | "htm" => Ok(html2text::from_read(bytes, 1000)),
+        _ => Ok(String::from_utf8_lossy(bytes).to_string()),
+    }
+}
+
+// packs paragraphs into chunks of up to `chunk_tokens`, carrying trailing paragraphs of up to
+// `overlap` tokens into the next chunk so context is not lost at the edges
+fn chunk_text(text: &str, chunk_tokens: usize, overlap: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current: Vec<(String, usize)> = Vec::new();
+    let mut current_tokens = 0;
+
+    let pieces = text
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|paragraph| !paragraph.is_empty())
+        .flat_map(|paragraph| {
+            if count_tokens(paragraph) > chunk_tokens {
+                token_windows(paragraph, chunk_tokens, overlap)
+            } else {
+                vec![paragraph.to_string()]
+            }
+        });
+
+    for piece in pieces {
+        let tokens = count_tokens(&piece);
+        if current_tokens + tokens > chunk_tokens && !current.is_empty() {
+            chunks.push(
+                current
+                    .iter()
+                    .map(|(text, _)| text.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n\n"),
+            );
+
+            let mut carried = Vec::new();
+            let mut carried_tokens = 0;
+            while let Some((text, count)) = current.pop() {
+                if carried_tokens + count > overlap
+                    || carried_tokens + count + tokens > chunk_tokens
+                {
+                    break;
+                }
+                carried_tokens += count;
+                carried.insert(0, (text, count));
+            }
+            current = carried;
+            current_tokens = carried_tokens;
+        }
+        current_tokens += tokens;
+        current.push((piece, tokens));
+    }
+
+    if !current.is_empty() {
+        chunks.push(
+            current
+                .iter()
+                .map(|(text, _)| text.as_str())
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+        );
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paragraph(i: usize) -> String {
+        format!("paragraph {} says something", i)
+    }
+
+    #[test]
+    fn chunk_text_keeps_short_text_whole() {
+        let text = format!("{}\n\n\n\n  {}  \n\n", paragraph(0), paragraph(1));
+        assert_eq!(
+            chunk_text(&text, 100, 0),
+            vec![format!("{}\n\n{}", paragraph(0), paragraph(1))]
+        );
+        assert!(chunk_text(" \n\n ", 100, 0).is_empty());
+    }
+
+    #[test]
+    fn chunk_text_packs_paragraphs_up_to_the_limit() {
+        let paragraphs = (0..4).map(paragraph).collect::<Vec<_>>();
+        let tokens = count_tokens(&paragraphs[0]);
+        let chunks = chunk_text(&paragraphs.join("\n\n"), tokens * 2, 0);
+        assert_eq!(
+            chunks,
+            vec![
+                format!("{}\n\n{}", paragraphs[0], paragraphs[1]),
+                format!("{}\n\n{}", paragraphs[2], paragraphs[3]),
+            ]
+        );
+    }
+
+    #[test]
+    fn chunk_text_carries_trailing_paragraphs_as_overlap() {
+        let paragraphs = (0..4).map(paragraph).collect::<Vec<_>>();
+        let tokens = count_tokens(&paragraphs[0]);
+        let chunks = chunk_text(&paragraphs.join("\n\n"), tokens * 2, tokens);
+        assert_eq!(
+            chunks,
+            vec![
+                format!("{}\n\n{}", paragraphs[0], paragraphs[1]),
+                format!("{}\n\n{}", paragraphs[1], paragraphs[2]),
+                format!("{}\n\n{}", paragraphs[2], paragraphs[3]),
+            ]
+        );
+    }
+
+    #[test]
+    fn chunk_text_splits_long_paragraphs_into_overlapping_windows() {
+        let words = (0..60).map(|i| format!("w{}", i)).collect::<Vec<_>>();
+        let chunks = chunk_text(&words.join(" "), 20, 5);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|chunk| count_tokens(chunk) <= 20));
+        assert!(chunks[0].starts_with("w0 "));
+        assert!(chunks.last().unwrap().ends_with("w59"));
+        // windows cut between tokens, so the overlap is compared as text
+        for pair in chunks.windows(2) {
+            assert!((4..pair[1].len()).any(|n| pair[0].ends_with(&pair[1][..n])));
+        }
+    }
+}
@@ -0,0 +1,531 @@
+use super::{CONFIG_PATH, CONFIG_PATHS};
+use serde::de::{
+    value::{MapAccessDeserializer, MapDeserializer, SeqDeserializer},
+    DeserializeOwned, Deserializer, IntoDeserializer, Visitor,
+};
+use serde_json::{Map, Value};
+use std::{env, fs, path::Path};
+
+// env vars named like this override any config value, `__` separates the keys of its path
+const ENV_PREFIX: &str = "AGENT__";
+
+// env vars the agent read before the `providers` section existed, still honoured as part of the
+// env layer so existing .env files keep working
+const PROVIDER_ENV: &[(&str, &str)] = &[
+    ("providers.anthropic.api_key", "ANTHROPIC_API_KEY"),
+    (
+        "providers.anthropic.completion_model",
+        "ANTHROPIC_COMPLETION_MODEL",
+    ),
+    ("providers.cohere.api_key", "COHERE_API_KEY"),
+    (
+        "providers.cohere.completion_model",
+        "COHERE_COMPLETION_MODEL",
+    ),
+    ("providers.gemini.api_key", "GEMINI_API_KEY"),
+    (
+        "providers.gemini.completion_model",
+        "GEMINI_COMPLETION_MODEL",
+    ),
+    ("providers.openai.api_key", "OPENAI_API_KEY"),
+    (
+        "providers.openai.completion_model",
+        "OPENAI_COMPLETION_MODEL",
+    ),
+    ("providers.perplexity.api_key", "PERPLEXITY_API_KEY"),
+    (
+        "providers.perplexity.completion_model",
+        "PERPLEXITY_COMPLETION_MODEL",
+    ),
+    ("providers.xai.api_key", "XAI_API_KEY"),
+    ("providers.xai.completion_model", "XAI_COMPLETION_MODEL"),
+    ("providers.deepseek.api_key", "DEEPSEEK_API_KEY"),
+    (
+        "providers.deepseek.completion_model",
+        "DEEPSEEK_COMPLETION_MODEL",
+    ),
+    (
+        "providers.openai_compatible.api_key",
+        "OPENAI_COMPATIBLE_API_KEY",
+    ),
+    ("providers.elevenlabs.api_key", "ELEVENLABS_API_KEY"),
+    ("providers.mongodb.conn_url", "MONGODB_CONN_URL"),
+    ("providers.mongodb.db", "MONGODB_DB"),
+    ("providers.mongodb.collection", "MONGODB_COLLECTION"),
+    ("providers.discord.token", "DISCORD_TOKEN"),
+    ("providers.telegram.token", "TELEGRAM_TOKEN"),
+    ("providers.twitter.api_key", "TWITTER_API_KEY"),
+    ("providers.twitter.api_secret", "TWITTER_API_SECRET"),
+    ("providers.twitter.access_token", "TWITTER_ACCESS_TOKEN"),
+    (
+        "providers.twitter.access_token_secret",
+        "TWITTER_ACCESS_TOKEN_SECRET",
+    ),
+    ("providers.truth.username", "TRUTH_USERNAME"),
+    ("providers.truth.password", "TRUTH_PASSWORD"),
+];
+
+// the first config file that exists, in any of the supported formats
+pub fn default_path() -> String {
+    CONFIG_PATHS
+        .iter()
+        .find(|path| Path::new(path).exists())
+        .unwrap_or(&CONFIG_PATH)
+        .to_string()
+}
+
+// the file with `${VAR}` interpolated, then env vars, then `key.path=value` overrides on top.
+// Defaults are left to serde.
+pub fn load(path: &str, overrides: &[String]) -> Result<Value, anyhow::Error> {
+    let mut config = read_file(path)?;
+
+    let mut undefined = Vec::new();
+    interpolate(&mut config, &mut undefined);
+    if !undefined.is_empty() {
+        undefined.sort();
+        undefined.dedup();
+        return Err(anyhow::anyhow!(
+            "{} references undefined environment variables: {}",
+            path,
+            undefined.join(", ")
+        ));
+    }
+
+    for (key, var) in PROVIDER_ENV {
+        if let Ok(value) = env::var(var) {
+            if !value.is_empty() {
+                set(&mut config, key, Value::String(value));
+            }
+        }
+    }
+    // kept as strings, `deserialize` turns them into numbers and booleans where those are expected
+    for (var, value) in env_vars() {
+        if let Some(key) = var.strip_prefix(ENV_PREFIX) {
+            let key = key.to_lowercase().replace("__", ".");
+            set(&mut config, &key, Value::String(value));
+        }
+    }
+
+    for assignment in overrides {
+        let (key, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("--set expects key=value, got {}", assignment))?;
+        set(&mut config, key.trim(), parse_value(value));
+    }
+
+    Ok(config)
+}
+
+// names of the set env vars that override config values
+pub fn env_overrides() -> Vec<String> {
+    let mut vars = PROVIDER_ENV
+        .iter()
+        .filter(|(_, var)| env::var(var).is_ok_and(|value| !value.is_empty()))
+        .map(|(_, var)| var.to_string())
+        .collect::<Vec<_>>();
+    vars.extend(
+        env_vars()
+            .map(|(var, _)| var)
+            .filter(|var| var.starts_with(ENV_PREFIX)),
+    );
+    vars.sort();
+    vars
+}
+
+// a loaded config as `T`, reading strings as numbers or booleans and the other way round
+// wherever `T` expects them, so `AGENT__SERVER__PORT=8080` is a port and
+// `AGENT__PROVIDERS__TRUTH__PASSWORD=12345678` a password
+pub fn deserialize<T: DeserializeOwned>(value: Value) -> Result<T, serde_json::Error> {
+    T::deserialize(Lenient(value))
+}
+
+// env vars with a name or value that is not unicode cannot be config values and are skipped
+fn env_vars() -> impl Iterator<Item = (String, String)> {
+    env::vars_os()
+        .filter_map(|(var, value)| Some((var.into_string().ok()?, value.into_string().ok()?)))
+}
+
+// a config path along with the env vars that can set it, for error messages
+pub fn describe(key: &str) -> String {
+    let mut vars = PROVIDER_ENV
+        .iter()
+        .filter(|(path, _)| *path == key)
+        .map(|(_, var)| var.to_string())
+        .collect::<Vec<_>>();
+    vars.push(format!(
+        "{}{}",
+        ENV_PREFIX,
+        key.replace('.', "__").to_uppercase()
+    ));
+    format!("{} (env {})", key, vars.join(" or "))
+}
+
+fn read_file(path: &str) -> Result<Value, anyhow::Error> {
+    let content =
+        fs::read_to_string(path).map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path, e))?;
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let value = match extension.as_str() {
+        "toml" => toml::from_str(&content)?,
+        "yaml" | "yml" => serde_yaml::from_str(&content)?,
+        _ => serde_json::from_str(&content)?,
+    };
+    Ok(value)
+}
+
+// replaces `${VAR}` and `${VAR:-default}` in every string value
+fn interpolate(value: &mut Value, undefined: &mut Vec<String>) {
+    match value {
+        Value::String(text) => *text = interpolate_str(text, undefined),
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|item| interpolate(item, undefined)),
+        Value::Object(map) => map
+            .values_mut()
+            .for_each(|item| interpolate(item, undefined)),
+        _ => {}
+    }
+}
+
+fn interpolate_str(text: &str, undefined: &mut Vec<String>) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            result.push_str(&rest[start..]);
+            return result;
+        };
+
+        let expression = &rest[start + 2..start + end];
+        let (name, default) = match expression.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (expression, None),
+        };
+        match (env::var(name).ok().filter(|v| !v.is_empty()), default) {
+            (Some(value), _) => result.push_str(&value),
+            (None, Some(default)) => result.push_str(default),
+            (None, None) => undefined.push(name.to_string()),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+// `--set` values are json when they parse (numbers, booleans, arrays, quoted strings), plain
+// string otherwise
+fn parse_value(value: &str) -> Value {
+    serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()))
+}
+
+fn set(config: &mut Value, key: &str, value: Value) {
+    let mut current = config;
+    let mut keys = key.split('.').peekable();
+    while let Some(key) = keys.next() {
+        if !current.is_object() {
+            *current = Value::Object(Map::new());
+        }
+        let map = current.as_object_mut().expect("just made an object");
+        if keys.peek().is_none() {
+            map.insert(key.to_string(), value);
+            return;
+        }
+        current = map
+            .entry(key.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+}
+
+struct Lenient(Value);
+
+impl Lenient {
+    // a number or boolean written as a string
+    fn scalar(self) -> Value {
+        match self.0 {
+            Value::String(text) => match serde_json::from_str(text.trim()) {
+                Ok(value @ (Value::Number(_) | Value::Bool(_))) => value,
+                _ => Value::String(text),
+            },
+            value => value,
+        }
+    }
+
+    // a number or boolean where a string is expected
+    fn text(self) -> Value {
+        match self.0 {
+            Value::Number(number) => Value::String(number.to_string()),
+            Value::Bool(flag) => Value::String(flag.to_string()),
+            value => value,
+        }
+    }
+}
+
+macro_rules! forward_lenient {
+    ($convert:ident: $($method:ident)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+            self.$convert().$method(visitor)
+        }
+    )*};
+}
+
+impl<'de> Deserializer<'de> for Lenient {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Array(items) => {
+                visitor.visit_seq(SeqDeserializer::new(items.into_iter().map(Lenient)))
+            }
+            Value::Object(map) => visitor.visit_map(MapDeserializer::new(
+                map.into_iter().map(|(key, value)| (key, Lenient(value))),
+            )),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Null => visitor.visit_none(),
+            value => visitor.visit_some(Lenient(value)),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Object(map) => visitor.visit_enum(MapAccessDeserializer::new(
+                MapDeserializer::new(map.into_iter().map(|(key, value)| (key, Lenient(value)))),
+            )),
+            value => value.deserialize_enum(name, variants, visitor),
+        }
+    }
+
+    forward_lenient! {
+        scalar: deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_i128 deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_u128 deserialize_f32 deserialize_f64
+    }
+
+    forward_lenient! {
+        text: deserialize_str deserialize_string deserialize_char
+    }
+
+    serde::forward_to_deserialize_any! {
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, serde_json::Error> for Lenient {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    // tests share the process environment, so each one sets env vars only it reads
+
+    fn interpolated(text: &str) -> (String, Vec<String>) {
+        let mut undefined = Vec::new();
+        let text = interpolate_str(text, &mut undefined);
+        (text, undefined)
+    }
+
+    #[test]
+    fn interpolate_replaces_variables_and_defaults() {
+        env::set_var("LAYERS_TEST_HOST", "example.com");
+        env::set_var("LAYERS_TEST_EMPTY", "");
+
+        assert_eq!(
+            interpolated("https://${LAYERS_TEST_HOST}:${LAYERS_TEST_PORT:-8080}/"),
+            ("https://example.com:8080/".to_string(), Vec::new())
+        );
+        // an empty variable counts as unset
+        assert_eq!(
+            interpolated("${LAYERS_TEST_EMPTY:-fallback}"),
+            ("fallback".to_string(), Vec::new())
+        );
+        assert_eq!(
+            interpolated("${LAYERS_TEST_HOST:-unused}"),
+            ("example.com".to_string(), Vec::new())
+        );
+        assert_eq!(
+            interpolated("${LAYERS_TEST_MISSING} and ${LAYERS_TEST_EMPTY}").1,
+            vec!["LAYERS_TEST_MISSING", "LAYERS_TEST_EMPTY"]
+        );
+        assert_eq!(
+            interpolated("no ${closing brace"),
+            ("no ${closing brace".to_string(), Vec::new())
+        );
+    }
+
+    #[test]
+    fn interpolate_walks_arrays_and_objects() {
+        env::set_var("LAYERS_TEST_NESTED", "nested");
+        let mut value = serde_json::json!({
+            "list": ["${LAYERS_TEST_NESTED}", 1],
+            "map": { "key": "${LAYERS_TEST_NESTED}-${LAYERS_TEST_UNSET:-}" },
+        });
+        let mut undefined = Vec::new();
+        interpolate(&mut value, &mut undefined);
+        assert!(undefined.is_empty());
+        assert_eq!(
+            value,
+            serde_json::json!({ "list": ["nested", 1], "map": { "key": "nested-" } })
+        );
+    }
+
+    #[test]
+    fn load_layers_file_env_and_overrides() {
+        let path = env::temp_dir().join(format!("layers_test_{}.json", std::process::id()));
+        fs::write(
+            &path,
+            r#"{ "layers_test": { "port": 1, "name": "file", "kept": true, "url": "${LAYERS_TEST_URL:-default}" } }"#,
+        )
+        .unwrap();
+        env::set_var("AGENT__LAYERS_TEST__PORT", "2");
+        env::set_var("AGENT__LAYERS_TEST__NAME", "env");
+
+        let config = load(
+            path.to_str().unwrap(),
+            &[
+                "layers_test.port=3".to_string(),
+                "layers_test.new.flag=false".to_string(),
+            ],
+        );
+        env::remove_var("AGENT__LAYERS_TEST__PORT");
+        env::remove_var("AGENT__LAYERS_TEST__NAME");
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            config.unwrap()["layers_test"],
+            serde_json::json!({
+                "port": 3,
+                "name": "env",
+                "kept": true,
+                "url": "default",
+                "new": { "flag": false },
+            })
+        );
+    }
+
+    #[test]
+    fn load_reports_undefined_variables() {
+        let path =
+            env::temp_dir().join(format!("layers_test_undefined_{}.json", std::process::id()));
+        fs::write(&path, r#"{ "key": "${LAYERS_TEST_UNDEFINED}" }"#).unwrap();
+        let error = load(path.to_str().unwrap(), &[]).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(error.to_string().contains("LAYERS_TEST_UNDEFINED"));
+    }
+
+    #[test]
+    fn set_replaces_values_that_are_not_objects() {
+        let mut config = serde_json::json!({ "a": 1 });
+        set(&mut config, "a.b.c", Value::Bool(true));
+        assert_eq!(config, serde_json::json!({ "a": { "b": { "c": true } } }));
+    }
+
+    #[test]
+    fn parse_value_reads_json_or_plain_text() {
+        assert_eq!(parse_value("0.2"), serde_json::json!(0.2));
+        assert_eq!(parse_value("[1, 2]"), serde_json::json!([1, 2]));
+        assert_eq!(parse_value("\"42\""), serde_json::json!("42"));
+        assert_eq!(parse_value("mongodb"), serde_json::json!("mongodb"));
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    enum Provider {
+        Local,
+        Remote { port: u16 },
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Settings {
+        port: u16,
+        enabled: bool,
+        rate: f64,
+        password: String,
+        cap: Option<u32>,
+        every: Vec<u32>,
+        provider: Provider,
+        remote: Provider,
+    }
+
+    #[test]
+    fn deserialize_coerces_strings_and_scalars() {
+        let settings: Settings = deserialize(serde_json::json!({
+            "port": "8080",
+            "enabled": " true ",
+            "rate": "0.5",
+            "password": 12345678,
+            "cap": "3",
+            "every": ["30", 60],
+            "provider": "local",
+            "remote": { "remote": { "port": "9000" } },
+        }))
+        .unwrap();
+        assert_eq!(
+            settings,
+            Settings {
+                port: 8080,
+                enabled: true,
+                rate: 0.5,
+                password: "12345678".to_string(),
+                cap: Some(3),
+                every: vec![30, 60],
+                provider: Provider::Local,
+                remote: Provider::Remote { port: 9000 },
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_still_rejects_wrong_types() {
+        let result = deserialize::<Settings>(serde_json::json!({
+            "port": "eighty",
+            "enabled": true,
+            "rate": 0.5,
+            "password": "secret",
+            "cap": null,
+            "every": [],
+            "provider": "local",
+            "remote": "local",
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn describe_names_every_env_var() {
+        assert_eq!(
+            describe("providers.discord.token"),
+            "providers.discord.token (env DISCORD_TOKEN or AGENT__PROVIDERS__DISCORD__TOKEN)"
+        );
+        assert_eq!(
+            describe("server.port"),
+            "server.port (env AGENT__SERVER__PORT)"
+        );
+    }
+}
@@ -0,0 +1,326 @@
+use super::{LogFormat, LoggingConfig};
+use fern::colors::{Color, ColoredLevelConfig};
+use log::{LevelFilter, Metadata, Record};
+use regex::{Captures, Regex};
+use std::{
+    fs::{self, File, OpenOptions},
+    future::Future,
+    io::{self, Write},
+    path::PathBuf,
+    sync::{LazyLock, Mutex, RwLock},
+};
+
+tokio::task_local! {
+    static CORRELATION_ID: String;
+}
+
+// debug on stdout until the config is loaded, as before there was a logging config
+static LOGGER: LazyLock<RwLock<Logger>> = LazyLock::new(|| {
+    RwLock::new(Logger {
+        level: LevelFilter::Debug,
+        modules: Vec::new(),
+        format: LogFormat::Text,
+        stdout: true,
+        file: None,
+        redactor: Some(Redactor::default()),
+    })
+});
+
+// bearer tokens, `key=value` or `"key": "value"` credentials, passwords in urls and api keys
+static PATTERNS: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    [
+        r"(?i)(bearer\s+)[a-z0-9\-._~+/]+=*",
+        r#"(?i)((?:api[_-]?key|access[_-]?token|token|secret|password)["']?\s*[:=]\s*["']?)[^\s"',&}]+"#,
+        r"(://[^:/\s@]+:)[^@\s/]+(@)",
+        r"\bsk-[A-Za-z0-9_\-]{16,}",
+    ]
+    .iter()
+    .map(|pattern| Regex::new(pattern).unwrap())
+    .collect()
+});
+
+struct Logger {
+    level: LevelFilter,
+    // most specific module first
+    modules: Vec<(String, LevelFilter)>,
+    format: LogFormat,
+    stdout: bool,
+    file: Option<(LogFormat, Mutex<RotatingFile>)>,
+    redactor: Option<Redactor>,
+}
+
+impl Logger {
+    fn level(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .find(|(module, _)| {
+                target == module
+                    || target
+                        .strip_prefix(module.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .map_or(self.level, |(_, level)| *level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.level, Ord::max)
+    }
+}
+
+// installs the logger, `configure` applies the logging config once it is loaded
+pub fn init() -> Result<(), log::SetLoggerError> {
+    fern::Dispatch::new()
+        .filter(enabled)
+        .chain(fern::Output::call(write))
+        .apply()?;
+    log::set_max_level(LOGGER.read().unwrap().max_level());
+    Ok(())
+}
+
+// `overrides` come from `--log`, either `LEVEL` or `MODULE=LEVEL`
+pub fn configure(
+    config: &LoggingConfig,
+    overrides: &[String],
+    secrets: Vec<String>,
+) -> Result<(), anyhow::Error> {
+    let mut level = config.level.parse()?;
+    let mut modules = config
+        .modules
+        .iter()
+        .map(|(module, level)| Ok((module.clone(), level.parse()?)))
+        .collect::<Result<Vec<_>, log::ParseLevelError>>()?;
+    for value in overrides {
+        match value.split_once('=') {
+            Some((module, value)) => {
+                let value = value.parse()?;
+                modules.retain(|(m, _)| m != module);
+                modules.push((module.to_string(), value));
+            }
+            None => level = value.parse()?,
+        }
+    }
+    sort(&mut modules);
+
+    let file = match &config.file {
+        Some(file) => Some((
+            file.format,
+            Mutex::new(RotatingFile::open(
+                PathBuf::from(&file.path),
+                file.max_size_mb * 1024 * 1024,
+                file.keep,
+            )?),
+        )),
+        None => None,
+    };
+
+    let mut logger = LOGGER.write().unwrap();
+    *logger = Logger {
+        level,
+        modules,
+        format: config.format,
+        stdout: config.stdout,
+        file,
+        redactor: config.redact.then(|| Redactor::new(secrets)),
+    };
+    log::set_max_level(logger.max_level());
+    Ok(())
+}
+
+// changes a level while the agent runs, the default level without `module`
+pub fn set_level(module: Option<&str>, level: LevelFilter) {
+    let mut logger = LOGGER.write().unwrap();
+    match module {
+        Some(module) => {
+            logger.modules.retain(|(m, _)| m != module);
+            logger.modules.push((module.to_string(), level));
+            sort(&mut logger.modules);
+        }
+        None => logger.level = level,
+    }
+    log::set_max_level(logger.max_level());
+}
+
+// the default level and the level of every module
+pub fn levels() -> (LevelFilter, Vec<(String, LevelFilter)>) {
+    let logger = LOGGER.read().unwrap();
+    (logger.level, logger.modules.clone())
+}
+
+// runs one action, e.g. a post cycle or a discord reply, tagging every line it logs with a new
+// `<action>-<id>` correlation id
+pub async fn correlated<F: Future>(action: &str, future: F) -> F::Output {
+    let id = format!("{}-{:08x}", action, rand::random::<u32>());
+    CORRELATION_ID.scope(id, future).await
+}
+
+fn sort(modules: &mut [(String, LevelFilter)]) {
+    modules.sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
+}
+
+fn enabled(metadata: &Metadata) -> bool {
+    metadata.level() <= LOGGER.read().unwrap().level(metadata.target())
+}
+
+fn write(record: &Record) {
+    let logger = LOGGER.read().unwrap();
+    let message = record.args().to_string();
+    let message = match &logger.redactor {
+        Some(redactor) => redactor.redact(&message),
+        None => message,
+    };
+    let correlation_id = CORRELATION_ID.try_with(|id| id.clone()).ok();
+
+    if logger.stdout {
+        let line = format(logger.format, record, &message, &correlation_id, true);
+        let _ = writeln!(io::stdout().lock(), "{}", line);
+    }
+    if let Some((file_format, file)) = &logger.file {
+        let line = format(*file_format, record, &message, &correlation_id, false);
+        if let Err(e) = file.lock().unwrap().write_line(&line) {
+            eprintln!("[LOGGING] Failed to write log file: {}", e);
+        }
+    }
+}
+
+fn format(
+    format: LogFormat,
+    record: &Record,
+    message: &str,
+    correlation_id: &Option<String>,
+    colored: bool,
+) -> String {
+    match format {
+        LogFormat::Text => {
+            let level = if colored {
+                ColoredLevelConfig::new()
+                    .error(Color::Red)
+                    .warn(Color::Yellow)
+                    .info(Color::Green)
+                    .debug(Color::Blue)
+                    .trace(Color::Magenta)
+                    .color(record.level())
+                    .to_string()
+            } else {
+                record.level().to_string()
+            };
+            let correlation_id = correlation_id
+                .as_ref()
+                .map(|id| format!("({})", id))
+                .unwrap_or_default();
+            format!(
+                "{}[{}]{} {}",
+                chrono::Local::now().format("[%Y-%m-%d %H:%M:%S]"),
+                level,
+                correlation_id,
+                message
+            )
+        }
+        LogFormat::Json => serde_json::json!({
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "level": record.level().as_str(),
+            "target": record.target(),
+            "correlation_id": correlation_id,
+            "message": message,
+        })
+        .to_string(),
+    }
+}
+
+// masks the configured credentials and anything that looks like one
+#[derive(Default)]
+struct Redactor {
+    secrets: Vec<String>,
+}
+
+impl Redactor {
+    fn new(mut secrets: Vec<String>) -> Self {
+        // short values would mask ordinary words
+        secrets.retain(|secret| secret.len() >= 6);
+        // longest first so a secret containing another is masked whole
+        secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+        Self { secrets }
+    }
+
+    fn redact(&self, message: &str) -> String {
+        let mut message = message.to_string();
+        for secret in &self.secrets {
+            if message.contains(secret.as_str()) {
+                message = message.replace(secret.as_str(), "***");
+            }
+        }
+        for pattern in PATTERNS.iter() {
+            message = pattern
+                .replace_all(&message, |captures: &Captures| {
+                    format!(
+                        "{}***{}",
+                        captures.get(1).map_or("", |m| m.as_str()),
+                        captures.get(2).map_or("", |m| m.as_str())
+                    )
+                })
+                .to_string();
+        }
+        message
+    }
+}
+
+struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, max_bytes: u64, keep: usize) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_bytes,
+            keep,
+            file,
+            size,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.size > 0 && self.size + len > self.max_bytes {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.size += len;
+        Ok(())
+    }
+
+    // `<path>.<keep>` is dropped, every other file moves up by one
+    fn rotate(&mut self) -> io::Result<()> {
+        for i in (1..self.keep).rev() {
+            let from = self.rotated(i);
+            if from.exists() {
+                fs::rename(&from, self.rotated(i + 1))?;
+            }
+        }
+        if self.keep > 0 {
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+
+    fn rotated(&self, i: usize) -> PathBuf {
+        PathBuf::from(format!("{}.{}", self.path.display(), i))
+    }
+}
@@ -0,0 +1,121 @@
+use super::MEDIA_FOLDER;
+use log::{error, info};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+// extensions posts can carry
+const TYPES: [(&str, MediaKind, &str); 8] = [
+    ("png", MediaKind::Image, "image/png"),
+    ("jpg", MediaKind::Image, "image/jpeg"),
+    ("jpeg", MediaKind::Image, "image/jpeg"),
+    ("gif", MediaKind::Image, "image/gif"),
+    ("webp", MediaKind::Image, "image/webp"),
+    ("mp3", MediaKind::Audio, "audio/mpeg"),
+    ("wav", MediaKind::Audio, "audio/wav"),
+    ("ogg", MediaKind::Audio, "audio/ogg"),
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaKind {
+    Image,
+    Audio,
+}
+
+// a file attached to a post, generated media is saved to a file first so drafts can keep it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Attachment {
+    pub path: String,
+    pub kind: MediaKind,
+    pub mime: String,
+    #[serde(default)]
+    pub alt_text: Option<String>,
+    // moved to `sent/` next to it once posted so it is attached only once
+    #[serde(default)]
+    pub once: bool,
+}
+
+impl Attachment {
+    // `None` for files of other types
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        let (_, kind, mime) = TYPES.iter().find(|(ext, _, _)| *ext == extension)?;
+        Some(Self {
+            path: path.display().to_string(),
+            kind: *kind,
+            mime: mime.to_string(),
+            alt_text: None,
+            once: false,
+        })
+    }
+
+    // saves generated media to `data/media/generated`
+    pub fn save(data: &[u8], extension: &str) -> Result<Self, anyhow::Error> {
+        let folder = PathBuf::from(MEDIA_FOLDER).join("generated");
+        fs::create_dir_all(&folder)?;
+        let path = folder.join(format!(
+            "{}-{:08x}.{}",
+            chrono::Utc::now().format("%Y%m%d%H%M%S"),
+            rand::random::<u32>(),
+            extension
+        ));
+        fs::write(&path, data)?;
+        Self::from_path(&path).ok_or_else(|| anyhow::anyhow!("unknown media type {}", extension))
+    }
+
+    pub fn file_name(&self) -> String {
+        Path::new(&self.path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    pub fn read(&self) -> Result<Vec<u8>, anyhow::Error> {
+        Ok(fs::read(&self.path)?)
+    }
+
+    // called once the post carrying it went out
+    pub fn sent(&self) {
+        if !self.once {
+            return;
+        }
+        let path = Path::new(&self.path);
+        let Some(folder) = path.parent() else {
+            return;
+        };
+        let sent = folder.join("sent");
+        match fs::create_dir_all(&sent).and_then(|_| fs::rename(path, sent.join(self.file_name())))
+        {
+            Ok(()) => info!("[MEDIA] Moved {} to {}", self.path, sent.display()),
+            Err(e) => error!("[MEDIA] Failed to move {}: {}", self.path, e),
+        }
+    }
+}
+
+// a random file in `folder` of one of `kinds`, `None` when there is none
+pub fn pick(
+    folder: &str,
+    kinds: &[MediaKind],
+    once: bool,
+) -> Result<Option<Attachment>, anyhow::Error> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(folder)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        if let Some(attachment) = Attachment::from_path(&path) {
+            if kinds.contains(&attachment.kind) {
+                files.push(attachment);
+            }
+        }
+    }
+    Ok(files
+        .choose(&mut rand::thread_rng())
+        .cloned()
+        .map(|attachment| Attachment { once, ..attachment }))
+}
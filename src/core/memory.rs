@@ -1,4 +1,13 @@
-use rig::{embeddings::EmbeddingModel, vector_store::VectorStoreError, Embed};
+use super::{
+    retrieval::{self, HybridSearchParams},
+    MemoryScoringConfig,
+};
+use chrono::{DateTime, Utc};
+use rig::{
+    embeddings::{Embedding, EmbeddingModel},
+    vector_store::VectorStoreError,
+    Embed,
+};
 use serde::{Deserialize, Serialize};
 use std::future::Future;
 
@@ -26,7 +35,144 @@ where
         n: usize,
     ) -> impl Future<Output = Result<Vec<(f64, String)>, VectorStoreError>> + Send;
 
+    // nearest documents to an already embedded query among those whose string fields equal the
+    // filter values, filtered before the n nearest are taken
+    fn top_n_where<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        embedding: &Embedding,
+        n: usize,
+        filter: &[(&str, &str)],
+    ) -> impl Future<Output = Result<Vec<(f64, String, T)>, VectorStoreError>> + Send;
+
+    fn keyword_top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> impl Future<Output = Result<Vec<(f64, String, T)>, VectorStoreError>> + Send;
+
+    fn hybrid_top_n<T: for<'a> Deserialize<'a> + Embed + Clone + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        params: &HybridSearchParams,
+    ) -> impl Future<Output = Result<Vec<(f64, String, T)>, VectorStoreError>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            if params.is_vector_only() {
+                return self.top_n::<T>(query, n).await;
+            }
+
+            let pool = params.candidates.max(n);
+            let vector = self.top_n::<T>(query, pool).await?;
+            let keyword = if params.keyword_weight > 0.0 {
+                self.keyword_top_n::<T>(query, pool).await?
+            } else {
+                Vec::new()
+            };
+
+            let mut candidates = retrieval::fuse(vector, keyword, params.keyword_weight);
+            candidates.truncate(pool);
+
+            if let Some(reranker) = &params.reranker {
+                candidates = retrieval::rerank(reranker, query, candidates)?;
+            }
+
+            candidates.truncate(n);
+            Ok(candidates)
+        }
+    }
+
+    // stored documents ordered by id, used to page through a whole store
+    fn documents<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> impl Future<Output = Result<Vec<(String, T)>, anyhow::Error>> + Send;
+
+    // removes the documents whose string `field` equals `value`, returns how many were removed
+    fn delete_where(
+        &mut self,
+        field: &str,
+        value: &str,
+    ) -> impl Future<Output = Result<usize, anyhow::Error>> + Send;
+
     fn clear(&mut self) -> impl Future<Output = Result<(), anyhow::Error>> + Send;
 
     fn count(&self) -> impl Future<Output = Result<usize, anyhow::Error>> + Send;
+
+    // dimensions of the stored embeddings, `None` while the store is empty
+    fn ndims(&self) -> impl Future<Output = Result<Option<usize>, anyhow::Error>> + Send;
+
+    fn check_ndims(&self, expected: usize) -> impl Future<Output = Result<(), anyhow::Error>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            match self.ndims().await? {
+                Some(ndims) if ndims != expected => Err(anyhow::anyhow!(
+                    "Memory store holds {}-dimensional embeddings but the embedding model produces {}",
+                    ndims,
+                    expected
+                )),
+                _ => Ok(()),
+            }
+        }
+    }
+}
+
+// a stored memory that can be weighed by how important and how recent it is
+pub trait Memory {
+    // 1 for mundane, 10 for poignant
+    fn importance(&self) -> u32;
+
+    fn timestamp(&self) -> Option<DateTime<Utc>>;
+}
+
+// neutral importance for memories that were never rated
+pub const DEFAULT_IMPORTANCE: u32 = 5;
+
+// reorders retrieved memories by recency, importance and relevance, each min-max normalized over
+// the candidates so no component dominates because of its scale
+pub fn rank<T: Memory>(
+    candidates: Vec<(f64, String, T)>,
+    scoring: &MemoryScoringConfig,
+) -> Vec<(f64, String, T)> {
+    let now = Utc::now();
+    let recency = candidates
+        .iter()
+        .map(|(_, _, memory)| {
+            let hours = memory.timestamp().map_or(0.0, |timestamp| {
+                (now - timestamp).num_seconds().max(0) as f64 / 3600.0
+            });
+            scoring.decay.powf(hours)
+        })
+        .collect::<Vec<_>>();
+    let importance = candidates
+        .iter()
+        .map(|(_, _, memory)| memory.importance() as f64)
+        .collect::<Vec<_>>();
+    let relevance = candidates
+        .iter()
+        .map(|(score, _, _)| *score)
+        .collect::<Vec<_>>();
+
+    let (recency, importance, relevance) = (
+        retrieval::normalize(&recency),
+        retrieval::normalize(&importance),
+        retrieval::normalize(&relevance),
+    );
+    let mut ranked = candidates
+        .into_iter()
+        .enumerate()
+        .map(|(i, (_, id, memory))| {
+            let score = scoring.recency_weight * recency[i]
+                + scoring.importance_weight * importance[i]
+                + scoring.relevance_weight * relevance[i];
+            (score, id, memory)
+        })
+        .collect::<Vec<_>>();
+    ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+    ranked
 }
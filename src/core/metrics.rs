@@ -0,0 +1,235 @@
+use chrono::{DateTime, Utc};
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, register_int_gauge_vec, Encoder,
+    HistogramVec, IntCounterVec, IntGaugeVec, TextEncoder,
+};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+static COMPLETIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "agent_completions_total",
+        "Completion requests by result",
+        &["status"]
+    )
+    .unwrap()
+});
+
+static COMPLETION_SECONDS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "agent_completion_seconds",
+        "Completion request latency",
+        &["status"],
+        vec![0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0]
+    )
+    .unwrap()
+});
+
+static POSTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("agent_posts_total", "Posts sent by client", &["client"]).unwrap()
+});
+
+static REPLIES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("agent_replies_total", "Replies sent by client", &["client"]).unwrap()
+});
+
+static ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "agent_errors_total",
+        "Errors by client and kind",
+        &["client", "kind"]
+    )
+    .unwrap()
+});
+
+static RESTARTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "agent_client_restarts_total",
+        "Client restarts by the supervisor",
+        &["client"]
+    )
+    .unwrap()
+});
+
+static MEMORY_DOCUMENTS: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "agent_memory_documents",
+        "Documents in each memory store",
+        &["store"]
+    )
+    .unwrap()
+});
+
+static SCHEDULE_LAG_SECONDS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "agent_schedule_lag_seconds",
+        "How late scheduled actions started",
+        &["schedule"],
+        vec![0.1, 1.0, 5.0, 30.0, 60.0, 300.0, 900.0]
+    )
+    .unwrap()
+});
+
+static RATE_LIMIT_REMAINING: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "agent_rate_limit_remaining",
+        "Requests left until the rate limit of each endpoint resets",
+        &["client", "endpoint"]
+    )
+    .unwrap()
+});
+
+static RATE_LIMITED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "agent_rate_limited_total",
+        "Requests delayed or skipped by a rate limit",
+        &["client", "endpoint", "outcome"]
+    )
+    .unwrap()
+});
+
+static POST_BUDGET_REMAINING: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "agent_post_budget_remaining",
+        "Posts left in the monthly budget",
+        &["client"]
+    )
+    .unwrap()
+});
+
+static HEALTH: LazyLock<Mutex<BTreeMap<String, ClientHealth>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientStatus {
+    Running,
+    Restarting,
+    // the supervisor gave up on it
+    Failed,
+    Stopped,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ClientHealth {
+    pub status: ClientStatus,
+    pub since: DateTime<Utc>,
+    pub restarts: u64,
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_action: Option<String>,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<DateTime<Utc>>,
+}
+
+fn update(client: &str, f: impl FnOnce(&mut ClientHealth)) {
+    let mut health = HEALTH.lock().unwrap();
+    let entry = health
+        .entry(client.to_string())
+        .or_insert_with(|| ClientHealth {
+            status: ClientStatus::Running,
+            since: Utc::now(),
+            restarts: 0,
+            last_success: None,
+            last_action: None,
+            last_error: None,
+            last_error_at: None,
+        });
+    f(entry);
+}
+
+pub fn client_status(client: &str, status: ClientStatus) {
+    if status == ClientStatus::Restarting {
+        RESTARTS.with_label_values(&[client]).inc();
+    }
+    update(client, |health| {
+        if status == ClientStatus::Restarting {
+            health.restarts += 1;
+        }
+        if health.status != status {
+            health.status = status;
+            health.since = Utc::now();
+        }
+    });
+}
+
+// a successful action other than a post or reply, e.g. a search
+pub fn succeeded(client: &str, action: &str) {
+    update(client, |health| {
+        health.last_success = Some(Utc::now());
+        health.last_action = Some(action.to_string());
+    });
+}
+
+pub fn posted(client: &str) {
+    POSTS.with_label_values(&[client]).inc();
+    succeeded(client, "post");
+}
+
+pub fn replied(client: &str) {
+    REPLIES.with_label_values(&[client]).inc();
+    succeeded(client, "reply");
+}
+
+// `kind` is a short label like `login`, `post` or `completion`
+pub fn failed(client: &str, kind: &str, error: impl Display) {
+    ERRORS.with_label_values(&[client, kind]).inc();
+    update(client, |health| {
+        health.last_error = Some(format!("{}: {}", kind, error));
+        health.last_error_at = Some(Utc::now());
+    });
+}
+
+pub fn completion(duration: Duration, ok: bool) {
+    let status = if ok { "ok" } else { "error" };
+    COMPLETIONS.with_label_values(&[status]).inc();
+    COMPLETION_SECONDS
+        .with_label_values(&[status])
+        .observe(duration.as_secs_f64());
+}
+
+pub fn memory_documents(store: &str, count: usize) {
+    MEMORY_DOCUMENTS
+        .with_label_values(&[store])
+        .set(count as i64);
+}
+
+pub fn schedule_lag(schedule: &str, lag: Duration) {
+    SCHEDULE_LAG_SECONDS
+        .with_label_values(&[schedule])
+        .observe(lag.as_secs_f64());
+}
+
+pub fn rate_limit(client: &str, endpoint: &str, remaining: u32) {
+    RATE_LIMIT_REMAINING
+        .with_label_values(&[client, endpoint])
+        .set(i64::from(remaining));
+}
+
+// `outcome` is `delayed` or `skipped`
+pub fn rate_limited(client: &str, endpoint: &str, outcome: &str) {
+    RATE_LIMITED
+        .with_label_values(&[client, endpoint, outcome])
+        .inc();
+}
+
+pub fn post_budget(client: &str, remaining: u32) {
+    POST_BUDGET_REMAINING
+        .with_label_values(&[client])
+        .set(i64::from(remaining));
+}
+
+pub fn health() -> BTreeMap<String, ClientHealth> {
+    HEALTH.lock().unwrap().clone()
+}
+
+// every metric in the prometheus text format
+pub fn encode() -> Result<String, anyhow::Error> {
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&prometheus::gather(), &mut buffer)?;
+    Ok(String::from_utf8(buffer)?)
+}
@@ -1,11 +1,35 @@
+pub mod admin;
 pub mod agent;
+pub mod approval;
+pub mod audit;
 pub mod character;
 pub mod config;
 pub mod consts;
+pub mod conversation;
+pub mod history;
+pub mod knowledge;
+pub mod layers;
+pub mod logging;
+pub mod media;
 pub mod memory;
+pub mod metrics;
+pub mod retrieval;
+pub mod scheduler;
+pub mod state;
+pub mod supervisor;
 
 pub use self::agent::Agent;
+pub use self::approval::{ApprovalQueue, Decision, DecisionAction, Draft, DraftKind, DraftStatus};
+pub use self::audit::{AuditLog, AuditQuery, Generation, Publication};
 pub use self::character::*;
 pub use self::config::*;
 pub use self::consts::*;
+pub use self::conversation::ConversationMemory;
+pub use self::history::ConversationBuffer;
+pub use self::knowledge::KnowledgeBase;
+pub use self::media::{Attachment, MediaKind};
 pub use self::memory::MemoryStore;
+pub use self::retrieval::HybridSearchParams;
+pub use self::scheduler::{RunRecorder, Scheduler};
+pub use self::state::{ClientState, Engagement, PostBudget, StateStore};
+pub use self::supervisor::{Shutdown, Supervisor, Tasks};
@@ -0,0 +1,220 @@
+use super::RetrievalConfig;
+use crate::providers::rerank::LocalRerankModel;
+use rig::{embeddings::embed::to_texts, vector_store::VectorStoreError, Embed};
+use std::collections::HashMap;
+
+#[derive(Clone)]
+pub struct HybridSearchParams {
+    pub candidates: usize,
+    pub keyword_weight: f64,
+    pub reranker: Option<LocalRerankModel>,
+}
+
+impl HybridSearchParams {
+    pub fn new(config: &RetrievalConfig, reranker: Option<LocalRerankModel>) -> Self {
+        Self {
+            candidates: config.candidates,
+            keyword_weight: config.keyword_weight,
+            reranker,
+        }
+    }
+
+    pub fn is_vector_only(&self) -> bool {
+        self.keyword_weight <= 0.0 && self.reranker.is_none()
+    }
+}
+
+// BM25
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| token.len() > 1)
+        .map(|token| token.to_lowercase())
+        .collect()
+}
+
+/// Scores every document in `documents` against `query` using Okapi BM25.
+pub fn bm25_scores(query: &str, documents: &[String]) -> Vec<f64> {
+    let query_terms = tokenize(query);
+    let documents = documents
+        .iter()
+        .map(|document| tokenize(document))
+        .collect::<Vec<_>>();
+    if query_terms.is_empty() || documents.is_empty() {
+        return vec![0.0; documents.len()];
+    }
+
+    let doc_count = documents.len() as f64;
+    let avg_len = documents.iter().map(|d| d.len()).sum::<usize>() as f64 / doc_count;
+
+    let mut doc_freq: HashMap<&str, usize> = HashMap::new();
+    for document in &documents {
+        let mut seen = document.iter().map(|t| t.as_str()).collect::<Vec<_>>();
+        seen.sort_unstable();
+        seen.dedup();
+        for term in seen {
+            *doc_freq.entry(term).or_default() += 1;
+        }
+    }
+
+    documents
+        .iter()
+        .map(|document| {
+            let len = document.len() as f64;
+            query_terms
+                .iter()
+                .map(|term| {
+                    let tf = document.iter().filter(|t| *t == term).count() as f64;
+                    if tf == 0.0 {
+                        return 0.0;
+                    }
+                    let df = *doc_freq.get(term.as_str()).unwrap_or(&0) as f64;
+                    let idf = ((doc_count - df + 0.5) / (df + 0.5) + 1.0).ln();
+                    idf * (tf * (BM25_K1 + 1.0))
+                        / (tf + BM25_K1 * (1.0 - BM25_B + BM25_B * len / avg_len.max(1.0)))
+                })
+                .sum()
+        })
+        .collect()
+}
+
+// FUSION
+// min-max normalized to 0..1, all 1.0 when the scores are equal
+pub(crate) fn normalize(scores: &[f64]) -> Vec<f64> {
+    let max = scores.iter().cloned().fold(f64::MIN, f64::max);
+    let min = scores.iter().cloned().fold(f64::MAX, f64::min);
+    if (max - min).abs() < f64::EPSILON {
+        return scores.iter().map(|_| 1.0).collect();
+    }
+    scores.iter().map(|s| (s - min) / (max - min)).collect()
+}
+
+/// Merges vector and keyword candidates by id into one list ordered by a weighted sum of their
+/// min-max normalized scores.
+pub fn fuse<T>(
+    vector: Vec<(f64, String, T)>,
+    keyword: Vec<(f64, String, T)>,
+    keyword_weight: f64,
+) -> Vec<(f64, String, T)> {
+    let keyword_weight = keyword_weight.clamp(0.0, 1.0);
+    let mut fused: HashMap<String, (f64, T)> = HashMap::new();
+
+    let vector_scores = normalize(&vector.iter().map(|(s, _, _)| *s).collect::<Vec<_>>());
+    for ((_, id, doc), score) in vector.into_iter().zip(vector_scores) {
+        fused.insert(id, ((1.0 - keyword_weight) * score, doc));
+    }
+
+    let keyword_scores = normalize(&keyword.iter().map(|(s, _, _)| *s).collect::<Vec<_>>());
+    for ((_, id, doc), score) in keyword.into_iter().zip(keyword_scores) {
+        fused
+            .entry(id)
+            .and_modify(|(total, _)| *total += keyword_weight * score)
+            .or_insert((keyword_weight * score, doc));
+    }
+
+    let mut results = fused
+        .into_iter()
+        .map(|(id, (score, doc))| (score, id, doc))
+        .collect::<Vec<_>>();
+    results.sort_by(|a, b| b.0.total_cmp(&a.0));
+    results
+}
+
+// RERANK
+pub fn rerank<T: Embed + Clone>(
+    reranker: &LocalRerankModel,
+    query: &str,
+    candidates: Vec<(f64, String, T)>,
+) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+    let texts = candidates
+        .iter()
+        .map(|(_, _, doc)| {
+            to_texts(doc.clone())
+                .map(|texts| texts.join(" "))
+                .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let scores = reranker
+        .rerank(query, texts)
+        .map_err(|e| VectorStoreError::DatastoreError(e.into()))?;
+
+    let mut candidates = candidates.into_iter().map(Some).collect::<Vec<_>>();
+    Ok(scores
+        .into_iter()
+        .filter_map(|(idx, score)| {
+            candidates
+                .get_mut(idx)
+                .and_then(Option::take)
+                .map(|(_, id, doc)| (score, id, doc))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hits(hits: &[(f64, &str)]) -> Vec<(f64, String, ())> {
+        hits.iter()
+            .map(|(score, id)| (*score, id.to_string(), ()))
+            .collect()
+    }
+
+    fn ids(results: &[(f64, String, ())]) -> Vec<&str> {
+        results.iter().map(|(_, id, _)| id.as_str()).collect()
+    }
+
+    #[test]
+    fn normalize_scales_to_unit_range() {
+        assert_eq!(normalize(&[2.0, 4.0, 3.0]), vec![0.0, 1.0, 0.5]);
+        assert_eq!(normalize(&[0.7, 0.7]), vec![1.0, 1.0]);
+        assert!(normalize(&[]).is_empty());
+    }
+
+    #[test]
+    fn fuse_merges_documents_found_by_both() {
+        let results = fuse(
+            hits(&[(0.9, "a"), (0.5, "b")]),
+            hits(&[(3.0, "b"), (1.0, "c")]),
+            0.5,
+        );
+        assert_eq!(results.len(), 3);
+        // b is last by vector but first by keyword, a and c only show up once
+        let scores = results
+            .iter()
+            .map(|(score, id, _)| (id.as_str(), *score))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(scores["a"], 0.5);
+        assert_eq!(scores["b"], 0.5);
+        assert_eq!(scores["c"], 0.0);
+    }
+
+    #[test]
+    fn fuse_orders_by_weighted_score() {
+        let vector = hits(&[(0.9, "a"), (0.1, "b")]);
+        let keyword = hits(&[(1.0, "a"), (9.0, "b")]);
+        assert_eq!(ids(&fuse(vector.clone(), keyword.clone(), 0.2)), ["a", "b"]);
+        assert_eq!(ids(&fuse(vector, keyword, 0.8)), ["b", "a"]);
+    }
+
+    #[test]
+    fn fuse_weight_is_clamped() {
+        let vector = hits(&[(0.9, "a"), (0.1, "b")]);
+        let keyword = hits(&[(1.0, "a"), (9.0, "b")]);
+        let results = fuse(vector, keyword, 2.0);
+        assert_eq!(ids(&results), ["b", "a"]);
+        assert_eq!(results[0].0, 1.0);
+        assert_eq!(results[1].0, 0.0);
+    }
+
+    #[test]
+    fn fuse_handles_a_missing_side() {
+        let results = fuse(hits(&[(0.2, "a"), (0.8, "b")]), Vec::new(), 0.3);
+        assert_eq!(ids(&results), ["b", "a"]);
+        assert!((results[0].0 - 0.7).abs() < 1e-9);
+        assert!(fuse::<()>(Vec::new(), Vec::new(), 0.3).is_empty());
+    }
+}
@@ -0,0 +1,445 @@
+use super::{metrics, ScheduleConfig};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use cron::Schedule;
+use log::info;
+use rand::Rng;
+use std::{
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+// steps `next_run` takes past quiet hours, windows and caps before giving up on a schedule
+const MAX_STEPS: usize = 1000;
+
+struct Window {
+    days: Vec<Weekday>,
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+// decides when a recurring action runs next, from a cron expression or a random interval,
+// limited to time windows, outside quiet hours and to a number of runs per day
+pub struct Scheduler {
+    name: String,
+    cron: Option<Schedule>,
+    every: Option<(u32, u32)>,
+    timezone: Tz,
+    windows: Vec<Window>,
+    quiet_hours: Option<(NaiveTime, NaiveTime)>,
+    jitter: u32,
+    daily_cap: Option<u32>,
+    // runs on the current day in `timezone`, shared with the `RunRecorder` of the client
+    runs: Arc<Mutex<(NaiveDate, u32)>>,
+    last_run: Option<DateTime<Utc>>,
+}
+
+// counts a run against the daily cap of a `Scheduler` once the action went through, so skipped
+// and failed runs leave room for another that day
+#[derive(Clone)]
+pub struct RunRecorder {
+    timezone: Tz,
+    runs: Arc<Mutex<(NaiveDate, u32)>>,
+}
+
+impl RunRecorder {
+    pub fn record_run(&self) {
+        let today = Utc::now().with_timezone(&self.timezone).date_naive();
+        let mut runs = self.runs.lock().unwrap();
+        if runs.0 == today {
+            runs.1 += 1;
+        } else {
+            *runs = (today, 1);
+        }
+    }
+}
+
+impl Scheduler {
+    // `name` prefixes the log lines, e.g. `[TWITTER][POST]`
+    pub fn new(name: &str, config: &ScheduleConfig) -> Result<Self, anyhow::Error> {
+        let cron = match &config.cron {
+            Some(expression) => {
+                // the cron crate wants a seconds field
+                let expression = if expression.split_whitespace().count() == 5 {
+                    format!("0 {}", expression)
+                } else {
+                    expression.clone()
+                };
+                Some(
+                    Schedule::from_str(&expression)
+                        .map_err(|e| anyhow::anyhow!("invalid cron {}: {}", expression, e))?,
+                )
+            }
+            None => None,
+        };
+        let every = match config.every.as_deref() {
+            Some([min, max]) if min <= max && *max > 0 => Some((*min, *max)),
+            Some(_) => {
+                return Err(anyhow::anyhow!(
+                    "every must be [min, max] minutes with min <= max and max > 0"
+                ))
+            }
+            None => None,
+        };
+        if cron.is_some() == every.is_some() {
+            return Err(anyhow::anyhow!("exactly one of cron and every must be set"));
+        }
+
+        let timezone = config
+            .timezone
+            .parse::<Tz>()
+            .map_err(|e| anyhow::anyhow!("invalid timezone {}: {}", config.timezone, e))?;
+
+        let mut windows = Vec::new();
+        for window in &config.windows {
+            let days = window
+                .days
+                .iter()
+                .map(|day| {
+                    day.parse::<Weekday>()
+                        .map_err(|_| anyhow::anyhow!("invalid day {}", day))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let (start, end) = (parse_time(&window.start)?, parse_time(&window.end)?);
+            if start >= end {
+                return Err(anyhow::anyhow!(
+                    "window {}-{} must start before it ends",
+                    window.start,
+                    window.end
+                ));
+            }
+            windows.push(Window { days, start, end });
+        }
+
+        let quiet_hours = match &config.quiet_hours {
+            Some(quiet) => Some((parse_time(&quiet.start)?, parse_time(&quiet.end)?)),
+            None => None,
+        };
+
+        if config.daily_cap == Some(0) {
+            return Err(anyhow::anyhow!("daily_cap must be above 0"));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            cron,
+            every,
+            timezone,
+            windows,
+            quiet_hours,
+            jitter: config.jitter,
+            daily_cap: config.daily_cap,
+            runs: Arc::new(Mutex::new((NaiveDate::MIN, 0))),
+            last_run: None,
+        })
+    }
+
+    pub fn recorder(&self) -> RunRecorder {
+        RunRecorder {
+            timezone: self.timezone,
+            runs: self.runs.clone(),
+        }
+    }
+
+    // sleeps until the next allowed run, runs are counted by the `RunRecorder`
+    pub async fn wait(&mut self) {
+        let next = loop {
+            let next = self.next_run(Utc::now());
+            info!(
+                "{} next run at {}",
+                self.name,
+                next.with_timezone(&self.timezone)
+                    .format("%Y-%m-%d %H:%M %Z")
+            );
+            if let Ok(delay) = (next - Utc::now()).to_std() {
+                tokio::time::sleep(delay).await;
+            }
+            // the previous run may have been recorded while sleeping and used up the cap
+            if self.blocked_until(next).is_none() {
+                break next;
+            }
+        };
+        if let Ok(lag) = (Utc::now() - next).to_std() {
+            // `[TWITTER][POST]` is exported as `twitter_post`
+            let label = self
+                .name
+                .trim_matches(|c| c == '[' || c == ']')
+                .replace("][", "_")
+                .to_lowercase();
+            metrics::schedule_lag(&label, lag);
+        }
+        self.last_run = Some(next);
+    }
+
+    // continues from the runs before a restart, so interval schedules do not run right away and
+    // every run today counts against today's cap
+    pub fn resume(&mut self, last_run: DateTime<Utc>, recent_runs: &[DateTime<Utc>]) {
+        let day = |at: &DateTime<Utc>| at.with_timezone(&self.timezone).date_naive();
+        let today = day(&Utc::now());
+        let runs = recent_runs.iter().filter(|run| day(run) == today).count() as u32;
+        // states saved before run times were kept only know the last run
+        let runs = if runs == 0 && day(&last_run) == today {
+            1
+        } else {
+            runs
+        };
+        *self.runs.lock().unwrap() = (today, runs);
+        self.last_run = Some(last_run);
+    }
+
+    pub fn next_run(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        // interval schedules run right away the first time, like the old timer loops
+        let mut candidate = match (&self.cron, self.every, self.last_run) {
+            (Some(_), _, _) => self.next_cron(now),
+            (None, Some(_), None) => now,
+            (None, Some((min, max)), Some(last_run)) => (last_run
+                + Duration::minutes(rand::thread_rng().gen_range(min..=max).into()))
+            .max(now),
+            (None, None, _) => unreachable!("checked in Scheduler::new"),
+        } + self.jitter();
+
+        for _ in 0..MAX_STEPS {
+            let Some(open) = self.blocked_until(candidate) else {
+                return candidate;
+            };
+            candidate = match &self.cron {
+                Some(_) => self.next_cron(open - Duration::seconds(1)),
+                None => open,
+            } + self.jitter();
+        }
+        // windows and quiet hours that never leave room, run once a day rather than never
+        now + Duration::days(1)
+    }
+
+    fn next_cron(&self, after: DateTime<Utc>) -> DateTime<Utc> {
+        self.cron
+            .as_ref()
+            .and_then(|cron| cron.after(&after.with_timezone(&self.timezone)).next())
+            .map(|next| next.with_timezone(&Utc))
+            .unwrap_or_else(|| after + Duration::days(1))
+    }
+
+    fn jitter(&self) -> Duration {
+        Duration::minutes(rand::thread_rng().gen_range(0..=self.jitter).into())
+    }
+
+    // when a run at `at` is not allowed, the earliest time it could be
+    fn blocked_until(&self, at: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let local = at.with_timezone(&self.timezone);
+        let (date, time) = (local.date_naive(), local.time());
+
+        if let Some(cap) = self.daily_cap {
+            let runs = *self.runs.lock().unwrap();
+            if runs.0 == date && runs.1 >= cap {
+                return Some(self.at(date.succ_opt()?, NaiveTime::MIN));
+            }
+        }
+
+        if let Some((start, end)) = self.quiet_hours {
+            let quiet = if start <= end {
+                start <= time && time < end
+            } else {
+                time >= start || time < end
+            };
+            if quiet {
+                let date = if start > end && time >= start {
+                    date.succ_opt()?
+                } else {
+                    date
+                };
+                return Some(self.at(date, end));
+            }
+        }
+
+        if !self.windows.is_empty() {
+            let inside = self.windows.iter().any(|window| {
+                window.applies(date.weekday()) && window.start <= time && time < window.end
+            });
+            if !inside {
+                // the next window start within a week
+                return (0..8)
+                    .filter_map(|offset| date.checked_add_signed(Duration::days(offset)))
+                    .flat_map(|day| {
+                        self.windows
+                            .iter()
+                            .filter(move |window| window.applies(day.weekday()))
+                            .map(move |window| self.at(day, window.start))
+                    })
+                    .filter(|start| *start > at)
+                    .min();
+            }
+        }
+
+        None
+    }
+
+    // local date and time in `timezone`, moved past gaps left by daylight saving changes
+    fn at(&self, date: NaiveDate, time: NaiveTime) -> DateTime<Utc> {
+        let local = date.and_time(time);
+        self.timezone
+            .from_local_datetime(&local)
+            .earliest()
+            .or_else(|| {
+                self.timezone
+                    .from_local_datetime(&(local + Duration::hours(1)))
+                    .earliest()
+            })
+            .map(|at| at.with_timezone(&Utc))
+            .unwrap_or_else(|| Utc.from_utc_datetime(&local))
+    }
+}
+
+impl Window {
+    fn applies(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+}
+
+fn parse_time(time: &str) -> Result<NaiveTime, anyhow::Error> {
+    NaiveTime::parse_from_str(time, "%H:%M")
+        .map_err(|_| anyhow::anyhow!("invalid time {}, expected HH:MM", time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduler(config: serde_json::Value) -> Scheduler {
+        let config = serde_json::from_value::<ScheduleConfig>(config).unwrap();
+        Scheduler::new("[TEST]", &config).unwrap()
+    }
+
+    fn utc(at: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(at)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn cron_runs_at_the_next_match() {
+        let scheduler = scheduler(serde_json::json!({ "cron": "0 9 * * *" }));
+        assert_eq!(
+            scheduler.next_run(utc("2025-01-01T10:00:00Z")),
+            utc("2025-01-02T09:00:00Z")
+        );
+    }
+
+    #[test]
+    fn cron_follows_daylight_saving_in_its_timezone() {
+        let scheduler = scheduler(serde_json::json!({
+            "cron": "0 9 * * *",
+            "timezone": "Europe/Berlin",
+        }));
+        // 9:00 is 8:00 utc in winter and 7:00 utc once summer time started on the 30th
+        assert_eq!(
+            scheduler.next_run(utc("2025-03-28T12:00:00Z")),
+            utc("2025-03-29T08:00:00Z")
+        );
+        assert_eq!(
+            scheduler.next_run(utc("2025-03-29T12:00:00Z")),
+            utc("2025-03-30T07:00:00Z")
+        );
+    }
+
+    #[test]
+    fn interval_runs_right_away_the_first_time() {
+        let scheduler = scheduler(serde_json::json!({ "every": [5, 5] }));
+        let now = utc("2025-01-01T10:00:00Z");
+        assert_eq!(scheduler.next_run(now), now);
+    }
+
+    #[test]
+    fn quiet_hours_wrapping_past_midnight_end_the_next_morning() {
+        let scheduler = scheduler(serde_json::json!({
+            "every": [5, 5],
+            "quiet_hours": { "start": "22:00", "end": "06:00" },
+        }));
+        assert_eq!(
+            scheduler.next_run(utc("2025-01-01T23:00:00Z")),
+            utc("2025-01-02T06:00:00Z")
+        );
+        assert_eq!(
+            scheduler.next_run(utc("2025-01-02T03:00:00Z")),
+            utc("2025-01-02T06:00:00Z")
+        );
+        let outside = utc("2025-01-02T12:00:00Z");
+        assert_eq!(scheduler.next_run(outside), outside);
+    }
+
+    #[test]
+    fn quiet_hours_ending_in_a_daylight_saving_gap_end_after_it() {
+        let scheduler = scheduler(serde_json::json!({
+            "every": [5, 5],
+            "timezone": "Europe/Berlin",
+            "quiet_hours": { "start": "01:00", "end": "02:30" },
+        }));
+        // 02:30 does not exist on the 30th, clocks go from 02:00 to 03:00
+        assert_eq!(
+            scheduler.next_run(utc("2025-03-30T00:15:00Z")),
+            utc("2025-03-30T01:30:00Z")
+        );
+    }
+
+    #[test]
+    fn windows_move_runs_to_the_next_open_day() {
+        let scheduler = scheduler(serde_json::json!({
+            "every": [5, 5],
+            "windows": [{ "days": ["mon", "tue", "wed", "thu", "fri"], "start": "09:00", "end": "17:00" }],
+        }));
+        // a saturday
+        assert_eq!(
+            scheduler.next_run(utc("2025-01-04T12:00:00Z")),
+            utc("2025-01-06T09:00:00Z")
+        );
+        // a wednesday evening
+        assert_eq!(
+            scheduler.next_run(utc("2025-01-08T18:00:00Z")),
+            utc("2025-01-09T09:00:00Z")
+        );
+    }
+
+    #[test]
+    fn daily_cap_counts_runs_from_before_a_restart() {
+        let mut scheduler = scheduler(serde_json::json!({
+            "every": [1, 1],
+            "daily_cap": 2,
+        }));
+        let now = Utc::now();
+        scheduler.resume(now, &[now - Duration::seconds(1), now]);
+        let tomorrow = now
+            .date_naive()
+            .succ_opt()
+            .unwrap()
+            .and_time(NaiveTime::MIN);
+        assert_eq!(scheduler.next_run(now), Utc.from_utc_datetime(&tomorrow));
+    }
+
+    #[test]
+    fn daily_cap_counts_only_recorded_runs() {
+        let scheduler = scheduler(serde_json::json!({
+            "every": [1, 1],
+            "daily_cap": 1,
+        }));
+        let now = Utc::now();
+        assert_eq!(scheduler.next_run(now), now);
+
+        scheduler.recorder().record_run();
+        let tomorrow = now
+            .date_naive()
+            .succ_opt()
+            .unwrap()
+            .and_time(NaiveTime::MIN);
+        assert_eq!(scheduler.next_run(now), Utc.from_utc_datetime(&tomorrow));
+    }
+
+    #[test]
+    fn daily_cap_allows_runs_below_it() {
+        let mut scheduler = scheduler(serde_json::json!({
+            "every": [1, 1],
+            "daily_cap": 2,
+        }));
+        let now = Utc::now();
+        scheduler.resume(now, &[now]);
+        assert_eq!(scheduler.next_run(now), now + Duration::minutes(1));
+    }
+}
@@ -0,0 +1,236 @@
+use super::{
+    Config, ConversationBuffer, DatabaseProvider, DraftKind, STATE_COLLECTION, STATE_FOLDER,
+};
+use chrono::{DateTime, Utc};
+use mongodb::{
+    bson::{self, doc, Document},
+    Client as MongoClient, Collection,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, path::PathBuf};
+
+// what an autonomous client needs to pick up where it stopped
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ClientState {
+    // e.g. the id of the newest mention already answered
+    #[serde(default)]
+    pub cursors: HashMap<String, String>,
+    // mentions past the cursor that were already dealt with, while older ones still wait
+    #[serde(default)]
+    pub handled_mentions: Vec<String>,
+    #[serde(default)]
+    pub post_history: Option<ConversationBuffer>,
+    // last successful run of each scheduled action
+    #[serde(default)]
+    pub last_runs: HashMap<String, DateTime<Utc>>,
+    // runs of each scheduled action over the last two days, so daily caps hold across restarts in
+    // any schedule timezone
+    #[serde(default)]
+    pub recent_runs: HashMap<String, Vec<DateTime<Utc>>>,
+    // recent likes, reposts and quotes, for their caps and so no post is engaged with twice
+    #[serde(default)]
+    pub engagements: Vec<Engagement>,
+    // posts the platform counted against its monthly budget
+    #[serde(default)]
+    pub post_budget: Option<PostBudget>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PostBudget {
+    // e.g. `2025-01`, in utc
+    pub month: String,
+    pub posts: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Engagement {
+    pub kind: DraftKind,
+    pub post_id: String,
+    pub at: DateTime<Utc>,
+}
+
+// one state per client, in `data/state/<client>.json` or a document of the mongodb state
+// collection, following `db`
+#[derive(Clone)]
+pub enum StateStore {
+    Local {
+        path: PathBuf,
+    },
+    MongoDB {
+        collection: Collection<Document>,
+        client: String,
+    },
+}
+
+impl StateStore {
+    pub async fn new(client: &str, root_config: &Config) -> Result<Self, anyhow::Error> {
+        match root_config.db {
+            DatabaseProvider::Local => Ok(Self::Local {
+                path: PathBuf::from(format!("{}/{}.json", STATE_FOLDER, client)),
+            }),
+            DatabaseProvider::MongoDB => {
+                let mongodb = &root_config.providers.mongodb;
+                let collection = MongoClient::with_uri_str(mongodb.conn_url())
+                    .await?
+                    .database(mongodb.db())
+                    .collection(STATE_COLLECTION);
+                Ok(Self::MongoDB {
+                    collection,
+                    client: client.to_string(),
+                })
+            }
+        }
+    }
+
+    // an empty state when nothing was saved yet
+    pub async fn load(&self) -> Result<ClientState, anyhow::Error> {
+        match self {
+            Self::Local { path } => {
+                if !path.exists() {
+                    return Ok(ClientState::default());
+                }
+                Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+            }
+            Self::MongoDB { collection, client } => {
+                match collection.find_one(doc! { "_id": client }).await? {
+                    Some(mut document) => {
+                        document.remove("_id");
+                        Ok(bson::from_document(document)?)
+                    }
+                    None => Ok(ClientState::default()),
+                }
+            }
+        }
+    }
+
+    pub async fn save(&self, state: &ClientState) -> Result<(), anyhow::Error> {
+        match self {
+            Self::Local { path } => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                // written to a temporary file first so a crash never leaves half a state behind
+                let tmp = path.with_extension("json.tmp");
+                fs::write(&tmp, serde_json::to_string_pretty(state)?)?;
+                fs::rename(&tmp, path)?;
+            }
+            Self::MongoDB { collection, client } => {
+                let mut document = bson::to_document(state)?;
+                document.insert("_id", client.as_str());
+                collection
+                    .replace_one(doc! { "_id": client }, document)
+                    .upsert(true)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> ClientState {
+        let at = DateTime::parse_from_rfc3339("2025-01-02T03:04:05Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let mut post_history = ConversationBuffer::new(100);
+        post_history.push("user", "topic");
+        ClientState {
+            cursors: HashMap::from([("mention".to_string(), "42".to_string())]),
+            handled_mentions: vec!["43".to_string()],
+            post_history: Some(post_history),
+            last_runs: HashMap::from([("post".to_string(), at)]),
+            recent_runs: HashMap::from([("post".to_string(), vec![at])]),
+            engagements: vec![Engagement {
+                kind: DraftKind::Like,
+                post_id: "44".to_string(),
+                at,
+            }],
+            post_budget: Some(PostBudget {
+                month: "2025-01".to_string(),
+                posts: 7,
+            }),
+        }
+    }
+
+    fn assert_same(loaded: &ClientState, saved: &ClientState) {
+        assert_eq!(loaded.cursors, saved.cursors);
+        assert_eq!(loaded.handled_mentions, saved.handled_mentions);
+        let history = |state: &ClientState| {
+            state.post_history.as_ref().map(|history| {
+                history
+                    .messages()
+                    .into_iter()
+                    .map(|message| (message.role, message.content))
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(history(loaded), history(saved));
+        assert_eq!(loaded.last_runs, saved.last_runs);
+        assert_eq!(loaded.recent_runs, saved.recent_runs);
+        assert_eq!(loaded.engagements.len(), 1);
+        assert_eq!(loaded.engagements[0].kind, DraftKind::Like);
+        assert_eq!(loaded.engagements[0].at, saved.engagements[0].at);
+        assert_eq!(loaded.post_budget, saved.post_budget);
+    }
+
+    fn local(name: &str) -> (PathBuf, StateStore) {
+        let dir = std::env::temp_dir().join(format!("state_test_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let store = StateStore::Local {
+            path: dir.join("nested").join("client.json"),
+        };
+        (dir, store)
+    }
+
+    #[tokio::test]
+    async fn local_state_round_trips() {
+        let (dir, store) = local("round_trip");
+        assert!(store.load().await.unwrap().cursors.is_empty());
+
+        let saved = state();
+        store.save(&saved).await.unwrap();
+        assert_same(&store.load().await.unwrap(), &saved);
+
+        let mut newer = state();
+        newer.handled_mentions.clear();
+        store.save(&newer).await.unwrap();
+        assert!(store.load().await.unwrap().handled_mentions.is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn local_save_replaces_the_file_in_one_step() {
+        let (dir, store) = local("atomic");
+        let StateStore::Local { path } = &store else {
+            unreachable!()
+        };
+        store.save(&state()).await.unwrap();
+        // what a crash between writing and renaming would leave behind
+        fs::write(path.with_extension("json.tmp"), "{ half a sta").unwrap();
+        assert_same(&store.load().await.unwrap(), &state());
+
+        store.save(&state()).await.unwrap();
+        assert!(!path.with_extension("json.tmp").exists());
+        assert_same(&store.load().await.unwrap(), &state());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn state_round_trips_through_bson() {
+        let saved = state();
+        let document = bson::to_document(&saved).unwrap();
+        assert_same(&bson::from_document(document).unwrap(), &saved);
+    }
+
+    #[test]
+    fn older_states_load_with_defaults() {
+        let loaded: ClientState =
+            serde_json::from_str(r#"{ "cursors": { "mention": "1" } }"#).unwrap();
+        assert_eq!(loaded.cursors["mention"], "1");
+        assert!(loaded.recent_runs.is_empty());
+        assert!(loaded.post_budget.is_none());
+    }
+}
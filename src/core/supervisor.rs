@@ -0,0 +1,281 @@
+use super::{
+    metrics::{self, ClientStatus},
+    SupervisorConfig,
+};
+use log::{error, info, warn};
+use std::{future::Future, time::Duration};
+use tokio::{
+    sync::watch,
+    task::{JoinHandle, JoinSet},
+    time::{sleep, timeout, Instant},
+};
+
+// handed to every client, resolves once the agent is asked to stop
+#[derive(Clone)]
+pub struct Shutdown(watch::Receiver<bool>);
+
+impl Shutdown {
+    pub fn is_triggered(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    pub async fn wait(&self) {
+        let mut receiver = self.0.clone();
+        // a dropped sender means the supervisor is gone, which is a shutdown as well
+        let _ = receiver.wait_for(|triggered| *triggered).await;
+    }
+}
+
+// background tasks of a client, aborted once it is dropped so a client that panics leaves none
+// running behind for its restart to add to
+#[derive(Default)]
+pub struct Tasks(Vec<JoinHandle<()>>);
+
+impl Tasks {
+    pub fn push(&mut self, task: JoinHandle<()>) {
+        self.0.push(task);
+    }
+}
+
+impl Drop for Tasks {
+    fn drop(&mut self) {
+        for task in &self.0 {
+            task.abort();
+        }
+    }
+}
+
+// runs every client, restarts the ones that fail with exponential backoff and stops them all on
+// SIGINT or SIGTERM
+pub struct Supervisor {
+    config: SupervisorConfig,
+    sender: watch::Sender<bool>,
+    tasks: JoinSet<()>,
+}
+
+impl Supervisor {
+    pub fn new(config: &SupervisorConfig) -> Self {
+        Self {
+            config: config.clone(),
+            sender: watch::channel(false).0,
+            tasks: JoinSet::new(),
+        }
+    }
+
+    pub fn shutdown(&self) -> Shutdown {
+        Shutdown(self.sender.subscribe())
+    }
+
+    // `run` starts the client once, a client that returns or panics before shutdown failed
+    pub fn spawn<F, Fut>(&mut self, name: &'static str, mut run: F)
+    where
+        F: FnMut(Shutdown) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let config = self.config.clone();
+        let shutdown = self.shutdown();
+        self.tasks.spawn(async move {
+            let mut restarts = 0;
+            let mut backoff = Duration::from_secs(config.backoff_secs);
+            loop {
+                let started = Instant::now();
+                metrics::client_status(name, ClientStatus::Running);
+                let result = tokio::spawn(run(shutdown.clone())).await;
+                if shutdown.is_triggered() {
+                    info!("[SUPERVISOR] {} stopped", name);
+                    metrics::client_status(name, ClientStatus::Stopped);
+                    return;
+                }
+                match result {
+                    Ok(()) => {
+                        error!("[SUPERVISOR] {} exited", name);
+                        metrics::failed(name, "exit", "client exited");
+                    }
+                    Err(e) => {
+                        error!("[SUPERVISOR] {} crashed: {}", name, e);
+                        metrics::failed(name, "crash", e);
+                    }
+                }
+
+                // a client that ran for a while before failing starts over with a clean slate
+                if started.elapsed() >= Duration::from_secs(config.reset_after_secs) {
+                    restarts = 0;
+                    backoff = Duration::from_secs(config.backoff_secs);
+                }
+                if restarts >= config.max_restarts {
+                    error!(
+                        "[SUPERVISOR] {} failed {} times in a row, giving up",
+                        name,
+                        restarts + 1
+                    );
+                    metrics::client_status(name, ClientStatus::Failed);
+                    return;
+                }
+                restarts += 1;
+                metrics::client_status(name, ClientStatus::Restarting);
+
+                warn!(
+                    "[SUPERVISOR] Restarting {} in {}s ({}/{})",
+                    name,
+                    backoff.as_secs(),
+                    restarts,
+                    config.max_restarts
+                );
+                tokio::select! {
+                    _ = sleep(backoff) => {}
+                    _ = shutdown.wait() => {
+                        metrics::client_status(name, ClientStatus::Stopped);
+                        return;
+                    }
+                }
+                backoff = (backoff * 2).min(Duration::from_secs(config.max_backoff_secs));
+            }
+        });
+    }
+
+    // returns once every client gave up, or after a signal once the clients drained or the
+    // shutdown timeout passed
+    pub async fn run(mut self) {
+        tokio::select! {
+            _ = signal() => {}
+            _ = async { while self.tasks.join_next().await.is_some() {} } => {
+                warn!("[SUPERVISOR] No clients left running");
+                return;
+            }
+        }
+
+        info!(
+            "[SUPERVISOR] Shutting down, waiting up to {}s for clients to finish...",
+            self.config.shutdown_timeout_secs
+        );
+        let _ = self.sender.send(true);
+
+        let drain = async { while self.tasks.join_next().await.is_some() {} };
+        if timeout(
+            Duration::from_secs(self.config.shutdown_timeout_secs),
+            drain,
+        )
+        .await
+        .is_err()
+        {
+            warn!(
+                "[SUPERVISOR] {} clients did not finish in time, stopping them",
+                self.tasks.len()
+            );
+            self.tasks.shutdown().await;
+        }
+        info!("[SUPERVISOR] Shut down");
+    }
+}
+
+async fn signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => info!("[SUPERVISOR] Received SIGINT"),
+            _ = terminate.recv() => info!("[SUPERVISOR] Received SIGTERM"),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        info!("[SUPERVISOR] Received Ctrl-C");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn config(max_restarts: u32) -> SupervisorConfig {
+        SupervisorConfig {
+            max_restarts,
+            backoff_secs: 1,
+            max_backoff_secs: 3,
+            reset_after_secs: 60,
+            shutdown_timeout_secs: 5,
+        }
+    }
+
+    // seconds since the first start at which each run started
+    fn starts(runs: &Mutex<Vec<Instant>>) -> Vec<u64> {
+        let runs = runs.lock().unwrap();
+        runs.iter()
+            .map(|start| (*start - runs[0]).as_secs())
+            .collect()
+    }
+
+    // spawns a client that records its starts and runs `run_secs(run)` seconds before failing
+    fn spawn_failing(
+        supervisor: &mut Supervisor,
+        run_secs: fn(usize) -> u64,
+    ) -> Arc<Mutex<Vec<Instant>>> {
+        let runs = Arc::new(Mutex::new(Vec::new()));
+        let recorded = runs.clone();
+        supervisor.spawn("test", move |_| {
+            let runs = recorded.clone();
+            async move {
+                let run = {
+                    let mut runs = runs.lock().unwrap();
+                    runs.push(Instant::now());
+                    runs.len() - 1
+                };
+                sleep(Duration::from_secs(run_secs(run))).await;
+            }
+        });
+        runs
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn restarts_with_growing_backoff_until_max_restarts() {
+        let mut supervisor = Supervisor::new(&config(3));
+        let runs = spawn_failing(&mut supervisor, |_| 0);
+        supervisor.run().await;
+        // backoff doubles from 1s and is capped at 3s
+        assert_eq!(starts(&runs), vec![0, 1, 3, 6]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn long_runs_reset_the_restart_count() {
+        let mut supervisor = Supervisor::new(&config(1));
+        let runs = spawn_failing(&mut supervisor, |run| if run < 2 { 100 } else { 0 });
+        supervisor.run().await;
+        // each run of 100s starts over at the first backoff, the short one after them gives up
+        assert_eq!(starts(&runs), vec![0, 101, 202]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown_stops_clients_without_restarting_them() {
+        let mut supervisor = Supervisor::new(&config(3));
+        let runs = Arc::new(Mutex::new(Vec::new()));
+        let recorded = runs.clone();
+        supervisor.spawn("test", move |shutdown| {
+            let runs = recorded.clone();
+            async move {
+                runs.lock().unwrap().push(Instant::now());
+                shutdown.wait().await;
+            }
+        });
+        sleep(Duration::from_secs(10)).await;
+        supervisor.sender.send(true).unwrap();
+        while supervisor.tasks.join_next().await.is_some() {}
+        assert_eq!(runs.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown_interrupts_a_backoff() {
+        let mut supervisor = Supervisor::new(&SupervisorConfig {
+            backoff_secs: 1000,
+            max_backoff_secs: 1000,
+            ..config(3)
+        });
+        let runs = spawn_failing(&mut supervisor, |_| 0);
+        sleep(Duration::from_secs(10)).await;
+        supervisor.sender.send(true).unwrap();
+        while supervisor.tasks.join_next().await.is_some() {}
+        assert_eq!(runs.lock().unwrap().len(), 1);
+    }
+}
@@ -2,7 +2,10 @@ use crate::{
     core::MemoryStore,
     dbs::{LocalMemoryStore, MongoDbMemoryStore},
 };
-use rig::{embeddings::EmbeddingModel, Embed};
+use rig::{
+    embeddings::{Embedding, EmbeddingModel},
+    Embed,
+};
 use serde::Serialize;
 
 #[derive(Clone)]
@@ -60,6 +63,47 @@ where
         }
     }
 
+    async fn top_n_where<T: for<'a> serde::Deserialize<'a> + Send>(
+        &self,
+        embedding: &Embedding,
+        n: usize,
+        filter: &[(&str, &str)],
+    ) -> Result<Vec<(f64, String, T)>, rig::vector_store::VectorStoreError> {
+        match self {
+            Self::Local { memory_store } => memory_store.top_n_where(embedding, n, filter).await,
+            Self::MongoDB { memory_store } => memory_store.top_n_where(embedding, n, filter).await,
+        }
+    }
+
+    async fn keyword_top_n<T: for<'a> serde::Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, rig::vector_store::VectorStoreError> {
+        match self {
+            Self::Local { memory_store } => memory_store.keyword_top_n(query, n).await,
+            Self::MongoDB { memory_store } => memory_store.keyword_top_n(query, n).await,
+        }
+    }
+
+    async fn documents<T: for<'a> serde::Deserialize<'a> + Send>(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<(String, T)>, anyhow::Error> {
+        match self {
+            Self::Local { memory_store } => memory_store.documents(offset, limit).await,
+            Self::MongoDB { memory_store } => memory_store.documents(offset, limit).await,
+        }
+    }
+
+    async fn delete_where(&mut self, field: &str, value: &str) -> Result<usize, anyhow::Error> {
+        match self {
+            Self::Local { memory_store } => memory_store.delete_where(field, value).await,
+            Self::MongoDB { memory_store } => memory_store.delete_where(field, value).await,
+        }
+    }
+
     async fn clear(&mut self) -> Result<(), anyhow::Error> {
         match self {
             Self::Local { memory_store } => memory_store.clear().await,
@@ -73,4 +117,11 @@ where
             Self::MongoDB { memory_store } => memory_store.count().await,
         }
     }
+
+    async fn ndims(&self) -> Result<Option<usize>, anyhow::Error> {
+        match self {
+            Self::Local { memory_store } => memory_store.ndims().await,
+            Self::MongoDB { memory_store } => memory_store.ndims().await,
+        }
+    }
 }
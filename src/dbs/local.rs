@@ -1,10 +1,18 @@
-use crate::core::memory::MemoryStore as CoreMemoryStore;
+use crate::core::{memory::MemoryStore as CoreMemoryStore, retrieval};
+use log::warn;
 use rig::{
-    embeddings::{EmbeddingModel, EmbeddingsBuilder},
+    embeddings::{
+        distance::VectorDistance, embed::to_texts, Embedding, EmbeddingModel, EmbeddingsBuilder,
+    },
     vector_store::{in_memory_store::InMemoryVectorStore, VectorStoreError, VectorStoreIndexDyn},
-    Embed,
+    Embed, OneOrMany,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    path::Path,
 };
-use serde::{Deserialize, Serialize};
 
 #[derive(Clone)]
 pub struct MemoryStore<D, EM>
@@ -14,6 +22,16 @@ where
 {
     store: InMemoryVectorStore<D>,
     embedding_model: EM,
+    // jsonl file new documents are appended to, rewritten as a whole only when documents are removed
+    snapshot: Option<String>,
+}
+
+// one line of a snapshot file
+#[derive(Serialize, Deserialize)]
+struct SnapshotEntry<D> {
+    id: String,
+    document: D,
+    embeddings: Vec<Embedding>,
 }
 
 impl<D, EM> MemoryStore<D, EM>
@@ -25,7 +43,113 @@ where
         Self {
             store: InMemoryVectorStore::from_documents(vec![]),
             embedding_model,
+            snapshot: None,
+        }
+    }
+
+    // stores embedded documents under the ids `add_documents` would give them and appends them to
+    // the snapshot
+    fn insert(&mut self, embedded: Vec<(D, OneOrMany<Embedding>)>) -> Result<(), anyhow::Error> {
+        let first = self.store.len();
+        let documents = embedded
+            .into_iter()
+            .enumerate()
+            .map(|(i, (document, embeddings))| (format!("doc{}", first + i), document, embeddings))
+            .collect::<Vec<_>>();
+
+        if let Some(path) = &self.snapshot {
+            if let Some(parent) = Path::new(path).parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut lines = String::new();
+            for (id, document, embeddings) in &documents {
+                let entry = SnapshotEntry {
+                    id: id.clone(),
+                    document,
+                    embeddings: embeddings.iter().cloned().collect(),
+                };
+                lines.push_str(&serde_json::to_string(&entry)?);
+                lines.push('\n');
+            }
+            // one write so an interrupted append cuts off at most the last line
+            fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?
+                .write_all(lines.as_bytes())?;
+        }
+
+        self.store.add_documents_with_ids(documents);
+        Ok(())
+    }
+
+    fn save_snapshot(&self) -> Result<(), anyhow::Error> {
+        let Some(path) = &self.snapshot else {
+            return Ok(());
+        };
+        if let Some(parent) = Path::new(path).parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // write to a temp file first so a crash never leaves a half written snapshot
+        let tmp_path = format!("{path}.tmp");
+        let mut file = fs::File::create(&tmp_path)?;
+        for (id, (document, embeddings)) in self.store.iter() {
+            let entry = SnapshotEntry {
+                id: id.clone(),
+                document,
+                embeddings: embeddings.iter().cloned().collect(),
+            };
+            writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        }
+        file.flush()?;
+        fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+}
+
+impl<D, EM> MemoryStore<D, EM>
+where
+    D: Embed + Serialize + DeserializeOwned + Send + Sync + Eq + Clone,
+    EM: EmbeddingModel,
+{
+    // loads the snapshot at `path` if there is one and keeps it up to date from then on
+    pub fn with_snapshot(embedding_model: EM, path: &str) -> Result<Self, anyhow::Error> {
+        let mut store = Self::new(embedding_model);
+        store.snapshot = Some(path.to_string());
+
+        if Path::new(path).exists() {
+            let reader = BufReader::new(fs::File::open(path)?);
+            let lines = reader.lines().collect::<Result<Vec<_>, _>>()?;
+            let mut documents = Vec::new();
+            let mut truncated = false;
+            for (i, line) in lines.iter().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let entry: SnapshotEntry<D> = match serde_json::from_str(line) {
+                    Ok(entry) => entry,
+                    // an append cut off by a crash, only the document it held is lost
+                    Err(e) if i == lines.len() - 1 => {
+                        warn!(
+                            "[LOCAL] Skipping the incomplete last line of {}: {}",
+                            path, e
+                        );
+                        truncated = true;
+                        continue;
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+                documents.push((entry.id, entry.document, OneOrMany::many(entry.embeddings)?));
+            }
+            store.store.add_documents_with_ids(documents);
+            // the cut off line is dropped before anything is appended after it
+            if truncated {
+                store.save_snapshot()?;
+            }
         }
+
+        Ok(store)
     }
 }
 
@@ -40,8 +164,7 @@ where
             .build()
             .await?;
 
-        self.store.add_documents(embedding);
-        Ok(())
+        self.insert(embedding)
     }
 
     async fn add_many(&mut self, documents: Vec<D>) -> Result<(), anyhow::Error> {
@@ -50,9 +173,7 @@ where
             .build()
             .await?;
 
-        self.store.add_documents(embedding);
-
-        Ok(())
+        self.insert(embedding)
     }
 
     async fn top_n<T: for<'a> Deserialize<'a> + Send>(
@@ -85,12 +206,123 @@ where
         Ok(results.into_iter().collect::<Vec<_>>())
     }
 
+    async fn top_n_where<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        embedding: &Embedding,
+        n: usize,
+        filter: &[(&str, &str)],
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let mut scored = Vec::new();
+        for (id, (doc, embeddings)) in self.store.iter() {
+            let value = serde_json::to_value(doc)?;
+            let matches = filter.iter().all(|(field, expected)| {
+                value.get(*field).and_then(|v| v.as_str()) == Some(*expected)
+            });
+            if !matches {
+                continue;
+            }
+
+            let score = embeddings
+                .iter()
+                .map(|e| e.cosine_similarity(embedding, false))
+                .fold(f64::NEG_INFINITY, f64::max);
+            scored.push((score, id.clone(), value));
+        }
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.truncate(n);
+
+        scored
+            .into_iter()
+            .map(|(score, id, value)| Ok((score, id, serde_json::from_value(value)?)))
+            .collect()
+    }
+
+    async fn keyword_top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let mut ids = Vec::with_capacity(self.store.len());
+        let mut texts = Vec::with_capacity(self.store.len());
+        let mut docs = Vec::with_capacity(self.store.len());
+        for (id, (doc, _)) in self.store.iter() {
+            let text = to_texts(doc.clone())
+                .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?
+                .join(" ");
+            ids.push(id.clone());
+            texts.push(text);
+            docs.push(doc);
+        }
+
+        let mut scored = retrieval::bm25_scores(query, &texts)
+            .into_iter()
+            .zip(ids.into_iter().zip(docs))
+            .filter(|(score, _)| *score > 0.0)
+            .collect::<Vec<_>>();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.truncate(n);
+
+        scored
+            .into_iter()
+            .map(|(score, (id, doc))| {
+                let t = serde_json::from_value(serde_json::to_value(doc)?)?;
+                Ok((score, id, t))
+            })
+            .collect()
+    }
+
+    async fn documents<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<(String, T)>, anyhow::Error> {
+        let mut documents = self.store.iter().collect::<Vec<_>>();
+        documents.sort_by(|a, b| a.0.cmp(b.0));
+
+        documents
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(|(id, (doc, _))| {
+                let t = serde_json::from_value(serde_json::to_value(doc)?)?;
+                Ok((id.clone(), t))
+            })
+            .collect()
+    }
+
+    async fn delete_where(&mut self, field: &str, value: &str) -> Result<usize, anyhow::Error> {
+        let before = self.store.len();
+        let mut kept = Vec::with_capacity(before);
+        for (_, (doc, embeddings)) in self.store.iter() {
+            let matches = serde_json::to_value(doc)?
+                .get(field)
+                .and_then(|v| v.as_str())
+                .is_some_and(|v| v == value);
+            if !matches {
+                kept.push((doc.clone(), embeddings.clone()));
+            }
+        }
+
+        // rebuilt with fresh ids since `add_documents` numbers new ids from the store length
+        self.store = InMemoryVectorStore::from_documents(kept);
+        self.save_snapshot()?;
+        Ok(before - self.store.len())
+    }
+
     async fn clear(&mut self) -> Result<(), anyhow::Error> {
-        *self = Self::new(self.embedding_model.clone());
-        Ok(())
+        self.store = InMemoryVectorStore::from_documents(vec![]);
+        self.save_snapshot()
     }
 
     async fn count(&self) -> Result<usize, anyhow::Error> {
         Ok(self.store.len())
     }
+
+    async fn ndims(&self) -> Result<Option<usize>, anyhow::Error> {
+        Ok(self
+            .store
+            .iter()
+            .next()
+            .map(|(_, (_, embeddings))| embeddings.first().vec.len()))
+    }
 }
@@ -1,5 +1,12 @@
 use crate::core::memory::MemoryStore as CoreMemoryStore;
-use mongodb::{bson::Document, options::ClientOptions, Client as MongoClient, Collection};
+use futures::StreamExt;
+use log::info;
+use mongodb::{
+    bson::{doc, Document},
+    error::ErrorKind,
+    options::{ClientOptions, IndexOptions},
+    Client as MongoClient, Collection, IndexModel,
+};
 use rig::{
     embeddings::{Embedding, EmbeddingModel, EmbeddingsBuilder},
     vector_store::{VectorStoreError, VectorStoreIndexDyn},
@@ -40,6 +47,9 @@ where
 
         let collection: Collection<Document> =
             mongodb_client.database(db_name).collection(coll_name);
+        ensure_text_index(&collection).await.map_err(|e| {
+            anyhow::anyhow!("Failed to create the text index on {}: {}", coll_name, e)
+        })?;
 
         Ok(Self {
             collection,
@@ -103,20 +113,8 @@ where
         let results = index.top_n(&query, n).await?;
         results
             .into_iter()
-            .map(|(score, id, mut value)| {
-                if !std::any::type_name::<T>().contains("String") {
-                    if let Some(obj) = value.as_object_mut() {
-                        if let Some(id_obj) = obj.get("_id").and_then(|id| id.as_object()) {
-                            if let Some(oid) = id_obj.get("$oid").and_then(|oid| oid.as_str()) {
-                                obj.insert(
-                                    "_id".to_string(),
-                                    serde_json::Value::String(oid.to_string()),
-                                );
-                            }
-                        }
-                    }
-                }
-                let t: T = serde_json::from_value(value)?;
+            .map(|(score, id, value)| {
+                let t: T = serde_json::from_value(flatten_oid::<T>(value))?;
                 Ok((score, id, t))
             })
             .collect()
@@ -143,9 +141,124 @@ where
             .collect()
     }
 
+    async fn top_n_where<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        embedding: &Embedding,
+        n: usize,
+        filter: &[(&str, &str)],
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        // the filtered fields have to be declared as `filter` fields of the vector_index
+        let mut filter_doc = Document::new();
+        for (field, value) in filter {
+            filter_doc.insert(*field, doc! { "$eq": *value });
+        }
+
+        let mut cursor = self
+            .collection
+            .aggregate([
+                doc! {
+                    "$vectorSearch": {
+                        "index": "vector_index",
+                        "path": "embedding",
+                        "queryVector": &embedding.vec,
+                        "numCandidates": (n * 10) as u32,
+                        "limit": n as u32,
+                        "filter": filter_doc,
+                    }
+                },
+                doc! { "$addFields": { "score": { "$meta": "vectorSearchScore" } } },
+                doc! { "$project": { "embedding": 0 } },
+            ])
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?
+            .with_type::<serde_json::Value>();
+
+        let mut results = Vec::new();
+        while let Some(value) = cursor.next().await {
+            let value = value.map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+            let score = value.get("score").and_then(|s| s.as_f64()).unwrap_or(0.0);
+            let id = value
+                .get("_id")
+                .map(|id| id.to_string())
+                .ok_or_else(|| VectorStoreError::MissingIdError("_id".to_string()))?;
+            let t: T = serde_json::from_value(flatten_oid::<T>(value))?;
+            results.push((score, id, t));
+        }
+
+        Ok(results)
+    }
+
+    async fn keyword_top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        // uses the text index made by `ensure_text_index`
+        let mut cursor = self
+            .collection
+            .aggregate([
+                doc! { "$match": { "$text": { "$search": query } } },
+                doc! { "$addFields": { "score": { "$meta": "textScore" } } },
+                doc! { "$sort": { "score": -1 } },
+                doc! { "$limit": n as i64 },
+                doc! { "$project": { "embedding": 0 } },
+            ])
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?
+            .with_type::<serde_json::Value>();
+
+        let mut results = Vec::new();
+        while let Some(value) = cursor.next().await {
+            let value = value.map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+            let score = value.get("score").and_then(|s| s.as_f64()).unwrap_or(0.0);
+            let id = value
+                .get("_id")
+                .map(|id| id.to_string())
+                .ok_or_else(|| VectorStoreError::MissingIdError("_id".to_string()))?;
+            let t: T = serde_json::from_value(flatten_oid::<T>(value))?;
+            results.push((score, id, t));
+        }
+
+        Ok(results)
+    }
+
+    async fn documents<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<(String, T)>, anyhow::Error> {
+        let mut cursor = self
+            .collection
+            .find(Document::new())
+            .sort(doc! { "_id": 1 })
+            .skip(offset as u64)
+            .limit(limit as i64)
+            .projection(doc! { "embedding": 0 })
+            .await?
+            .with_type::<serde_json::Value>();
+
+        let mut results = Vec::new();
+        while let Some(value) = cursor.next().await {
+            let value = value?;
+            let id = value
+                .get("_id")
+                .map(|id| id.to_string())
+                .ok_or_else(|| anyhow::anyhow!("Document without _id"))?;
+            let t: T = serde_json::from_value(flatten_oid::<T>(value))?;
+            results.push((id, t));
+        }
+
+        Ok(results)
+    }
+
+    async fn delete_where(&mut self, field: &str, value: &str) -> Result<usize, anyhow::Error> {
+        let result = self.collection.delete_many(doc! { field: value }).await?;
+        Ok(result.deleted_count as usize)
+    }
+
     async fn clear(&mut self) -> Result<(), anyhow::Error> {
         self.collection
-            .delete_many(mongodb::bson::doc! {})
+            .delete_many(doc! {})
             .await
             .map(|_| ())
             .map_err(anyhow::Error::from)
@@ -158,4 +271,64 @@ where
             .map(|count| count as usize)
             .map_err(anyhow::Error::from)
     }
+
+    async fn ndims(&self) -> Result<Option<usize>, anyhow::Error> {
+        let document = self
+            .collection
+            .find_one(doc! { "embedding": { "$exists": true } })
+            .projection(doc! { "embedding": 1 })
+            .await?;
+        Ok(document
+            .as_ref()
+            .and_then(|document| document.get_array("embedding").ok())
+            .map(|embedding| embedding.len()))
+    }
+}
+
+fn flatten_oid<T>(mut value: serde_json::Value) -> serde_json::Value {
+    if !std::any::type_name::<T>().contains("String") {
+        if let Some(obj) = value.as_object_mut() {
+            if let Some(id_obj) = obj.get("_id").and_then(|id| id.as_object()) {
+                if let Some(oid) = id_obj.get("$oid").and_then(|oid| oid.as_str()) {
+                    obj.insert(
+                        "_id".to_string(),
+                        serde_json::Value::String(oid.to_string()),
+                    );
+                }
+            }
+        }
+    }
+    value
+}
+
+// keyword search runs `$text`, which needs the collection's one text index; a wildcard index over
+// every string field is created unless the collection already has a text index
+async fn ensure_text_index(collection: &Collection<Document>) -> Result<(), mongodb::error::Error> {
+    match collection.list_indexes().await {
+        Ok(mut indexes) => {
+            while let Some(index) = indexes.next().await {
+                if index?
+                    .keys
+                    .values()
+                    .any(|kind| kind.as_str() == Some("text"))
+                {
+                    return Ok(());
+                }
+            }
+        }
+        // the collection does not exist yet
+        Err(e) if matches!(*e.kind, ErrorKind::Command(ref command) if command.code == 26) => {}
+        Err(e) => return Err(e),
+    }
+    let index = IndexModel::builder()
+        .keys(doc! { "$**": "text" })
+        .options(
+            IndexOptions::builder()
+                .name("text_index".to_string())
+                .build(),
+        )
+        .build();
+    collection.create_index(index).await?;
+    info!("[MONGODB] Created text index on {}", collection.name());
+    Ok(())
 }
@@ -1,21 +1,21 @@
 mod clients;
+mod commands;
 mod core;
 mod dbs;
 mod providers;
 use clap::Parser;
 use clients::{CliClient, DashboardClient};
-use core::{
-    Character, CompletionProvider, Config, EmbeddingProvider, CHARACTERS_FOLDER, CONFIG_PATH,
-};
+use commands::Command;
+use core::{Character, Config, HybridSearchParams, KnowledgeBase, Supervisor, CHARACTERS_FOLDER};
 use dotenv::dotenv;
-use fern::colors::{Color, ColoredLevelConfig};
 use log::{error, info};
 use providers::{
-    completion::CompletionModelEnum,
-    embedding::{EmbeddingModelEnum, LocalEmbeddingModel},
+    completion::{BudgetedCompletionModel, CompletionModelEnum, TokenBudget},
+    embedding::{CachedEmbeddingModel, EmbeddingCache, EmbeddingModelEnum},
+    rerank::LocalRerankModel,
 };
-use std::{env, error::Error, sync::Arc};
-use tokio::{task::JoinSet, time::sleep};
+use std::{error::Error, sync::Arc};
+use tokio::time::sleep;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -28,6 +28,14 @@ struct Args {
     dashboard: bool,
     #[arg(long)]
     force: bool,
+    /// Override a config value, e.g. --set db=mongodb --set providers.openai.completion_model=gpt-4o
+    #[arg(long = "set", value_name = "KEY=VALUE")]
+    set: Vec<String>,
+    /// Override a log level, e.g. --log debug --log serenity=warn
+    #[arg(long = "log", value_name = "[MODULE=]LEVEL")]
+    log: Vec<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
 #[tokio::main]
@@ -35,130 +43,123 @@ async fn main() -> Result<(), Box<dyn Error>> {
     // parse args
     let args = Args::parse();
 
-    // init logging
-    let colors = ColoredLevelConfig::new()
-        .error(Color::Red)
-        .warn(Color::Yellow)
-        .info(Color::Green)
-        .debug(Color::Blue)
-        .trace(Color::Magenta);
-
-    fern::Dispatch::new()
-        .format(move |out, message, record| {
-            out.finish(format_args!(
-                "{}[{}] {}",
-                chrono::Local::now().format("[%Y-%m-%d %H:%M:%S]"),
-                colors.color(record.level()),
-                message
-            ))
-        })
-        .level(log::LevelFilter::Debug)
-        .chain(std::io::stdout())
-        .apply()?;
+    // init logging, configured once the config is loaded
+    core::logging::init()?;
     info!("Starting FABELIS.AI Agent...");
 
-    // load config.json
-    let config_path = &args.config.unwrap_or(CONFIG_PATH.to_string());
-    info!("[SETUP] Loading from {config_path}...");
-    let config =
-        Config::new(config_path.to_string()).expect(&format!("Failed to load {config_path}"));
-    info!("[SETUP] Loaded: {:#?}", config);
-
-    // load .env
+    // load .env first so the config can interpolate it
     dotenv().ok();
     info!("[SETUP] Loaded .env");
 
-    // load completion model
-    let completion_model: CompletionModelEnum = match config.completion_provider {
-        CompletionProvider::Anthropic => {
-            let api_key = env::var("ANTHROPIC_API_KEY").expect("ANTHROPIC_API_KEY not set");
-            let model =
-                env::var("ANTHROPIC_COMPLETION_MODEL").expect("ANTHROPIC_COMPLETION_MODEL not set");
-            let client = rig::providers::anthropic::ClientBuilder::new(&api_key).build();
-            let model = CompletionModelEnum::Anthropic(client.completion_model(&model));
-            info!("[SETUP] Loaded Anthropic Completion Model");
-            model
-        }
-        CompletionProvider::Cohere => {
-            let api_key = env::var("COHERE_API_KEY").expect("COHERE_API_KEY not set");
-            let model =
-                env::var("COHERE_COMPLETION_MODEL").expect("COHERE_COMPLETION_MODEL not set");
-            let client = rig::providers::cohere::Client::new(&api_key);
-            let model = CompletionModelEnum::Cohere(client.completion_model(&model));
-            info!("[SETUP] Loaded Cohere Completion Model");
-            model
-        }
-        CompletionProvider::Gemini => {
-            let api_key = env::var("GEMINI_API_KEY").expect("GEMINI_API_KEY not set");
-            let model =
-                env::var("GEMINI_COMPLETION_MODEL").expect("GEMINI_COMPLETION_MODEL not set");
-            let client = rig::providers::gemini::Client::new(&api_key);
-            let model = CompletionModelEnum::Gemini(client.completion_model(&model));
-            info!("[SETUP] Loaded Gemini Completion Model");
-            model
-        }
-        CompletionProvider::OpenAI => {
-            let api_key = env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY not set");
-            let model =
-                env::var("OPENAI_COMPLETION_MODEL").expect("OPENAI_COMPLETION_MODEL not set");
-            let client = rig::providers::openai::Client::new(&api_key);
-            let model = CompletionModelEnum::OpenAI(client.completion_model(&model));
-            info!("[SETUP] Loaded OpenAI Completion Model");
-            model
-        }
-        CompletionProvider::Perplexity => {
-            let api_key = env::var("PERPLEXITY_API_KEY").expect("PERPLEXITY_API_KEY not set");
-            let model = env::var("PERPLEXITY_COMPLETION_MODEL")
-                .expect("PERPLEXITY_COMPLETION_MODEL not set");
-            let client = rig::providers::perplexity::Client::new(&api_key);
-            let model = CompletionModelEnum::Perplexity(client.completion_model(&model));
-            info!("[SETUP] Loaded Perplexity Completion Model");
-            model
-        }
-        CompletionProvider::XAI => {
-            let api_key = env::var("XAI_API_KEY").expect("XAI_API_KEY not set");
-            let model = env::var("XAI_COMPLETION_MODEL").expect("XAI_COMPLETION_MODEL not set");
-            let client = rig::providers::xai::Client::new(&api_key);
-            let model = CompletionModelEnum::XAI(client.completion_model(&model));
-            info!("[SETUP] Loaded XAI Completion Model");
-            model
-        }
-        CompletionProvider::DeepSeek => {
-            let api_key = env::var("DEEPSEEK_API_KEY").expect("DEEPSEEK_API_KEY not set");
-            let model =
-                env::var("DEEPSEEK_COMPLETION_MODEL").expect("DEEPSEEK_COMPLETION_MODEL not set");
-            let client = crate::providers::completion::deepseek::Client::new(&api_key);
-            let model = CompletionModelEnum::DeepSeek(client.completion_model(&model));
-            info!("[SETUP] Loaded DeepSeek Completion Model");
-            model
+    let config_path = &args.config.unwrap_or_else(core::layers::default_path);
+    let character_path = format!(
+        "{}/{}",
+        CHARACTERS_FOLDER,
+        args.character
+            .unwrap_or("character.example.json".to_string())
+    );
+
+    // the doctor reports a broken config instead of failing on it
+    if let Some(Command::Doctor) = args.command {
+        commands::doctor::run(config_path, &args.set, &character_path).await?;
+        return Ok(());
+    }
+
+    // load config
+    info!("[SETUP] Loading from {config_path}...");
+    let config = match Config::load(config_path, &args.set) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("[SETUP] Failed to load {}: {}", config_path, e);
+            std::process::exit(1);
         }
     };
+    if let Err(e) = config.check(args.command.is_none()) {
+        error!("[SETUP] {}", e);
+        std::process::exit(1);
+    }
+    if let Err(e) = core::logging::configure(&config.logging, &args.log, config.providers.secrets())
+    {
+        error!("[SETUP] Failed to configure logging: {}", e);
+        std::process::exit(1);
+    }
+    info!("[SETUP] Loaded: {:#?}", config);
 
-    // load embedding model
-    let embedding_model: EmbeddingModelEnum = match config.embedding_provider {
-        EmbeddingProvider::Local => {
-            let provider =
-                LocalEmbeddingModel::new().expect("Failed to initialize local embedding model");
-            info!("[SETUP] Loaded Local Embedding Model");
-            EmbeddingModelEnum::Local { model: provider }
+    // drafts are reviewed and the audit log is searched without loading any model
+    match args.command {
+        Some(Command::Drafts { command }) => {
+            commands::drafts::run(command, &config)?;
+            return Ok(());
         }
-        EmbeddingProvider::OpenAI => {
-            let api_key = env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY not set");
-            let client = rig::providers::openai::Client::new(&api_key);
-            let provider =
-                rig::providers::openai::EmbeddingModel::new(client, "text-embedding-ada-002", 1536);
-            info!("[SETUP] Loaded OpenAI Embedding Model");
-            EmbeddingModelEnum::OpenAI { model: provider }
-        }
-        EmbeddingProvider::XAI => {
-            let api_key = env::var("XAI_API_KEY").expect("XAI_API_KEY not set");
-            let client = rig::providers::xai::Client::new(&api_key);
-            let provider =
-                rig::providers::xai::embedding::EmbeddingModel::new(client, "text-embedding", 768);
-            info!("[SETUP] Loaded XAI Embedding Model");
-            EmbeddingModelEnum::XAI { model: provider }
+        Some(Command::Audit { query, json }) => {
+            commands::audit::run(query, json, &config)?;
+            return Ok(());
         }
+        _ => {}
+    }
+
+    // load embedding model
+    let embedding_model =
+        EmbeddingModelEnum::from_config(&config).expect("Failed to load embedding model");
+
+    // wrap embedding model with on-disk cache
+    let embedding_cache = if config.embedding.cache {
+        Some(
+            EmbeddingCache::load(&config.embedding.cache_dir, &embedding_model.model_id())
+                .expect("Failed to load embedding cache"),
+        )
+    } else {
+        None
     };
+    let embedding_model = CachedEmbeddingModel::new(embedding_model, embedding_cache);
+
+    // run subcommand instead of the agent
+    if let Some(command) = args.command {
+        match command {
+            Command::Memory { command } => {
+                commands::memory::run(command, embedding_model, &config).await?
+            }
+            Command::Knowledge { command } => {
+                let mut character = Character::new(character_path);
+                character
+                    .load()
+                    .expect("Failed to load character from file");
+                commands::knowledge::run(command, &character, embedding_model, &config).await?
+            }
+            Command::Doctor => unreachable!("handled before loading the config"),
+            Command::Drafts { .. } | Command::Audit { .. } => {
+                unreachable!("handled before loading the models")
+            }
+        }
+        return Ok(());
+    }
+
+    // load completion model
+    let completion_model = CompletionModelEnum::from_config(&config);
+
+    // fit every request into the model's context window
+    let token_budget = TokenBudget::new(completion_model.model_name(), &config.context);
+    info!(
+        "[SETUP] Context window: {} tokens ({} reserved for responses)",
+        token_budget.context_length, token_budget.reserve_tokens
+    );
+    let completion_model = BudgetedCompletionModel::new(completion_model, token_budget);
+
+    // load rerank model
+    let rerank_model = config
+        .retrieval
+        .reranker
+        .as_deref()
+        .map(|model_code| {
+            LocalRerankModel::new(model_code).map_err(|e| {
+                anyhow::anyhow!("Failed to initialize retrieval.reranker {model_code}: {e}")
+            })
+        })
+        .transpose()?;
+    if let Some(model_code) = &config.retrieval.reranker {
+        info!("[SETUP] Loaded Local Rerank Model: {}", model_code);
+    }
+    let search_params = HybridSearchParams::new(&config.retrieval, rerank_model);
 
     if args.dashboard {
         if args.force {
@@ -197,118 +198,174 @@ async fn main() -> Result<(), Box<dyn Error>> {
     }
 
     // load character
-    let character_path = format!(
-        "{}/{}",
-        CHARACTERS_FOLDER,
-        args.character
-            .unwrap_or("character.example.json".to_string())
-    );
     info!("[SETUP] Loading character: {}", character_path);
     let mut character = Character::new(character_path);
     character
         .load()
         .expect("Failed to load character from file");
 
+    // load knowledge base, shared by every client
+    let knowledge = KnowledgeBase::new(&character.alias, &config, embedding_model.clone()).await;
+
     // cli and other clients cannot run at the same time
     if config.enabled_clients.contains(&core::Clients::Cli) && config.client_configs.cli.is_some() {
-        let mut cli_client = CliClient::new(character, completion_model);
+        let mut cli_client = CliClient::new(
+            character,
+            completion_model,
+            embedding_model,
+            config.clone().client_configs.cli.unwrap(),
+            config.clone(),
+            knowledge,
+        )
+        .await;
         cli_client.start().await;
     } else {
-        // store clients using JoinSet for concurrency
-        let mut join_set = JoinSet::new();
+        // restarts failed clients and stops them all on SIGINT or SIGTERM
+        let mut supervisor = Supervisor::new(&config.supervisor);
+
+        if config.admin.enabled {
+            let admin = config.admin.clone();
+            supervisor.spawn("admin", move |shutdown| {
+                core::admin::start(admin.clone(), shutdown)
+            });
+        }
 
         if config.enabled_clients.contains(&core::Clients::Api)
             && config.client_configs.api.is_some()
         {
-            let config = config.clone().client_configs.api.unwrap();
-            let client = Arc::new(clients::ApiClient::new(
-                character.clone(),
-                completion_model.clone(),
-                config,
-            ));
-            join_set.spawn(async move {
-                client.start().await;
-            });
+            let client = Arc::new(
+                clients::ApiClient::new(
+                    character.clone(),
+                    completion_model.clone(),
+                    embedding_model.clone(),
+                    config.clone().client_configs.api.unwrap(),
+                    config.clone(),
+                    knowledge.clone(),
+                )
+                .await,
+            );
+            supervisor.spawn("api", move |shutdown| client.clone().start(shutdown));
         }
         if config
             .enabled_clients
             .contains(&core::Clients::Storytelling)
             && config.client_configs.storytelling.is_some()
         {
-            let config = config.clone().client_configs.storytelling.unwrap();
             let client = Arc::new(clients::StoryTellingClient::new(
                 character.clone(),
                 completion_model.clone(),
-                config,
+                config.clone().client_configs.storytelling.unwrap(),
+                config.clone(),
             ));
-            join_set.spawn(async move {
-                client.start().await;
+            supervisor.spawn("storytelling", move |shutdown| {
+                client.clone().start(shutdown)
             });
         }
         if config.enabled_clients.contains(&core::Clients::Twitter)
             && config.client_configs.twitter.is_some()
         {
-            let mut client = clients::TwitterClient::new(
+            // opened once up front, so a store of other dimensions stops the agent here instead of
+            // failing every restart
+            clients::social::open_search_memory::<clients::twitter::TwitterClient, _>(
+                embedding_model.clone(),
+                &config,
+            )
+            .await
+            .expect("Failed to open the Twitter memory store");
+            let (character, completion_model, embedding_model) = (
                 character.clone(),
                 completion_model.clone(),
                 embedding_model.clone(),
-                config.clone().client_configs.twitter.unwrap(),
-                config.clone(),
-            )
-            .await;
-            join_set.spawn(async move {
-                client.start().await;
+            );
+            let (search_params, config, knowledge) =
+                (search_params.clone(), config.clone(), knowledge.clone());
+            // every restart builds the client again, so its memory store and state are read back
+            // from disk instead of copied from before the failure
+            supervisor.spawn("twitter", move |shutdown| {
+                let client = clients::TwitterClient::new(
+                    character.clone(),
+                    completion_model.clone(),
+                    embedding_model.clone(),
+                    config.clone().client_configs.twitter.unwrap(),
+                    search_params.clone(),
+                    config.clone(),
+                    knowledge.clone(),
+                );
+                async move { client.await.start(shutdown).await }
             });
         }
         if config.enabled_clients.contains(&core::Clients::Discord)
             && config.client_configs.discord.is_some()
         {
-            let client = clients::DiscordClient::new(
-                character.clone(),
-                completion_model.clone(),
-                config.clone().client_configs.discord.unwrap(),
-            )
-            .await;
-            join_set.spawn(async move {
-                client.start().await;
+            let client = Arc::new(
+                clients::DiscordClient::new(
+                    character.clone(),
+                    completion_model.clone(),
+                    embedding_model.clone(),
+                    config.clone().client_configs.discord.unwrap(),
+                    config.clone(),
+                    knowledge.clone(),
+                )
+                .await,
+            );
+            supervisor.spawn("discord", move |shutdown| {
+                let client = client.clone();
+                async move { client.start(shutdown).await }
             });
         }
         if config.enabled_clients.contains(&core::Clients::Telegram)
             && config.client_configs.telegram.is_some()
         {
-            let client = clients::TelegramClient::new(
-                character.clone(),
-                completion_model.clone(),
-                config.clone().client_configs.telegram.unwrap(),
-            )
-            .await;
-            join_set.spawn(async move {
-                client.start().await;
+            let client = Arc::new(
+                clients::TelegramClient::new(
+                    character.clone(),
+                    completion_model.clone(),
+                    embedding_model.clone(),
+                    config.clone().client_configs.telegram.unwrap(),
+                    config.clone(),
+                    knowledge.clone(),
+                )
+                .await,
+            );
+            supervisor.spawn("telegram", move |shutdown| {
+                let client = client.clone();
+                async move { client.start(shutdown).await }
             });
         }
         if config.enabled_clients.contains(&core::Clients::Truth)
             && config.client_configs.truth.is_some()
         {
-            let mut client = clients::TruthClient::new(
+            clients::social::open_search_memory::<clients::truth::Truth, _>(
+                embedding_model.clone(),
+                &config,
+            )
+            .await
+            .expect("Failed to open the Truth Social memory store");
+            let (character, completion_model, embedding_model) = (
                 character.clone(),
                 completion_model.clone(),
                 embedding_model.clone(),
-                config.clone().client_configs.truth.unwrap(),
-                config.clone(),
-            )
-            .await;
-            join_set.spawn(async move {
-                client.start().await;
+            );
+            let (search_params, config, knowledge) =
+                (search_params.clone(), config.clone(), knowledge.clone());
+            // every restart builds the client again, so its memory store and state are read back
+            // from disk instead of copied from before the failure
+            supervisor.spawn("truth", move |shutdown| {
+                let client = clients::TruthClient::new(
+                    character.clone(),
+                    completion_model.clone(),
+                    embedding_model.clone(),
+                    config.clone().client_configs.truth.unwrap(),
+                    search_params.clone(),
+                    config.clone(),
+                    knowledge.clone(),
+                );
+                async move { client.await.start(shutdown).await }
             });
         }
 
         // start clients
-        while let Some(result) = join_set.join_next().await {
-            match result {
-                Ok(_) => {}
-                Err(e) => error!("[CLIENTS] Failed: {:?}", e),
-            }
-        }
+        supervisor.run().await;
     }
 
     Ok(())
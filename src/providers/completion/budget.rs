@@ -0,0 +1,466 @@
+use crate::core::{metrics, ContextConfig};
+use log::{info, warn};
+use rig::completion::{
+    CompletionError, CompletionModel, CompletionRequest, CompletionResponse, Document, ModelChoice,
+};
+use std::time::Instant;
+use tiktoken_rs::cl100k_base_singleton;
+
+// cl100k is not exact for every provider but close enough to stay inside the window
+pub fn count_tokens(text: &str) -> usize {
+    cl100k_base_singleton()
+        .encode_with_special_tokens(text)
+        .len()
+}
+
+pub fn truncate_to_tokens(text: &str, max_tokens: usize) -> String {
+    let bpe = cl100k_base_singleton();
+    let tokens = bpe.encode_with_special_tokens(text);
+    if tokens.len() <= max_tokens {
+        return text.to_string();
+    }
+    bpe.decode_bytes(&tokens[..max_tokens])
+        .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+        .unwrap_or_default()
+}
+
+// cuts text into windows of `size` tokens, each starting `overlap` tokens before the previous one ended
+pub fn token_windows(text: &str, size: usize, overlap: usize) -> Vec<String> {
+    let bpe = cl100k_base_singleton();
+    let tokens = bpe.encode_with_special_tokens(text);
+    let step = size.saturating_sub(overlap).max(1);
+    let mut windows = Vec::new();
+    let mut start = 0;
+    while start < tokens.len() {
+        let end = (start + size).min(tokens.len());
+        if let Ok(bytes) = bpe.decode_bytes(&tokens[start..end]) {
+            windows.push(String::from_utf8_lossy(&bytes).to_string());
+        }
+        if end == tokens.len() {
+            break;
+        }
+        start += step;
+    }
+    windows
+}
+
+// keeps the leading items that fit in `max_tokens`, so callers pass them in priority order
+pub fn take_within(items: Vec<String>, max_tokens: usize) -> Vec<String> {
+    let mut used = 0;
+    items
+        .into_iter()
+        .take_while(|item| {
+            used += count_tokens(item) + 1;
+            used <= max_tokens
+        })
+        .collect()
+}
+
+const DEFAULT_CONTEXT_LENGTH: usize = 8_192;
+
+// matched by prefix in order, so more specific names come first
+const CONTEXT_LENGTHS: &[(&str, usize)] = &[
+    ("claude", 200_000),
+    ("gpt-4o", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4.1", 1_047_576),
+    ("gpt-4", 8_192),
+    ("gpt-3.5", 16_385),
+    ("o1", 200_000),
+    ("o3", 200_000),
+    ("gemini-1.5", 1_048_576),
+    ("gemini-2", 1_048_576),
+    ("gemini", 32_768),
+    ("command-r", 128_000),
+    ("command", 4_096),
+    ("grok", 131_072),
+    ("deepseek", 64_000),
+    ("llama-3.1-sonar", 127_072),
+    ("sonar", 127_072),
+];
+
+pub fn context_length(model: &str) -> usize {
+    let model = model.to_lowercase();
+    CONTEXT_LENGTHS
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|(_, length)| *length)
+        .unwrap_or(DEFAULT_CONTEXT_LENGTH)
+}
+
+#[derive(Clone, Debug)]
+pub struct TokenBudget {
+    pub context_length: usize,
+    // kept free for the response when a request does not set `max_tokens`
+    pub reserve_tokens: usize,
+    pub summarize: bool,
+}
+
+impl TokenBudget {
+    // per message overhead of the chat formats
+    const MESSAGE_OVERHEAD: usize = 4;
+    // history messages that outrank documents
+    const RECENT_MESSAGES: usize = 2;
+    // no point in asking for a summary smaller than this
+    const MIN_SUMMARY_TOKENS: usize = 64;
+
+    pub fn new(model: &str, config: &ContextConfig) -> Self {
+        Self {
+            context_length: config
+                .context_length
+                .unwrap_or_else(|| context_length(model)),
+            reserve_tokens: config.reserve_tokens,
+            summarize: config.summarize,
+        }
+    }
+
+    fn response_tokens(&self, request: &CompletionRequest) -> usize {
+        request
+            .max_tokens
+            .map(|max_tokens| max_tokens as usize)
+            .unwrap_or(self.reserve_tokens)
+    }
+
+    // drops history and documents that do not fit, by priority: prompt and preamble, the most
+    // recent messages, documents newest first, then older messages newest first. Returns the
+    // dropped documents and the tokens still free.
+    pub fn fit(&self, request: &mut CompletionRequest) -> (Vec<Document>, usize) {
+        let fixed = count_tokens(&request.prompt)
+            + request.preamble.as_deref().map_or(0, count_tokens)
+            + Self::MESSAGE_OVERHEAD * 2;
+        let available = self
+            .context_length
+            .saturating_sub(self.response_tokens(request));
+        if fixed > available {
+            warn!(
+                "[BUDGET] Prompt needs {} tokens but only {} are available",
+                fixed, available
+            );
+        }
+        let mut remaining = available.saturating_sub(fixed);
+
+        let message_tokens = request
+            .chat_history
+            .iter()
+            .map(|m| count_tokens(&m.content) + Self::MESSAGE_OVERHEAD)
+            .collect::<Vec<_>>();
+        let document_tokens = request
+            .documents
+            .iter()
+            .map(|d| count_tokens(&d.to_string()))
+            .collect::<Vec<_>>();
+
+        let mut keep_messages = vec![false; message_tokens.len()];
+        let mut keep_documents = vec![false; document_tokens.len()];
+        let mut take = |tokens: usize, keep: &mut bool| {
+            if tokens <= remaining {
+                remaining -= tokens;
+                *keep = true;
+            }
+        };
+
+        let recent_start = message_tokens.len().saturating_sub(Self::RECENT_MESSAGES);
+        for i in (recent_start..message_tokens.len()).rev() {
+            take(message_tokens[i], &mut keep_messages[i]);
+        }
+        for i in (0..document_tokens.len()).rev() {
+            take(document_tokens[i], &mut keep_documents[i]);
+        }
+        for i in (0..recent_start).rev() {
+            take(message_tokens[i], &mut keep_messages[i]);
+        }
+
+        // drop everything older than the first message that did not fit so the history stays contiguous
+        if let Some(cutoff) = keep_messages.iter().rposition(|keep| !keep) {
+            remaining += (0..cutoff)
+                .filter(|i| keep_messages[*i])
+                .map(|i| message_tokens[i])
+                .sum::<usize>();
+            request.chat_history.drain(..=cutoff);
+            warn!(
+                "[BUDGET] Dropped {} history messages to fit the context window",
+                cutoff + 1
+            );
+        }
+
+        let mut dropped_documents = Vec::new();
+        let documents = std::mem::take(&mut request.documents);
+        for (document, keep) in documents.into_iter().zip(keep_documents) {
+            if keep {
+                request.documents.push(document);
+            } else {
+                dropped_documents.push(document);
+            }
+        }
+        if !dropped_documents.is_empty() {
+            warn!(
+                "[BUDGET] Dropped {} documents to fit the context window",
+                dropped_documents.len()
+            );
+        }
+
+        (dropped_documents, remaining)
+    }
+}
+
+// completion model that fits every request into the model's context window before sending it
+#[derive(Clone)]
+pub struct BudgetedCompletionModel<CM> {
+    pub model: CM,
+    pub budget: TokenBudget,
+}
+
+impl<CM> BudgetedCompletionModel<CM>
+where
+    CM: CompletionModel,
+{
+    pub fn new(model: CM, budget: TokenBudget) -> Self {
+        Self { model, budget }
+    }
+
+    // condenses documents that did not fit into a single document of at most `max_tokens`
+    async fn summarize(&self, documents: &[Document], max_tokens: usize) -> Option<Document> {
+        let input_tokens = self
+            .budget
+            .context_length
+            .saturating_sub(max_tokens + self.budget.reserve_tokens);
+        let text = truncate_to_tokens(
+            &documents
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            input_tokens,
+        );
+
+        let request = CompletionRequest {
+            prompt: format!(
+                "<documents>\n{text}\n</documents>\n\nSummarize <documents> in less than {} words. Keep names, events and their order. Only output the summary.",
+                max_tokens * 3 / 4
+            ),
+            preamble: Some("You condense documents so they fit into a limited context.".to_string()),
+            chat_history: Vec::new(),
+            documents: Vec::new(),
+            tools: Vec::new(),
+            temperature: None,
+            max_tokens: Some(max_tokens as u64),
+            additional_params: None,
+        };
+
+        match self.model.completion(request).await {
+            Ok(CompletionResponse {
+                choice: ModelChoice::Message(summary),
+                ..
+            }) => {
+                info!("[BUDGET] Summarized {} dropped documents", documents.len());
+                Some(Document {
+                    id: "summary of earlier documents".to_string(),
+                    text: truncate_to_tokens(&summary, max_tokens),
+                    additional_props: Default::default(),
+                })
+            }
+            Ok(_) => None,
+            Err(e) => {
+                warn!("[BUDGET] Failed to summarize dropped documents: {}", e);
+                None
+            }
+        }
+    }
+}
+
+impl<CM> CompletionModel for BudgetedCompletionModel<CM>
+where
+    CM: CompletionModel,
+{
+    type Response = CM::Response;
+
+    async fn completion(
+        &self,
+        mut request: CompletionRequest,
+    ) -> Result<CompletionResponse<Self::Response>, CompletionError> {
+        let (dropped, remaining) = self.budget.fit(&mut request);
+
+        // the document wrapper costs a few tokens on top of the summary itself
+        let summary_tokens = remaining.saturating_sub(32);
+        if !dropped.is_empty()
+            && self.budget.summarize
+            && summary_tokens >= TokenBudget::MIN_SUMMARY_TOKENS
+        {
+            if let Some(summary) = self.summarize(&dropped, summary_tokens).await {
+                request.documents.insert(0, summary);
+            }
+        }
+
+        let started = Instant::now();
+        let response = self.model.completion(request).await;
+        metrics::completion(started.elapsed(), response.is_ok());
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rig::completion::Message;
+
+    fn words(word: &str, n: usize) -> String {
+        vec![word; n].join(" ")
+    }
+
+    fn request(messages: &[String], documents: &[String]) -> CompletionRequest {
+        CompletionRequest {
+            prompt: "prompt".to_string(),
+            preamble: Some("preamble".to_string()),
+            chat_history: messages
+                .iter()
+                .map(|content| Message {
+                    role: "user".to_string(),
+                    content: content.clone(),
+                })
+                .collect(),
+            documents: documents
+                .iter()
+                .enumerate()
+                .map(|(i, text)| Document {
+                    id: format!("doc{i}"),
+                    text: text.clone(),
+                    additional_props: Default::default(),
+                })
+                .collect(),
+            tools: Vec::new(),
+            temperature: None,
+            max_tokens: None,
+            additional_params: None,
+        }
+    }
+
+    fn fixed(request: &CompletionRequest) -> usize {
+        count_tokens(&request.prompt)
+            + request.preamble.as_deref().map_or(0, count_tokens)
+            + TokenBudget::MESSAGE_OVERHEAD * 2
+    }
+
+    fn message(content: &str) -> usize {
+        count_tokens(content) + TokenBudget::MESSAGE_OVERHEAD
+    }
+
+    fn document(request: &CompletionRequest, i: usize) -> usize {
+        count_tokens(&request.documents[i].to_string())
+    }
+
+    fn budget(context_length: usize) -> TokenBudget {
+        TokenBudget {
+            context_length,
+            reserve_tokens: 0,
+            summarize: false,
+        }
+    }
+
+    #[test]
+    fn fit_keeps_everything_that_fits() {
+        let messages = vec![words("old", 10), words("new", 10)];
+        let mut request = request(&messages, &[words("fact", 10)]);
+        let used =
+            fixed(&request) + message(&messages[0]) + message(&messages[1]) + document(&request, 0);
+
+        let (dropped, remaining) = budget(used + 5).fit(&mut request);
+        assert!(dropped.is_empty());
+        assert_eq!(remaining, 5);
+        assert_eq!(request.chat_history.len(), 2);
+        assert_eq!(request.documents.len(), 1);
+    }
+
+    #[test]
+    fn fit_drops_older_messages_before_documents() {
+        let messages = (0..4)
+            .map(|i| words(&format!("m{i}"), 10))
+            .collect::<Vec<_>>();
+        let mut request = request(&messages, &[words("fact", 10), words("lore", 10)]);
+        let used = fixed(&request)
+            + message(&messages[2])
+            + message(&messages[3])
+            + document(&request, 0)
+            + document(&request, 1);
+
+        let (dropped, remaining) = budget(used).fit(&mut request);
+        assert!(dropped.is_empty());
+        assert_eq!(remaining, 0);
+        let kept = request
+            .chat_history
+            .iter()
+            .map(|m| m.content.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(kept, messages[2..]);
+        assert_eq!(request.documents.len(), 2);
+    }
+
+    #[test]
+    fn fit_drops_oldest_documents_first() {
+        let messages = vec![words("recent", 10)];
+        let mut request = request(&messages, &[words("fact", 10), words("lore", 10)]);
+        let used = fixed(&request) + message(&messages[0]) + document(&request, 1);
+
+        let (dropped, _) = budget(used).fit(&mut request);
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].id, "doc0");
+        assert_eq!(request.documents[0].id, "doc1");
+        assert_eq!(request.chat_history.len(), 1);
+    }
+
+    #[test]
+    fn fit_keeps_history_contiguous() {
+        // the oldest message would fit on its own, but not without the one after it
+        let messages = vec![
+            words("first", 2),
+            words("second", 50),
+            words("third", 5),
+            words("fourth", 5),
+        ];
+        let mut request = request(&messages, &[]);
+        let used = fixed(&request) + message(&messages[2]) + message(&messages[3]);
+
+        let (_, remaining) = budget(used + message(&messages[0])).fit(&mut request);
+        assert_eq!(request.chat_history.len(), 2);
+        assert_eq!(request.chat_history[0].content, messages[2]);
+        assert_eq!(remaining, message(&messages[0]));
+    }
+
+    #[test]
+    fn fit_leaves_room_for_the_response() {
+        let mut request = request(&[], &[words("fact", 10)]);
+        let used = fixed(&request) + document(&request, 0);
+        let budget = TokenBudget {
+            context_length: used + 100,
+            reserve_tokens: 100,
+            summarize: false,
+        };
+
+        let (dropped, remaining) = budget.fit(&mut request);
+        assert!(dropped.is_empty());
+        assert_eq!(remaining, 0);
+
+        // a request's own max_tokens replaces the reserve
+        let mut request = self::request(&[], &[words("fact", 10)]);
+        request.max_tokens = Some(101);
+        let (dropped, _) = budget.fit(&mut request);
+        assert_eq!(dropped.len(), 1);
+    }
+
+    #[test]
+    fn take_within_keeps_leading_items_that_fit() {
+        let items = vec![words("a", 5), words("b", 50), words("c", 1)];
+        let max = count_tokens(&items[0]) + 1;
+        assert_eq!(take_within(items.clone(), max), items[..1]);
+        // a later item that would fit is not taken past one that does not
+        assert_eq!(take_within(items.clone(), max + 2), items[..1]);
+        assert!(take_within(items, 0).is_empty());
+    }
+
+    #[test]
+    fn context_length_matches_the_most_specific_prefix() {
+        assert_eq!(context_length("gpt-4o-mini"), 128_000);
+        assert_eq!(context_length("gpt-4"), 8_192);
+        assert_eq!(context_length("Claude-3-5-Sonnet"), 200_000);
+        assert_eq!(context_length("unknown"), DEFAULT_CONTEXT_LENGTH);
+    }
+}
@@ -1,4 +1,6 @@
 use super::deepseek::{DeepSeekCompletionModel, DeepSeekResponse};
+use crate::core::{CompletionProvider, Config};
+use log::info;
 use rig::{
     completion::{CompletionError, CompletionModel, CompletionRequest, CompletionResponse},
     providers::{
@@ -22,6 +24,81 @@ pub enum CompletionModelEnum {
     DeepSeek(DeepSeekCompletionModel),
 }
 
+impl CompletionModelEnum {
+    pub fn from_config(config: &Config) -> Self {
+        match config.completion_provider {
+            CompletionProvider::Anthropic => {
+                let api_key = config.providers.anthropic.api_key();
+                let model = config.providers.anthropic.completion_model();
+                let client = rig::providers::anthropic::ClientBuilder::new(api_key).build();
+                let model = Self::Anthropic(client.completion_model(model));
+                info!("[SETUP] Loaded Anthropic Completion Model");
+                model
+            }
+            CompletionProvider::Cohere => {
+                let api_key = config.providers.cohere.api_key();
+                let model = config.providers.cohere.completion_model();
+                let client = rig::providers::cohere::Client::new(api_key);
+                let model = Self::Cohere(client.completion_model(model));
+                info!("[SETUP] Loaded Cohere Completion Model");
+                model
+            }
+            CompletionProvider::Gemini => {
+                let api_key = config.providers.gemini.api_key();
+                let model = config.providers.gemini.completion_model();
+                let client = rig::providers::gemini::Client::new(api_key);
+                let model = Self::Gemini(client.completion_model(model));
+                info!("[SETUP] Loaded Gemini Completion Model");
+                model
+            }
+            CompletionProvider::OpenAI => {
+                let api_key = config.providers.openai.api_key();
+                let model = config.providers.openai.completion_model();
+                let client = rig::providers::openai::Client::new(api_key);
+                let model = Self::OpenAI(client.completion_model(model));
+                info!("[SETUP] Loaded OpenAI Completion Model");
+                model
+            }
+            CompletionProvider::Perplexity => {
+                let api_key = config.providers.perplexity.api_key();
+                let model = config.providers.perplexity.completion_model();
+                let client = rig::providers::perplexity::Client::new(api_key);
+                let model = Self::Perplexity(client.completion_model(model));
+                info!("[SETUP] Loaded Perplexity Completion Model");
+                model
+            }
+            CompletionProvider::XAI => {
+                let api_key = config.providers.xai.api_key();
+                let model = config.providers.xai.completion_model();
+                let client = rig::providers::xai::Client::new(api_key);
+                let model = Self::XAI(client.completion_model(model));
+                info!("[SETUP] Loaded XAI Completion Model");
+                model
+            }
+            CompletionProvider::DeepSeek => {
+                let api_key = config.providers.deepseek.api_key();
+                let model = config.providers.deepseek.completion_model();
+                let client = crate::providers::completion::deepseek::Client::new(api_key);
+                let model = Self::DeepSeek(client.completion_model(model));
+                info!("[SETUP] Loaded DeepSeek Completion Model");
+                model
+            }
+        }
+    }
+
+    pub fn model_name(&self) -> &str {
+        match self {
+            Self::Anthropic(model) => &model.model,
+            Self::Cohere(model) => &model.model,
+            Self::Gemini(model) => &model.model,
+            Self::OpenAI(model) => &model.model,
+            Self::Perplexity(model) => &model.model,
+            Self::XAI(model) => &model.model,
+            Self::DeepSeek(model) => &model.model,
+        }
+    }
+}
+
 pub enum CompletionResponseEnum {
     Anthropic(anthropic_completion::CompletionResponse),
     Cohere(cohere_completion::CompletionResponse),
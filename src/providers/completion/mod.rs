@@ -1,4 +1,6 @@
+pub mod budget;
 pub mod deepseek;
 pub mod enums;
 
+pub use budget::{BudgetedCompletionModel, TokenBudget};
 pub use enums::*;
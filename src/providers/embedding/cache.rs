@@ -0,0 +1,187 @@
+use log::{info, warn};
+use rig::embeddings::{Embedding, EmbeddingError, EmbeddingModel};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    key: String,
+    vec: Vec<f64>,
+}
+
+// on-disk jsonl cache of embeddings for a single model, keyed by a hash of the embedded text
+#[derive(Clone)]
+pub struct EmbeddingCache {
+    path: PathBuf,
+    entries: Arc<Mutex<HashMap<String, Vec<f64>>>>,
+}
+
+impl EmbeddingCache {
+    pub fn load(dir: &str, model_id: &str) -> Result<Self, anyhow::Error> {
+        fs::create_dir_all(dir)?;
+        let file_name = model_id
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+        let path = PathBuf::from(dir).join(format!("{file_name}.jsonl"));
+
+        let mut entries = HashMap::new();
+        if path.exists() {
+            let reader = BufReader::new(fs::File::open(&path)?);
+            for line in reader.lines() {
+                match serde_json::from_str::<CacheEntry>(&line?) {
+                    Ok(entry) => {
+                        entries.insert(entry.key, entry.vec);
+                    }
+                    Err(e) => warn!("[EMBEDDING][CACHE] Skipping corrupt entry: {}", e),
+                }
+            }
+        }
+        info!(
+            "[EMBEDDING][CACHE] Loaded {} cached embeddings from {:?}",
+            entries.len(),
+            path
+        );
+
+        Ok(Self {
+            path,
+            entries: Arc::new(Mutex::new(entries)),
+        })
+    }
+
+    fn key(text: &str) -> String {
+        Sha256::digest(text.as_bytes())
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+
+    pub fn get(&self, text: &str) -> Option<Vec<f64>> {
+        self.entries.lock().ok()?.get(&Self::key(text)).cloned()
+    }
+
+    pub fn insert_many(&self, embeddings: &[Embedding]) -> Result<(), anyhow::Error> {
+        let mut entries = self
+            .entries
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Embedding cache lock poisoned"))?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+
+        for embedding in embeddings {
+            let entry = CacheEntry {
+                key: Self::key(&embedding.document),
+                vec: embedding.vec.clone(),
+            };
+            writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+            entries.insert(entry.key, entry.vec);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct CachedEmbeddingModel<EM>
+where
+    EM: EmbeddingModel,
+{
+    pub model: EM,
+    cache: Option<EmbeddingCache>,
+}
+
+impl<EM> CachedEmbeddingModel<EM>
+where
+    EM: EmbeddingModel,
+{
+    pub fn new(model: EM, cache: Option<EmbeddingCache>) -> Self {
+        Self { model, cache }
+    }
+}
+
+impl<EM> EmbeddingModel for CachedEmbeddingModel<EM>
+where
+    EM: EmbeddingModel,
+{
+    const MAX_DOCUMENTS: usize = EM::MAX_DOCUMENTS;
+
+    fn ndims(&self) -> usize {
+        self.model.ndims()
+    }
+
+    // search queries go to the model uncached, some providers embed them unlike documents
+    async fn embed_text(&self, text: &str) -> Result<Embedding, EmbeddingError> {
+        self.model.embed_text(text).await
+    }
+
+    async fn embed_texts(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        let texts = texts.into_iter().collect::<Vec<_>>();
+        let Some(cache) = &self.cache else {
+            return self.model.embed_texts(texts).await;
+        };
+
+        let mut embeddings = texts
+            .iter()
+            .map(|text| {
+                cache.get(text).map(|vec| Embedding {
+                    document: text.clone(),
+                    vec,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let misses = embeddings
+            .iter()
+            .enumerate()
+            .filter(|(_, embedding)| embedding.is_none())
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        if misses.is_empty() {
+            return Ok(embeddings.into_iter().flatten().collect());
+        }
+
+        let miss_texts = misses.iter().map(|i| texts[*i].clone()).collect::<Vec<_>>();
+        let computed = self.model.embed_texts(miss_texts).await?;
+        if computed.len() != misses.len() {
+            return Err(EmbeddingError::ResponseError(format!(
+                "Expected {} embeddings, got {}",
+                misses.len(),
+                computed.len()
+            )));
+        }
+        let computed = computed
+            .into_iter()
+            .zip(misses.iter())
+            .map(|(embedding, i)| Embedding {
+                document: texts[*i].clone(),
+                vec: embedding.vec,
+            })
+            .collect::<Vec<_>>();
+
+        if let Err(e) = cache.insert_many(&computed) {
+            warn!("[EMBEDDING][CACHE] Failed to persist embeddings: {}", e);
+        }
+
+        for (embedding, i) in computed.into_iter().zip(misses) {
+            embeddings[i] = Some(embedding);
+        }
+        Ok(embeddings.into_iter().flatten().collect())
+    }
+}
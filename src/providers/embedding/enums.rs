@@ -1,36 +1,252 @@
 use super::LocalEmbeddingModel;
+use crate::core::{Config, EmbeddingProvider};
+use futures::future::try_join_all;
+use log::info;
 use rig::{
     embeddings::{Embedding, EmbeddingError, EmbeddingModel as RigEmbeddingModel},
+    providers::cohere::EmbeddingModel as CohereEmbeddingModel,
+    providers::gemini::embedding::EmbeddingModel as GeminiEmbeddingModel,
     providers::openai::EmbeddingModel as OpenAiEmbeddingModel,
     providers::xai::embedding::EmbeddingModel as XaiEmbeddingModel,
 };
 
 #[derive(Clone)]
 pub enum EmbeddingModelEnum {
-    Local { model: LocalEmbeddingModel },
-    OpenAI { model: OpenAiEmbeddingModel },
-    XAI { model: XaiEmbeddingModel },
+    Local {
+        model: LocalEmbeddingModel,
+    },
+    OpenAI {
+        model: OpenAiEmbeddingModel,
+    },
+    XAI {
+        model: XaiEmbeddingModel,
+    },
+    Cohere {
+        model: CohereEmbeddingModel,
+    },
+    // rig keeps the gemini model name private and only knows the dims of its own constants
+    Gemini {
+        model: GeminiEmbeddingModel,
+        name: String,
+        ndims: usize,
+    },
+    OpenAICompatible {
+        model: OpenAiEmbeddingModel,
+    },
+}
+
+impl EmbeddingModelEnum {
+    // number of batches embedded at the same time
+    pub const CONCURRENCY: usize = 4;
+
+    // builds the model picked by `embedding_provider`, falling back to the provider's default model
+    pub fn from_config(config: &Config) -> Result<Self, anyhow::Error> {
+        let (default_model, default_dims) = config
+            .embedding_provider
+            .default_model()
+            .map(|(model, dims)| (Some(model.to_string()), Some(dims)))
+            .unwrap_or((None, None));
+        let embedding_model_name = config
+            .embedding
+            .model
+            .clone()
+            .or(default_model)
+            .ok_or_else(|| anyhow::anyhow!("embedding.model not set"))?;
+        let embedding_dims = config.embedding.dimensions.or(default_dims);
+        Ok(match config.embedding_provider {
+            EmbeddingProvider::Local => {
+                let provider = LocalEmbeddingModel::new(&embedding_model_name).map_err(|e| {
+                    anyhow::anyhow!("Failed to initialize local embedding model: {}", e)
+                })?;
+                if let Some(dims) = config.embedding.dimensions {
+                    if dims != provider.ndims() {
+                        return Err(anyhow::anyhow!(
+                            "embedding.dimensions is {} but {} produces {} dimensions",
+                            dims,
+                            embedding_model_name,
+                            provider.ndims()
+                        ));
+                    }
+                }
+                info!(
+                    "[SETUP] Loaded Local Embedding Model: {}",
+                    provider.model_code
+                );
+                Self::Local { model: provider }
+            }
+            EmbeddingProvider::OpenAI => {
+                let api_key = config.providers.openai.api_key();
+                let client = rig::providers::openai::Client::new(api_key);
+                let provider = rig::providers::openai::EmbeddingModel::new(
+                    client,
+                    &embedding_model_name,
+                    embedding_dims
+                        .ok_or_else(|| anyhow::anyhow!("embedding.dimensions not set"))?,
+                );
+                info!(
+                    "[SETUP] Loaded OpenAI Embedding Model: {}",
+                    embedding_model_name
+                );
+                Self::OpenAI { model: provider }
+            }
+            EmbeddingProvider::XAI => {
+                let api_key = config.providers.xai.api_key();
+                let client = rig::providers::xai::Client::new(api_key);
+                let provider = rig::providers::xai::embedding::EmbeddingModel::new(
+                    client,
+                    &embedding_model_name,
+                    embedding_dims
+                        .ok_or_else(|| anyhow::anyhow!("embedding.dimensions not set"))?,
+                );
+                info!(
+                    "[SETUP] Loaded XAI Embedding Model: {}",
+                    embedding_model_name
+                );
+                Self::XAI { model: provider }
+            }
+            EmbeddingProvider::Cohere => {
+                let api_key = config.providers.cohere.api_key();
+                let client = rig::providers::cohere::Client::new(api_key);
+                let provider = rig::providers::cohere::EmbeddingModel::new(
+                    client,
+                    &embedding_model_name,
+                    // documents, `embed_text` switches to search_query for queries
+                    "search_document",
+                    embedding_dims
+                        .ok_or_else(|| anyhow::anyhow!("embedding.dimensions not set"))?,
+                );
+                info!(
+                    "[SETUP] Loaded Cohere Embedding Model: {}",
+                    embedding_model_name
+                );
+                Self::Cohere { model: provider }
+            }
+            EmbeddingProvider::Gemini => {
+                let api_key = config.providers.gemini.api_key();
+                let client = rig::providers::gemini::Client::new(api_key);
+                let ndims = embedding_dims
+                    .ok_or_else(|| anyhow::anyhow!("embedding.dimensions not set"))?;
+                let provider = rig::providers::gemini::embedding::EmbeddingModel::new(
+                    client,
+                    &embedding_model_name,
+                    Some(ndims),
+                );
+                info!(
+                    "[SETUP] Loaded Gemini Embedding Model: {}",
+                    embedding_model_name
+                );
+                Self::Gemini {
+                    model: provider,
+                    name: embedding_model_name.clone(),
+                    ndims,
+                }
+            }
+            EmbeddingProvider::OpenAICompatible => {
+                let api_key = config.providers.openai_compatible.api_key();
+                let base_url = config
+                    .embedding
+                    .base_url
+                    .clone()
+                    .ok_or_else(|| anyhow::anyhow!("embedding.base_url not set"))?;
+                let client = rig::providers::openai::Client::from_url(api_key, &base_url);
+                let provider = rig::providers::openai::EmbeddingModel::new(
+                    client,
+                    &embedding_model_name,
+                    embedding_dims
+                        .ok_or_else(|| anyhow::anyhow!("embedding.dimensions not set"))?,
+                );
+                info!(
+                    "[SETUP] Loaded OpenAI Compatible Embedding Model: {} ({})",
+                    embedding_model_name, base_url
+                );
+                Self::OpenAICompatible { model: provider }
+            }
+        })
+    }
+
+    pub fn model_id(&self) -> String {
+        match self {
+            Self::Local { model } => format!("local/{}", model.model_code),
+            Self::OpenAI { model } => format!("openai/{}", model.model),
+            Self::XAI { model } => format!("xai/{}", model.model),
+            Self::Cohere { model } => format!("cohere/{}", model.model),
+            Self::Gemini { name, .. } => format!("gemini/{}", name),
+            Self::OpenAICompatible { model } => format!("openai_compatible/{}", model.model),
+        }
+    }
+
+    pub fn batch_size(&self) -> usize {
+        match self {
+            Self::Local { .. } => LocalEmbeddingModel::MAX_DOCUMENTS,
+            Self::OpenAI { .. } => OpenAiEmbeddingModel::MAX_DOCUMENTS,
+            Self::XAI { .. } => XaiEmbeddingModel::MAX_DOCUMENTS,
+            Self::Cohere { .. } => CohereEmbeddingModel::MAX_DOCUMENTS,
+            // embedContent merges every part into a single embedding, so send one text per request
+            Self::Gemini { .. } => 1,
+            Self::OpenAICompatible { .. } => OpenAiEmbeddingModel::MAX_DOCUMENTS,
+        }
+    }
+
+    async fn embed_batch(&self, texts: Vec<String>) -> Result<Vec<Embedding>, EmbeddingError> {
+        match self {
+            Self::Local { model } => model.embed_texts(texts).await,
+            Self::OpenAI { model } => model.embed_texts(texts).await,
+            Self::XAI { model } => model.embed_texts(texts).await,
+            Self::Cohere { model } => model.embed_texts(texts).await,
+            Self::Gemini { model, .. } => model.embed_texts(texts).await,
+            Self::OpenAICompatible { model } => model.embed_texts(texts).await,
+        }
+    }
 }
 
 impl RigEmbeddingModel for EmbeddingModelEnum {
-    const MAX_DOCUMENTS: usize = 1;
+    // the largest batch any variant accepts, `embed_texts` splits it per variant
+    const MAX_DOCUMENTS: usize = 1024;
 
     fn ndims(&self) -> usize {
         match self {
             Self::Local { model } => model.ndims(),
             Self::OpenAI { model } => model.ndims(),
             Self::XAI { model } => model.ndims(),
+            Self::Cohere { model } => model.ndims(),
+            Self::Gemini { ndims, .. } => *ndims,
+            Self::OpenAICompatible { model } => model.ndims(),
         }
     }
 
+    // rig only embeds search queries through `embed_text`, cohere wants those marked as queries
+    async fn embed_text(&self, text: &str) -> Result<Embedding, EmbeddingError> {
+        let embeddings = match self {
+            Self::Cohere { model } => {
+                let mut model = model.clone();
+                model.input_type = "search_query".to_string();
+                model.embed_texts(vec![text.to_string()]).await?
+            }
+            _ => self.embed_texts(vec![text.to_string()]).await?,
+        };
+        embeddings
+            .into_iter()
+            .next()
+            .ok_or_else(|| EmbeddingError::ResponseError("No embedding returned".to_string()))
+    }
+
     async fn embed_texts(
         &self,
         texts: impl IntoIterator<Item = String> + Send,
     ) -> Result<Vec<Embedding>, EmbeddingError> {
-        match self {
-            Self::Local { model } => model.embed_texts(texts).await,
-            Self::OpenAI { model } => model.embed_texts(texts).await,
-            Self::XAI { model } => model.embed_texts(texts).await,
+        let texts = texts.into_iter().collect::<Vec<_>>();
+        let batches = texts
+            .chunks(self.batch_size())
+            .map(|batch| batch.to_vec())
+            .collect::<Vec<_>>();
+
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for group in batches.chunks(Self::CONCURRENCY) {
+            let results =
+                try_join_all(group.iter().map(|batch| self.embed_batch(batch.clone()))).await?;
+            embeddings.extend(results);
         }
+
+        Ok(embeddings.into_iter().flatten().collect())
     }
 }
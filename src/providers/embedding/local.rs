@@ -1,20 +1,33 @@
 use async_trait::async_trait;
-use fastembed::{EmbeddingModel as FastEmbedModel, InitOptions, TextEmbedding};
+use fastembed::{InitOptions, TextEmbedding};
 use std::sync::Arc;
 use std::{future::Future, pin::Pin};
 
 #[derive(Clone)]
 pub struct EmbeddingModel {
     model: Arc<TextEmbedding>,
+    pub model_code: String,
+    ndims: usize,
 }
 
 impl EmbeddingModel {
-    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
+    // accepts either the model code (`BAAI/bge-small-en-v1.5`) or the fastembed variant name (`BGESmallENV15`)
+    pub fn new(model_name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let model_info = TextEmbedding::list_supported_models()
+            .into_iter()
+            .find(|info| {
+                info.model_code.eq_ignore_ascii_case(model_name)
+                    || format!("{:?}", info.model).eq_ignore_ascii_case(model_name)
+            })
+            .ok_or_else(|| format!("Unknown fastembed model: {model_name}"))?;
+
         let model = TextEmbedding::try_new(
-            InitOptions::new(FastEmbedModel::AllMiniLML6V2).with_show_download_progress(true),
+            InitOptions::new(model_info.model).with_show_download_progress(true),
         )?;
         Ok(Self {
             model: Arc::new(model),
+            model_code: model_info.model_code,
+            ndims: model_info.dim,
         })
     }
 }
@@ -24,7 +37,7 @@ impl rig::embeddings::EmbeddingModel for EmbeddingModel {
     const MAX_DOCUMENTS: usize = 256;
 
     fn ndims(&self) -> usize {
-        384
+        self.ndims
     }
 
     #[allow(refining_impl_trait)]
@@ -45,12 +58,21 @@ impl rig::embeddings::EmbeddingModel for EmbeddingModel {
         let model = self.model.clone();
 
         Box::pin(async move {
-            let embeddings = model.embed(texts, None).map_err(|e| {
-                rig::embeddings::EmbeddingError::ProviderError(format!(
-                    "Local Embedding error: {}",
-                    e
-                ))
-            })?;
+            // inference is blocking CPU work, kept off the runtime's worker threads
+            let embeddings = tokio::task::spawn_blocking(move || model.embed(texts, None))
+                .await
+                .map_err(|e| {
+                    rig::embeddings::EmbeddingError::ProviderError(format!(
+                        "Local Embedding task failed: {}",
+                        e
+                    ))
+                })?
+                .map_err(|e| {
+                    rig::embeddings::EmbeddingError::ProviderError(format!(
+                        "Local Embedding error: {}",
+                        e
+                    ))
+                })?;
             Ok(embeddings
                 .into_iter()
                 .map(|e| rig::embeddings::Embedding {
@@ -1,5 +1,7 @@
+pub mod cache;
 pub mod enums;
 pub mod local;
 
+pub use cache::{CachedEmbeddingModel, EmbeddingCache};
 pub use enums::*;
 pub use local::EmbeddingModel as LocalEmbeddingModel;
@@ -1,4 +1,6 @@
 pub mod completion;
 pub mod elevenlabs;
 pub mod embedding;
+pub mod openai;
+pub mod rerank;
 pub mod truth;
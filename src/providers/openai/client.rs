@@ -0,0 +1,25 @@
+use reqwest::{header, Client as ReqwestClient, ClientBuilder as ReqwestClientBuilder};
+
+// the openai endpoints rig does not cover
+#[derive(Clone)]
+pub struct Client {
+    pub http_client: ReqwestClient,
+}
+
+impl Client {
+    pub const BASE_URL: &str = "https://api.openai.com";
+
+    pub fn new(api_key: String) -> Result<Self, anyhow::Error> {
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            header::HeaderValue::try_from(format!("Bearer {}", api_key))?,
+        );
+
+        let http_client = ReqwestClientBuilder::new()
+            .default_headers(headers)
+            .build()?;
+
+        Ok(Self { http_client })
+    }
+}
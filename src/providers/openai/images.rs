@@ -0,0 +1,58 @@
+use super::Client;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize)]
+pub struct ImageRequest {
+    pub prompt: String,
+    pub model: String,
+    pub size: String,
+    response_format: &'static str,
+    n: u8,
+}
+
+impl ImageRequest {
+    pub fn new(prompt: String, model: Option<String>, size: Option<String>) -> Self {
+        Self {
+            prompt,
+            model: model.unwrap_or_else(|| "dall-e-3".to_string()),
+            size: size.unwrap_or_else(|| "1024x1024".to_string()),
+            response_format: "b64_json",
+            n: 1,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ImageResponse {
+    data: Vec<ImageData>,
+}
+
+#[derive(Deserialize)]
+struct ImageData {
+    b64_json: String,
+}
+
+impl Client {
+    // the generated png
+    pub async fn generate_image(&self, request: ImageRequest) -> Result<Vec<u8>, anyhow::Error> {
+        let url = format!("{}/v1/images/generations", Self::BASE_URL);
+
+        let response = self
+            .http_client
+            .post(&url)
+            .json(&request)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<ImageResponse>()
+            .await?;
+
+        let image = response
+            .data
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("no image generated"))?;
+        Ok(STANDARD.decode(image.b64_json)?)
+    }
+}
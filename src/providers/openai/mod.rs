@@ -0,0 +1,4 @@
+pub mod client;
+pub mod images;
+
+pub use client::*;
@@ -0,0 +1,41 @@
+use fastembed::{RerankInitOptions, TextRerank};
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct RerankModel {
+    model: Arc<TextRerank>,
+}
+
+impl RerankModel {
+    pub fn new(model_code: &str) -> Result<Self, anyhow::Error> {
+        let model_info = TextRerank::list_supported_models()
+            .into_iter()
+            .find(|info| info.model_code.eq_ignore_ascii_case(model_code))
+            .ok_or_else(|| anyhow::anyhow!("Unknown rerank model: {}", model_code))?;
+
+        let model = TextRerank::try_new(
+            RerankInitOptions::new(model_info.model).with_show_download_progress(true),
+        )?;
+        Ok(Self {
+            model: Arc::new(model),
+        })
+    }
+
+    // returns (document index, score) pairs ordered from most to least relevant
+    pub fn rerank(
+        &self,
+        query: &str,
+        documents: Vec<String>,
+    ) -> Result<Vec<(usize, f64)>, anyhow::Error> {
+        if documents.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let documents = documents.iter().map(|d| d.as_str()).collect::<Vec<_>>();
+        let results = self.model.rerank(query, documents, false, None)?;
+        Ok(results
+            .into_iter()
+            .map(|result| (result.index, result.score as f64))
+            .collect())
+    }
+}
@@ -0,0 +1,3 @@
+pub mod local;
+
+pub use local::RerankModel as LocalRerankModel;
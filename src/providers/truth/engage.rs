@@ -0,0 +1,71 @@
+use super::client::Client;
+use std::io::{Read, Write};
+
+impl Client {
+    pub async fn favourite(&mut self, id: String) -> Result<(), anyhow::Error> {
+        self.status_action(id, "favourite").await?;
+        Ok(())
+    }
+
+    // returns the id of the reblog
+    pub async fn reblog(&mut self, id: String) -> Result<Option<String>, anyhow::Error> {
+        let body = self.status_action(id, "reblog").await?;
+        Ok(serde_json::from_str::<serde_json::Value>(&body)
+            .ok()
+            .and_then(|status| status["id"].as_str().map(str::to_string)))
+    }
+
+    // `POST /api/v1/statuses/<id>/<action>`, returns the response body
+    async fn status_action(&mut self, id: String, action: &str) -> Result<String, anyhow::Error> {
+        let mut stream = self.clone().create_tls_stream()?;
+
+        let request = format!(
+            "POST /api/v1/statuses/{}/{} HTTP/1.1\r\n\
+            Host: truthsocial.com\r\n\
+            Accept: application/json, text/plain, */*\r\n\
+            Accept-Language: en-US,en;q=0.9\r\n\
+            Authorization: Bearer {}\r\n\
+            Cookie: {}\r\n\
+            Origin: https://truthsocial.com\r\n\
+            Referer: https://truthsocial.com/\r\n\
+            Sec-CH-UA: \"Google Chrome\";v=\"131\", \"Chromium\";v=\"131\", \"Not_A Brand\";v=\"24\"\r\n\
+            Sec-CH-UA-Mobile: ?0\r\n\
+            Sec-CH-UA-Platform: \"Windows\"\r\n\
+            Sec-Fetch-Dest: empty\r\n\
+            Sec-Fetch-Mode: cors\r\n\
+            Sec-Fetch-Site: same-origin\r\n\
+            User-Agent: Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36\r\n\
+            Content-Length: 0\r\n\
+            Connection: close\r\n\
+            \r\n",
+            id,
+            action,
+            self.access_token,
+            self.cookies.format()
+        );
+
+        stream.write_all(request.as_bytes())?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+
+        let response_str = String::from_utf8(response)?;
+
+        if !response_str.starts_with("HTTP/1.1 200") {
+            return Err(anyhow::anyhow!(
+                "Failed to {} status {}: {}",
+                action,
+                id,
+                response_str.lines().next().unwrap_or_default()
+            ));
+        }
+
+        let parsed_response = super::parse_tls_response(&response_str)?;
+
+        for (key, value) in parsed_response.cookies {
+            self.cookies.set(key, value);
+        }
+
+        Ok(parsed_response.body)
+    }
+}
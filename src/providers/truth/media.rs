@@ -0,0 +1,82 @@
+use super::client::Client;
+use std::io::{Read, Write};
+
+impl Client {
+    // uploads a file for `post`, returns its media id
+    pub async fn upload_media(
+        &mut self,
+        data: Vec<u8>,
+        file_name: String,
+        mime: String,
+        description: Option<String>,
+    ) -> Result<String, anyhow::Error> {
+        let mut stream = self.clone().create_tls_stream()?;
+
+        let boundary = format!("----WebKitFormBoundary{:016x}", rand::random::<u64>());
+        let mut body = Vec::new();
+        write!(
+            body,
+            "--{boundary}\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"{file_name}\"\r\n\
+            Content-Type: {mime}\r\n\
+            \r\n"
+        )?;
+        body.extend_from_slice(&data);
+        body.extend_from_slice(b"\r\n");
+        if let Some(description) = description {
+            write!(
+                body,
+                "--{boundary}\r\n\
+                Content-Disposition: form-data; name=\"description\"\r\n\
+                \r\n\
+                {description}\r\n"
+            )?;
+        }
+        write!(body, "--{boundary}--\r\n")?;
+
+        let request = format!(
+            "POST /api/v1/media HTTP/1.1\r\n\
+            Host: truthsocial.com\r\n\
+            Accept: application/json, text/plain, */*\r\n\
+            Accept-Language: en-US,en;q=0.9\r\n\
+            Authorization: Bearer {}\r\n\
+            Cookie: {}\r\n\
+            Content-Type: multipart/form-data; boundary={}\r\n\
+            Origin: https://truthsocial.com\r\n\
+            Referer: https://truthsocial.com/\r\n\
+            Sec-CH-UA: \"Google Chrome\";v=\"131\", \"Chromium\";v=\"131\", \"Not_A Brand\";v=\"24\"\r\n\
+            Sec-CH-UA-Mobile: ?0\r\n\
+            Sec-CH-UA-Platform: \"Windows\"\r\n\
+            Sec-Fetch-Dest: empty\r\n\
+            Sec-Fetch-Mode: cors\r\n\
+            Sec-Fetch-Site: same-origin\r\n\
+            User-Agent: Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36\r\n\
+            Content-Length: {}\r\n\
+            Connection: close\r\n\
+            \r\n",
+            self.access_token,
+            self.cookies.format(),
+            boundary,
+            body.len()
+        );
+
+        stream.write_all(request.as_bytes())?;
+        stream.write_all(&body)?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+
+        let response_str = String::from_utf8(response)?;
+
+        let parsed_response = super::parse_tls_response(&response_str)?;
+
+        for (key, value) in parsed_response.cookies {
+            self.cookies.set(key, value);
+        }
+
+        serde_json::from_str::<serde_json::Value>(&parsed_response.body)
+            .ok()
+            .and_then(|media| media["id"].as_str().map(str::to_string))
+            .ok_or_else(|| anyhow::anyhow!("Failed to upload media: {}", parsed_response.body))
+    }
+}
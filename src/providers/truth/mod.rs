@@ -1,7 +1,9 @@
 pub mod client;
 pub mod cookies;
+pub mod engage;
 pub mod feed;
 pub mod login;
+pub mod media;
 pub mod post;
 pub mod posts;
 pub mod profile;
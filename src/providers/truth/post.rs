@@ -2,11 +2,19 @@ use super::client::Client;
 use std::io::{Read, Write};
 
 impl Client {
-    pub async fn post(&mut self, content: String) -> Result<(), anyhow::Error> {
+    // `media_ids` come from `upload_media`, `quote_id` makes it a quote post
+    pub async fn post(
+        &mut self,
+        content: String,
+        media_ids: Vec<String>,
+        quote_id: Option<String>,
+    ) -> Result<Option<String>, anyhow::Error> {
         let mut stream = self.clone().create_tls_stream()?;
 
         let body = format!(
-            r#"{{"content_type":"text/plain","in_reply_to_id":"","media_ids":[],"poll":null,"quote_id":"","status":"{}","to":[],"visibility":"public","group_timeline_visible":false}}"#,
+            r#"{{"content_type":"text/plain","in_reply_to_id":"","media_ids":{},"poll":null,"quote_id":"{}","status":"{}","to":[],"visibility":"public","group_timeline_visible":false}}"#,
+            serde_json::to_string(&media_ids)?,
+            quote_id.unwrap_or_default(),
             content
         );
 
@@ -50,6 +58,11 @@ impl Client {
             self.cookies.set(key, value);
         }
 
-        Ok(())
+        // the id of the created status, the post went out even when it can't be read
+        Ok(
+            serde_json::from_str::<serde_json::Value>(&parsed_response.body)
+                .ok()
+                .and_then(|status| status["id"].as_str().map(str::to_string)),
+        )
     }
 }
@@ -7,7 +7,7 @@ impl Client {
         content: String,
         in_reply_to_id: String,
         reply_to_username: Option<String>,
-    ) -> Result<(), anyhow::Error> {
+    ) -> Result<Option<String>, anyhow::Error> {
         let mut stream = self.clone().create_tls_stream()?;
 
         let body = format!(
@@ -57,6 +57,11 @@ impl Client {
             self.cookies.set(key, value);
         }
 
-        Ok(())
+        // the id of the created status, the post went out even when it can't be read
+        Ok(
+            serde_json::from_str::<serde_json::Value>(&parsed_response.body)
+                .ok()
+                .and_then(|status| status["id"].as_str().map(str::to_string)),
+        )
     }
 }
@@ -100,3 +100,9 @@ pub struct PostMention {
     pub url: String,
     pub acct: String,
 }
+
+// the part of `GET /api/v1/statuses/<id>/context` replies need
+#[derive(Deserialize, Debug, Clone)]
+pub struct Context {
+    pub ancestors: Vec<Post>,
+}